@@ -0,0 +1,125 @@
+// Interactive shrink tree explorer:
+// > cargo run --example explore -- vec 3 50
+// generates a value from the named generator with the given seed and size, and reads commands
+// from stdin for walking around its shrink tree. Type "help" for the commands.
+use std::error::Error;
+use std::fmt::Debug;
+use std::io::{self, BufRead, Write};
+
+use hedgehog::gen::graph::GraphConfig;
+use hedgehog::hh3_lazy_tree::{Gen, Tree};
+use hedgehog::random::Random;
+use hedgehog::tree::render;
+use hedgehog::tree::zipper::TreeZipper;
+
+const HELP : &str = "commands:
+  ls       list the children of the current node
+  cd N     move to child N
+  up       move to the parent
+  next     move to the next sibling
+  prev     move to the previous sibling
+  top      move back to the root
+  p        print the current value
+  path     print the child indices from the root to here, eg \"3.0.7\"
+  dot N    print the tree below here as Graphviz DOT, N levels deep
+  json N   print the tree below here as JSON, N levels deep
+  q        quit";
+
+/// Most children to list, since some trees have a lot of them
+const MAX_LISTED : usize = 50;
+
+type Generate = fn(u128, usize) -> Tree<'static, String>;
+
+/// Run a generator, rendering the values with Debug so that every tree has the same type
+fn debug_tree<A : Debug + 'static>(gen : Gen<'static, A>, seed : u128, size : usize) -> Tree<'static, String> {
+    (*gen.run)(Random::new_from_seed(seed), size).map(|a| format!("{:?}", a))
+}
+
+fn generators() -> Vec<(&'static str, Generate)> {
+    vec![
+        ("u64", |seed, size| debug_tree(Gen::u64(0..1000), seed, size)),
+        ("vec", |seed, size| debug_tree(Gen::u64(0..100).vec(Gen::usize(0..20)), seed, size)),
+        ("string", |seed, size| debug_tree(Gen::string(Gen::char_ascii_alphanumeric(), Gen::usize(0..20)), seed, size)),
+        ("email", |seed, size| debug_tree(Gen::email(), seed, size)),
+        ("url", |seed, size| debug_tree(Gen::url(), seed, size)),
+        ("semver", |seed, size| debug_tree(Gen::semver(), seed, size)),
+        ("dag", |seed, size| debug_tree(Gen::graph_dag(&GraphConfig::default()), seed, size)),
+        ("matrix", |seed, size| debug_tree(Gen::matrix(Gen::usize(1..4), Gen::usize(1..4), Gen::u64(0..10)), seed, size)),
+    ]
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args : Vec<String> = std::env::args().skip(1).collect();
+    let names : Vec<&str> = generators().iter().map(|(name, _)| *name).collect();
+    let generate = match args.first().and_then(|arg| generators().into_iter().find(|(name, _)| name == arg)) {
+        Some((_, generate)) => generate,
+        None => {
+            eprintln!("usage: cargo run --example explore -- <generator> [seed] [size]");
+            eprintln!("generators: {}", names.join(", "));
+            std::process::exit(1);
+        }
+    };
+    let seed = args.get(1).map(|s| s.parse()).transpose()?.unwrap_or(0);
+    let size = args.get(2).map(|s| s.parse()).transpose()?.unwrap_or(10);
+
+    let mut z = TreeZipper::new(generate(seed, size));
+    println!("{}", z.value());
+
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let words : Vec<&str> = line.split_whitespace().collect();
+        let here = z.focus().clone();
+        let number = |default : usize| words.get(1).map_or(Ok(default), |w| w.parse::<usize>());
+
+        match words.first().copied() {
+            None => {}
+            Some("ls") => {
+                let mut children = (*here.children)();
+                for (ix, child) in children.by_ref().take(MAX_LISTED).enumerate() {
+                    println!("{:4}  {}", ix, child.value);
+                }
+                if children.next().is_some() {
+                    println!("  ...");
+                }
+            }
+            Some("cd") => match number(0) {
+                Ok(ix) if z.down(ix) => println!("{}", z.value()),
+                _ => println!("no such child"),
+            },
+            Some("up") => {
+                z.up();
+                println!("{}", z.value());
+            }
+            Some("next") | Some("prev") => {
+                let moved = if words[0] == "next" { z.right() } else { z.left() };
+                if moved {
+                    println!("{}", z.value());
+                } else {
+                    println!("no such sibling");
+                }
+            }
+            Some("top") => {
+                z.top();
+                println!("{}", z.value());
+            }
+            Some("p") => println!("{}", here.value),
+            Some("path") => println!("\"{}\"", z.path()),
+            Some("dot") => match number(2) {
+                Ok(depth) => print!("{}", render::to_dot(&here, depth)),
+                Err(err) => println!("bad depth: {}", err),
+            },
+            Some("json") => match number(2) {
+                Ok(depth) => println!("{}", render::to_json(&here, depth)),
+                Err(err) => println!("bad depth: {}", err),
+            },
+            Some("q") | Some("quit") => return Ok(()),
+            Some(_) => println!("{}", HELP),
+        }
+    }
+}
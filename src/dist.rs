@@ -0,0 +1,150 @@
+// Discrete probability distributions, in the same style as NonDet: each choice has a probability,
+// and combining distributions runs the closure once for every combination of choices, multiplying
+// the probabilities of the choices made along the way. The result is the exact distribution of the
+// closure's result, rather than an estimate from sampling.
+// > let dice = Dist::combine(|c| c.of(Dist::uniform(vec![1, 2, 3, 4, 5, 6])) + c.of(Dist::uniform(vec![1, 2, 3, 4, 5, 6])));
+// > dice.probability_of(|&x| x == 7) // 1/6
+use std::rc::Rc;
+
+use crate::nondet::{Combinations, Witness};
+use crate::nonempty::NonEmpty;
+
+/// A probability distribution over a finite number of outcomes.
+/// The closure returns every outcome with its probability; the probabilities are positive and add up
+/// to one. The same value can be more than one outcome, eg when different combinations of choices
+/// give the same result.
+pub struct Dist<'a, A> {
+    pub run : Rc<dyn Fn() -> NonEmpty<(f64, A)> + 'a>
+}
+
+/// Witness for extracting values from distributions, which keeps track of the probability of the
+/// choices made so far as well as which choices to make
+pub struct DistWitness<'w> {
+    witness : &'w mut Witness,
+    probability : f64,
+}
+
+impl<'a, A> Dist<'a, A> {
+    /// Distribution with the given relative weights. Outcomes with zero weight are left out, since
+    /// they can never happen.
+    pub fn weighted(values : Vec<(f64, A)>) -> Dist<'a, A>
+    where A : Clone + 'a {
+        assert!(values.iter().all(|(w, _)| *w >= 0.0), "Dist::weighted: weights can't be negative");
+        let total : f64 = values.iter().map(|(w, _)| w).sum();
+        let mut outcomes = values.into_iter().filter(|(w, _)| *w > 0.0).map(|(w, a)| (w / total, a));
+        let zero = outcomes.next().expect("Dist::weighted: needs at least one value with positive weight");
+        let outcomes = NonEmpty { zero, vec : outcomes.collect() };
+        Dist { run : Rc::new(move || outcomes.clone()) }
+    }
+
+    /// Every value is equally likely
+    pub fn uniform(values : Vec<A>) -> Dist<'a, A>
+    where A : Clone + 'a {
+        Dist::weighted(values.into_iter().map(|a| (1.0, a)).collect())
+    }
+
+    /// A single outcome that always happens
+    pub fn certain(value : A) -> Dist<'a, A>
+    where A : Clone + 'a {
+        Dist::weighted(vec![(1.0, value)])
+    }
+
+    /// Combine together multiple distributions.
+    /// The probability of each result is the product of the probabilities of the choices that led to
+    /// it, so the closure should only make choices through the witness.
+    pub fn combine<F>(f : F) -> Dist<'a, A>
+    where F : Fn(&mut DistWitness) -> A + 'a,
+          A : 'a {
+        let f = Rc::new(f);
+        Dist {
+            run : Rc::new(move || {
+                let f = f.clone();
+                let mut outcomes = Combinations::new(move |w : &mut Witness| {
+                    let mut c = DistWitness { witness : w, probability : 1.0 };
+                    let value = f(&mut c);
+                    (c.probability, value)
+                });
+                let zero = outcomes.next().expect("Dist::combine: there's always a first combination");
+                NonEmpty { zero, vec : outcomes.collect() }
+            })
+        }
+    }
+
+    /// Outcomes and their probabilities
+    pub fn outcomes(&self) -> Vec<(f64, A)> {
+        (*self.run)().to_vec()
+    }
+
+    /// Expected value of a function of the outcome
+    pub fn expectation<F>(&self, f : F) -> f64
+    where F : Fn(&A) -> f64 {
+        self.outcomes().iter().map(|(p, a)| p * f(a)).sum()
+    }
+
+    /// Probability that the outcome satisfies the predicate
+    pub fn probability_of<F>(&self, predicate : F) -> f64
+    where F : Fn(&A) -> bool {
+        self.outcomes().iter().filter(|(_, a)| predicate(a)).map(|(p, _)| p).sum()
+    }
+
+    /// Each distinct value that can happen, with its total probability, in the order the values are
+    /// first found
+    pub fn support(&self) -> Vec<(f64, A)>
+    where A : PartialEq {
+        let mut support : Vec<(f64, A)> = Vec::new();
+        for (p, a) in self.outcomes() {
+            match support.iter_mut().find(|(_, b)| *b == a) {
+                Some((q, _)) => *q += p,
+                None => support.push((p, a)),
+            }
+        }
+        support
+    }
+}
+
+impl<'w> DistWitness<'w> {
+    /// Extract a value from a distribution, and multiply in its probability
+    pub fn of<'a, A>(&mut self, d : Dist<'a, A>) -> A
+    where A : Clone {
+        let outcomes = (*d.run)();
+        let (p, value) = self.witness.choose(&outcomes);
+        self.probability *= p;
+        value
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::dist::*;
+
+    fn close(a : f64, b : f64) -> bool {
+        (a - b).abs() < 1e-12
+    }
+
+    #[test]
+    fn dice() {
+        let die = || Dist::uniform((1..=6).collect::<Vec<u64>>());
+        let two = Dist::combine(move |c| c.of(die()) + c.of(die()));
+        assert_eq!(two.outcomes().len(), 36);
+        assert!(close(two.probability_of(|&x| x == 7), 1.0 / 6.0));
+        assert!(close(two.expectation(|&x| x as f64), 7.0));
+
+        let support = two.support();
+        assert_eq!(support.iter().map(|(_, x)| *x).collect::<Vec<_>>(), (2..=12).collect::<Vec<_>>());
+        assert!(close(support.iter().map(|(p, _)| p).sum(), 1.0));
+        assert!(close(support[0].0, 1.0 / 36.0));
+    }
+
+    #[test]
+    fn weights_multiply() {
+        // Only flip the biased coin if the fair one comes up heads
+        let fair = || Dist::uniform(vec![true, false]);
+        let biased = || Dist::weighted(vec![(3.0, true), (1.0, false), (0.0, true)]);
+        let both = Dist::combine(move |c| c.of(fair()) && c.of(biased()));
+        assert!(close(both.probability_of(|&b| b), 0.375));
+        // The tails path doesn't flip the second coin, so it's a single outcome
+        assert_eq!(both.outcomes().len(), 3);
+        assert_eq!(Dist::certain(5).support(), vec![(1.0, 5)]);
+    }
+}
@@ -0,0 +1,26 @@
+// Generators for more specific types, on top of the primitives in hh3_lazy_tree.
+// These are all inherent methods on Gen, so are used the same way, eg Gen::char_ascii().
+pub mod text;
+pub mod bytes;
+pub mod collections;
+pub mod time;
+pub mod net;
+pub mod net_text;
+pub mod decimal;
+pub mod matrix;
+pub mod markov;
+pub mod path;
+pub mod variant;
+pub mod function;
+pub mod wrapper;
+pub mod graph;
+pub mod invalid;
+
+#[cfg(feature = "regex-syntax")]
+pub mod regex;
+
+#[cfg(feature = "uuid")]
+pub mod uuid;
+
+#[cfg(feature = "serde_json")]
+pub mod json;
@@ -0,0 +1,81 @@
+use crate::hh3_lazy_tree::{Gen, Tree};
+
+/// Byte sequences that tend to find bugs in parsers and protocol code
+pub fn default_dictionary() -> Vec<Vec<u8>> {
+    vec![
+        // Boundaries of a single byte
+        vec![0x00], vec![0x01], vec![0x7F], vec![0x80], vec![0xFF],
+        // UTF-8: byte order mark, overlong encoding of NUL, encoded surrogate, largest scalar value,
+        // and a truncated multi-byte sequence
+        vec![0xEF, 0xBB, 0xBF], vec![0xC0, 0x80], vec![0xED, 0xA0, 0x80], vec![0xF4, 0x8F, 0xBF, 0xBF], vec![0xE2, 0x82],
+        // Length prefixes: zero, one, and maximum in little and big endian
+        vec![0x00, 0x00, 0x00, 0x00], vec![0x01, 0x00, 0x00, 0x00], vec![0x00, 0x00, 0x00, 0x01],
+        vec![0xFF, 0xFF, 0xFF, 0xFF], vec![0xFF, 0xFF, 0xFF, 0x7F], vec![0x7F, 0xFF, 0xFF, 0xFF],
+        // Separators and escapes
+        b"\r\n".to_vec(), b"\"".to_vec(), b"\\".to_vec(), b"%s".to_vec(),
+    ]
+}
+
+impl<'a> Gen<'a, Vec<u8>> {
+    /// Byte buffers with length from len_gen, mixing random bytes with entries from the default
+    /// dictionary of interesting values.
+    pub fn bytes(len_gen : Gen<'a, usize>) -> Gen<'a, Vec<u8>> {
+        Gen::bytes_with_dictionary(len_gen, default_dictionary())
+    }
+
+    /// Byte buffers with length from len_gen, where roughly one chunk in four is an entry from the
+    /// dictionary and the rest are random bytes. Dictionary entries are truncated if they don't fit.
+    /// Shrinking removes chunks, as long as the buffer stays at least as long as len_gen's minimum,
+    /// and shrinks the random bytes toward zero; dictionary entries are kept whole so they stay
+    /// interesting.
+    pub fn bytes_with_dictionary(len_gen : Gen<'a, usize>, dictionary : Vec<Vec<u8>>) -> Gen<'a, Vec<u8>> {
+        Gen::new(move |mut r, s| {
+            let len_tree = (*len_gen.run)(r.split(), s);
+            let min_len = len_tree.first_shrink_leaf();
+            let len = len_tree.value;
+            // All the random bytes there could be room for, filled at once rather than one at a time
+            let mut random = vec![0; len];
+            r.fill_bytes(&mut random);
+            let mut chunks : Vec<Tree<'a, Vec<u8>>> = Vec::new();
+            let mut total = 0;
+            while total < len {
+                let chunk = if !dictionary.is_empty() && r.u64_range(0..4) == 0 {
+                    let mut entry = dictionary[r.u64_range(0..dictionary.len() as u64) as usize].clone();
+                    entry.truncate(len - total);
+                    Tree::leaf(entry)
+                } else {
+                    Gen::shrink_u128(0, random[total] as u128).map(|b| vec![b as u8])
+                };
+                total += chunk.value.len();
+                chunks.push(chunk);
+            }
+            // Chunks can be longer than one byte, so bound the concatenated length rather than the
+            // number of chunks
+            Tree::interleave(chunks, 0).map(|v| v.concat()).filter(move |v| v.len() >= min_len)
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::Gen;
+    use crate::property::Property;
+    use crate::random::Random;
+
+    #[test]
+    fn uses_dictionary() {
+        let gen = Gen::bytes_with_dictionary(Gen::usize(10..20), vec![b"MAGIC".to_vec()]);
+        let mut found = false;
+        for seed in 0..20 {
+            let v = (*gen.run)(Random::new_from_seed(seed), 0).value;
+            assert!((10..20).contains(&v.len()));
+            found |= v.windows(5).any(|w| w == b"MAGIC");
+        }
+        assert!(found);
+
+        Property::check_shrink(Gen::bytes_with_dictionary(Gen::usize(0..20), vec![]), |v| v.iter().all(|b| *b < 100), vec![100]);
+        let gen = Gen::bytes_with_dictionary(Gen::usize(10..20), vec![b"MAGIC".to_vec()]);
+        Property::check_shrink(gen, |v| v.iter().all(|b| *b < 100), b"\x00\x64MAGICMAG".to_vec());
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, LinkedList, VecDeque};
+use std::convert::TryInto;
+use std::ops::Range;
+use std::hash::Hash;
+use std::rc::Rc;
+
+use crate::hh3_lazy_tree::{Gen, Tree, FILTER_RETRIES};
+use crate::nonempty::NonEmpty;
+use crate::random::Random;
+
+impl<'a, A> Gen<'a, A> {
+    /// Fixed-size arrays, eg Gen::u8(0..255).array::<32>().
+    /// The length never changes, so shrinking only simplifies each element in turn.
+    pub fn array<const N : usize>(self) -> Gen<'a, [A; N]>
+    where A : 'a + Clone {
+        Gen::new(move |mut r, s| {
+            let trees = (0..N).map(|_| (*self.run)(r.split(), s)).collect();
+            Tree::interleave(trees, N).map(|v : Vec<A>| {
+                v.try_into().unwrap_or_else(|_| unreachable!("Gen::array: length is always N"))
+            })
+        })
+    }
+}
+
+impl<'a, A> Gen<'a, Vec<A>> {
+    /// Run each generator in turn and collect the results, eg for a record where each field needs a
+    /// differently configured generator.
+    /// The length is fixed, so shrinking only simplifies each element in turn.
+    /// > Gen::collect(vec![Gen::u64(0..10), Gen::u64(100..200), Gen::constant(7)])
+    pub fn collect<I>(gens : I) -> Gen<'a, Vec<A>>
+    where I : IntoIterator<Item = Gen<'a, A>>,
+    A : 'a + Clone {
+        let gens : Vec<Gen<'a, A>> = gens.into_iter().collect();
+        let gens_enum = gens.clone();
+        Gen::new(move |mut r, s| {
+            let trees = gens.iter().map(|g| (*g.run)(r.split(), s)).collect();
+            Tree::interleave(trees, gens.len())
+        }).with_enumerate(move |limit| {
+            // Every combination, with the first generator changing slowest
+            let mut values : Vec<Vec<A>> = vec![Vec::new()];
+            for gen in &gens_enum {
+                let elems = (*gen.enumerate)(limit)?.to_vec();
+                if values.len() * elems.len() > limit {
+                    return None;
+                }
+                values = values.iter()
+                    .flat_map(|v| elems.iter().map(move |e| {
+                        let mut v = v.clone();
+                        v.push(e.clone());
+                        v
+                    }))
+                    .collect();
+            }
+            let mut values = values.into_iter();
+            Some(NonEmpty { zero : values.next()?, vec : values.collect() })
+        })
+    }
+}
+
+impl<'a, K, V> Gen<'a, HashMap<K, V>> {
+    /// Maps with len_gen entries. Keys that collide with earlier ones are regenerated, up to
+    /// FILTER_RETRIES times in total, so the map is smaller than requested only if the key domain is.
+    /// Shrinks the same way as Gen::vec_unique_by on the keys, so shrinks never make two keys
+    /// equal and never go below len_gen's minimum.
+    pub fn hash_map(key_gen : Gen<'a, K>, val_gen : Gen<'a, V>, len_gen : Gen<'a, usize>) -> Gen<'a, HashMap<K, V>>
+    where K : 'a + Clone + Eq + Hash,
+    V : 'a + Clone {
+        Gen::entries(key_gen, val_gen, len_gen).map(|kvs| kvs.into_iter().collect())
+    }
+}
+
+impl<'a, K, V> Gen<'a, BTreeMap<K, V>> {
+    /// Ordered maps; see Gen::hash_map
+    pub fn btree_map(key_gen : Gen<'a, K>, val_gen : Gen<'a, V>, len_gen : Gen<'a, usize>) -> Gen<'a, BTreeMap<K, V>>
+    where K : 'a + Clone + Ord,
+    V : 'a + Clone {
+        Gen::entries(key_gen, val_gen, len_gen).map(|kvs| kvs.into_iter().collect())
+    }
+}
+
+impl<'a, K, V> Gen<'a, Vec<(K, V)>> {
+    /// Key-value pairs with distinct keys, for building maps
+    fn entries(key_gen : Gen<'a, K>, val_gen : Gen<'a, V>, len_gen : Gen<'a, usize>) -> Gen<'a, Vec<(K, V)>>
+    where K : 'a + Clone + PartialEq,
+    V : 'a + Clone {
+        key_gen.zip(val_gen).vec_unique_by(len_gen, |(k, _)| k.clone())
+    }
+}
+
+impl<'a, A> Gen<'a, HashSet<A>> {
+    /// Sets with len_gen elements. Elements that collide with earlier ones are regenerated, up to
+    /// FILTER_RETRIES times in total, so the set is smaller than requested only if the domain is.
+    /// Shrinks the same way as Gen::vec_unique, so shrinks never make two elements equal and
+    /// never go below len_gen's minimum.
+    pub fn hash_set(elem_gen : Gen<'a, A>, len_gen : Gen<'a, usize>) -> Gen<'a, HashSet<A>>
+    where A : 'a + Clone + Eq + Hash {
+        elem_gen.vec_unique(len_gen).map(|v| v.into_iter().collect())
+    }
+}
+
+impl<'a, A> Gen<'a, BTreeSet<A>> {
+    /// Ordered sets; see Gen::hash_set
+    pub fn btree_set(elem_gen : Gen<'a, A>, len_gen : Gen<'a, usize>) -> Gen<'a, BTreeSet<A>>
+    where A : 'a + Clone + Ord {
+        elem_gen.vec_unique(len_gen).map(|v| v.into_iter().collect())
+    }
+}
+
+impl<'a, A> Gen<'a, A> {
+    /// Lists of distinct elements; see Gen::vec_unique_by
+    pub fn vec_unique(self, len_gen : Gen<'a, usize>) -> Gen<'a, Vec<A>>
+    where A : 'a + Clone + PartialEq {
+        self.vec_unique_by(len_gen, A::clone)
+    }
+
+    /// Lists in ascending order, possibly with repeated elements, eg for testing binary search.
+    /// Shrinks the same way as Gen::vec, re-sorting after each shrink.
+    pub fn vec_sorted(self, len_gen : Gen<'a, usize>) -> Gen<'a, Vec<A>>
+    where A : 'a + Clone + Ord {
+        self.vec(len_gen).map(|mut v| {
+            v.sort();
+            v
+        })
+    }
+
+    /// Lists in strictly ascending order, eg for testing interval code.
+    /// Shrinks the same way as Gen::vec_unique, re-sorting after each shrink.
+    pub fn vec_strictly_increasing(self, len_gen : Gen<'a, usize>) -> Gen<'a, Vec<A>>
+    where A : 'a + Clone + Ord {
+        self.vec_unique(len_gen).map(|mut v| {
+            v.sort();
+            v
+        })
+    }
+
+    /// Lists where no two elements have the same key.
+    /// Elements with keys that collide with earlier ones are regenerated, up to FILTER_RETRIES times
+    /// in total, so the list is shorter than requested only if there aren't enough distinct keys.
+    /// Shrinks the same way as Gen::vec, except that shrinks with duplicate keys are skipped.
+    pub fn vec_unique_by<K, F>(self, len_gen : Gen<'a, usize>, key : F) -> Gen<'a, Vec<A>>
+    where K : PartialEq,
+    F : Fn(&A) -> K + 'a,
+    A : 'a + Clone {
+        let key = Rc::new(key);
+        Gen::new(move |mut r, s| {
+            let len_tree = (*len_gen.run)(r.split(), s);
+            let min_len = len_tree.first_shrink_leaf();
+            let trees = distinct_trees(&self, &mut r, s, len_tree.value, &*key);
+            let key = key.clone();
+            let unique = move |v : &Vec<A>| {
+                let keys : Vec<K> = v.iter().map(|a| key(a)).collect();
+                keys.iter().enumerate().all(|(i, k)| !keys[..i].contains(k))
+            };
+            Tree::interleave(trees, min_len).filter(unique)
+        })
+    }
+}
+
+impl<'a, A> Gen<'a, A> {
+    /// Double-ended queues, front to back in generated order; shrinks the same way as Gen::vec
+    pub fn vec_deque(self, len_gen : Gen<'a, usize>) -> Gen<'a, VecDeque<A>>
+    where A : 'a + Clone {
+        self.vec(len_gen).map(VecDeque::from)
+    }
+
+    /// Linked lists, front to back in generated order; shrinks the same way as Gen::vec
+    pub fn linked_list(self, len_gen : Gen<'a, usize>) -> Gen<'a, LinkedList<A>>
+    where A : 'a + Clone {
+        self.vec(len_gen).map(|v| v.into_iter().collect())
+    }
+
+    /// Max-heaps; shrinks the same way as Gen::vec
+    pub fn binary_heap(self, len_gen : Gen<'a, usize>) -> Gen<'a, BinaryHeap<A>>
+    where A : 'a + Clone + Ord {
+        self.vec(len_gen).map(BinaryHeap::from)
+    }
+}
+impl<'a, A> Gen<'a, Vec<A>> {
+    /// Random permutations of the given list.
+    /// The permutation is a Fisher-Yates shuffle, stored as the list of transpositions it made.
+    /// Shrinking removes transpositions, so shrinks move back toward the original order.
+    pub fn shuffle(vec : Vec<A>) -> Gen<'a, Vec<A>>
+    where A : 'a + Clone {
+        Gen::new(move |mut r, _| {
+            let swaps : Vec<Tree<'a, (usize, usize)>> = (1..vec.len()).rev()
+                .map(|i| (i, r.u64_range(0..i as u64 + 1) as usize))
+                .filter(|(i, j)| i != j)
+                .map(Tree::leaf)
+                .collect();
+            let vec = vec.clone();
+            Tree::interleave(swaps, 0).map(move |swaps| {
+                let mut shuffled = vec.clone();
+                for (i, j) in swaps {
+                    shuffled.swap(i, j);
+                }
+                shuffled
+            })
+        })
+    }
+}
+
+impl<'a, A> Gen<'a, Vec<A>> {
+    /// Random subsequences of the given list, keeping the original order.
+    /// Each element is kept with probability one half; shrinks by removing kept elements.
+    pub fn subsequence(vec : Vec<A>) -> Gen<'a, Vec<A>>
+    where A : 'a + Clone {
+        Gen::new(move |mut r, _| {
+            let kept = vec.iter()
+                .filter(|_| r.bool())
+                .map(|a| Tree::leaf(a.clone()))
+                .collect();
+            Tree::interleave(kept, 0)
+        })
+    }
+}
+
+impl<'a, A> Gen<'a, BTreeSet<A>> {
+    /// Random subsets of the given set; see Gen::subsequence.
+    /// This takes an ordered set so that the same seed always gives the same subset.
+    pub fn subset(set : BTreeSet<A>) -> Gen<'a, BTreeSet<A>>
+    where A : 'a + Clone + Ord {
+        Gen::subsequence(set.into_iter().collect()).map(|v| v.into_iter().collect())
+    }
+}
+
+impl<'a> Gen<'a, Vec<usize>> {
+    /// Random orderings of the range; shrinks toward ascending order. See Gen::shuffle
+    pub fn permutation_of(range : Range<usize>) -> Gen<'a, Vec<usize>> {
+        Gen::shuffle(range.collect())
+    }
+}
+
+/// Generate up to len trees with distinct keys, retrying collisions up to FILTER_RETRIES times in total
+fn distinct_trees<'a, A, K, F>(gen : &Gen<'a, A>, r : &mut Random, s : usize, len : usize, key : F) -> Vec<Tree<'a, A>>
+where K : PartialEq,
+F : Fn(&A) -> K {
+    let mut trees : Vec<Tree<'a, A>> = Vec::new();
+    let mut keys : Vec<K> = Vec::new();
+    let mut retries = 0;
+    while trees.len() < len && retries <= FILTER_RETRIES {
+        let tree = (*gen.run)(r.split(), s);
+        let k = key(&tree.value);
+        if keys.contains(&k) {
+            retries += 1;
+        } else {
+            keys.push(k);
+            trees.push(tree);
+        }
+    }
+    trees
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+    use crate::hh3_lazy_tree::Gen;
+    use crate::property::{Config, Property};
+    use crate::random::Random;
+
+    #[test]
+    fn array_shrinks_elements() {
+        Property::check_shrink(Gen::u8(0..100).array::<4>(), |a| a.iter().all(|x| *x < 50), [0, 0, 0, 50]);
+    }
+
+    #[test]
+    fn collect_fixed_gens() {
+        let gen = Gen::collect(vec![Gen::u8(0..10), Gen::u8(100..200), Gen::constant(7)]);
+        assert!(gen.sample(0, 0, 100).iter().all(|v| v.len() == 3 && v[0] < 10 && v[1] >= 100 && v[2] == 7));
+        Property::check_shrink(gen.clone(), |v| v[0] + v[2] < 10, vec![3, 100, 7]);
+        assert_eq!((*gen.enumerate)(1000).map(|vs| vs.len()), Some(1000));
+    }
+
+    #[test]
+    fn maps_shrink_entries() {
+        let gen = Gen::btree_map(Gen::u8(0..10), Gen::u8(0..100), Gen::usize(0..8));
+        let minimal : BTreeMap<u8, u8> = vec![(0, 50)].into_iter().collect();
+        Property::check_shrink(gen, |m| m.values().all(|v| *v < 50), minimal);
+
+        let gen = Gen::hash_map(Gen::u8(0..4), Gen::constant(()), Gen::usize(0..20));
+        Property::check_shrink(gen, |m| m.len() < 4, vec![(0, ()), (1, ()), (2, ()), (3, ())].into_iter().collect());
+
+        let gen = Gen::btree_map(Gen::u8(0..100), Gen::u8(0..100), Gen::usize(5..10));
+        let minimal : BTreeMap<u8, u8> = vec![(0, 0), (1, 0), (2, 0), (3, 0), (4, 50)].into_iter().collect();
+        Property::check_shrink(gen, |m| m.values().all(|v| *v < 50), minimal);
+    }
+
+    #[test]
+    fn sets_have_requested_size() {
+        let gen = Gen::hash_set(Gen::u8(0..20), Gen::constant(10));
+        for seed in 0..20 {
+            assert_eq!((*gen.run)(Random::new_from_seed(seed), 10).value.len(), 10);
+        }
+        // Only 4 distinct values are possible
+        let gen = Gen::btree_set(Gen::u8(0..4), Gen::constant(10));
+        assert_eq!((*gen.run)(Random::new_from_seed(0), 10).value.len(), 4);
+
+        let gen : Gen<HashSet<u8>> = Gen::hash_set(Gen::u8(0..100), Gen::usize(0..10));
+        Property::check_shrink(gen, |s| s.len() < 3, vec![0, 1, 2].into_iter().collect());
+
+        let gen = Gen::btree_set(Gen::u8(0..100), Gen::usize(5..10));
+        Property::check_shrink(gen, |s| s.iter().all(|x| *x < 50), vec![0, 1, 2, 3, 50].into_iter().collect());
+    }
+
+    #[test]
+    fn other_collections() {
+        let deque = (*Gen::u8(0..10).vec_deque(Gen::constant(5)).run)(Random::new_from_seed(1), 10).value;
+        let list = (*Gen::u8(0..10).linked_list(Gen::constant(5)).run)(Random::new_from_seed(1), 10).value;
+        let mut heap = (*Gen::u8(0..10).binary_heap(Gen::constant(5)).run)(Random::new_from_seed(1), 10).value;
+        assert!(deque.iter().eq(list.iter()));
+        assert_eq!(heap.len(), 5);
+        assert_eq!(heap.pop(), deque.iter().max().cloned());
+    }
+
+    #[test]
+    fn vec_unique_shrinks_stay_unique() {
+        let gen = Gen::u8(0..100).vec_unique_by(Gen::usize(0..10), |x| x / 10);
+        Property::check_shrink(gen, |v| v.len() < 3, vec![0, 10, 20]);
+        Property::check_shrink(Gen::u8(0..100).vec_unique(Gen::usize(0..10)), |v| v.len() < 3, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn shuffle_is_permutation() {
+        let gen = Gen::permutation_of(0..10);
+        for seed in 0..10 {
+            let mut v = (*gen.run)(Random::new_from_seed(seed), 0).value;
+            v.sort_unstable();
+            assert_eq!(v, (0..10).collect::<Vec<_>>());
+        }
+        // Shrinks to a single transposition
+        let report = Property::new(gen, |v| v.windows(2).all(|w| w[0] < w[1])).check(&Config::default());
+        assert_eq!(report.counterexamples[0].minimal.iter().zip(0..).filter(|(a, b)| **a != *b).count(), 2);
+    }
+
+    #[test]
+    fn subsequence_keeps_order() {
+        let gen = Gen::subsequence((0..20).collect());
+        for seed in 0..10 {
+            let v = (*gen.run)(Random::new_from_seed(seed), 0).value;
+            assert!(v.windows(2).all(|w| w[0] < w[1]));
+        }
+        Property::check_shrink(gen, |v| !v.contains(&7), vec![7]);
+
+        let set : BTreeSet<u8> = (0..10).collect();
+        Property::check_shrink(Gen::subset(set), |s| s.len() < 2, vec![8, 9].into_iter().collect());
+    }
+
+    #[test]
+    fn sorted_under_shrinking() {
+        let gen = Gen::i32(-100..100).vec_strictly_increasing(Gen::usize(0..10));
+        let report = Property::new(gen.clone(), |v| v.windows(2).all(|w| w[0] < w[1])).check(&Config::default());
+        assert!(report.is_success(), "{}", report);
+        Property::check_shrink(gen, |v| v.len() < 3, vec![-2, -1, 0]);
+        Property::check_shrink(Gen::u8(0..10).vec_sorted(Gen::usize(0..10)), |v| v.iter().sum::<u8>() < 10, vec![2, 4, 4]);
+    }
+}
@@ -0,0 +1,112 @@
+// Fixed-point decimals for testing financial arithmetic. Values are stored as an integer number of
+// the smallest unit, so every generated value has an exact decimal representation.
+use std::fmt;
+
+use crate::hh3_lazy_tree::Gen;
+use crate::range::IntoGenRange;
+
+/// Largest magnitude of Gen::money amounts, in whole units of the currency
+pub const MONEY_MAX_MAJOR_UNITS : i128 = 1_000_000;
+
+/// A decimal number units / 10^scale, eg units 12345 with scale 2 is 123.45.
+/// Equality is structural, so 1.0 and 1.00 are different values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    units : i128,
+    scale : u32,
+}
+
+impl Decimal {
+    pub fn new(units : i128, scale : u32) -> Decimal {
+        Decimal { units, scale }
+    }
+
+    /// The unscaled integer value
+    pub fn units(&self) -> i128 {
+        self.units
+    }
+
+    /// Number of digits after the decimal point
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.units < 0 { "-" } else { "" };
+        let digits = self.units.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        // Pad with zeros so there's at least one digit before the point
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, frac) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, whole, frac)
+    }
+}
+
+/// An amount of money in a currency with the given ISO 4217 code
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Money {
+    pub currency : &'static str,
+    pub amount : Decimal,
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.amount, self.currency)
+    }
+}
+
+/// Currency codes and the number of digits in their minor unit.
+/// Includes currencies without minor units and with three digits, which often trip up code that
+/// assumes cents.
+pub fn currencies() -> Vec<(&'static str, u32)> {
+    vec![("USD", 2), ("EUR", 2), ("JPY", 0), ("GBP", 2), ("KWD", 3), ("BHD", 3), ("CLF", 4), ("ISK", 0)]
+}
+
+impl<'a> Gen<'a, Decimal> {
+    /// Decimals with the given number of digits after the point, where range is the range of the
+    /// unscaled value. Shrinks toward the origin of the range, which is nearest zero by default.
+    /// > Gen::decimal(2, -10000..10000) // -100.00 to 99.99
+    pub fn decimal<R : IntoGenRange<i128>>(scale : u32, range : R) -> Gen<'a, Decimal> {
+        Gen::i128(range).map(move |units| Decimal::new(units, scale))
+    }
+}
+
+impl<'a> Gen<'a, Money> {
+    /// Amounts of money in one of the currencies, up to MONEY_MAX_MAJOR_UNITS either side of zero.
+    /// Shrinks toward the first currency and toward zero.
+    pub fn money() -> Gen<'a, Money> {
+        let currencies = currencies();
+        Gen::index(currencies.len()).and_then(move |ix| {
+            let (currency, scale) = currencies[ix];
+            let max = MONEY_MAX_MAJOR_UNITS * 10i128.pow(scale);
+            Gen::decimal(scale, -max..max + 1).map(move |amount| Money { currency, amount })
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::gen::decimal::*;
+    use crate::property::Property;
+
+    #[test]
+    fn display() {
+        assert_eq!(Decimal::new(12345, 2).to_string(), "123.45");
+        assert_eq!(Decimal::new(-5, 3).to_string(), "-0.005");
+        assert_eq!(Decimal::new(7, 0).to_string(), "7");
+        assert_eq!(Money { currency : "JPY", amount : Decimal::new(-100, 0) }.to_string(), "-100 JPY");
+    }
+
+    #[test]
+    fn shrinks_toward_zero() {
+        Property::check_shrink(Gen::decimal(2, -10000..10000), |d| d.units() > -150, Decimal::new(-150, 2));
+        Property::check_shrink(Gen::money(), |m| m.amount.units() < 1000,
+            Money { currency : "USD", amount : Decimal::new(1000, 2) });
+    }
+}
@@ -0,0 +1,107 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use crate::hh3_lazy_tree::{Gen, Tree};
+use crate::random::{Fnv1a, Random};
+
+/// A generated pure function, for testing higher-order code.
+/// The function records the arguments it is called with, so that counterexamples can be shown as
+/// a table of the calls that were made:
+/// > {1 => true, 5 => false, _ => ...}
+pub struct Function<'a, A, B> {
+    apply : Rc<dyn Fn(&A) -> B + 'a>,
+    table : Rc<RefCell<Vec<(A, B)>>>,
+}
+
+impl<'a, A, B> Function<'a, A, B> {
+    pub fn new<F>(f : F) -> Function<'a, A, B>
+    where F : Fn(&A) -> B + 'a {
+        Function { apply : Rc::new(f), table : Rc::new(RefCell::new(Vec::new())) }
+    }
+
+    /// Apply the function, remembering the argument and result
+    pub fn call(&self, a : &A) -> B
+    where A : Clone + PartialEq,
+    B : Clone {
+        let b = (self.apply)(a);
+        let mut table = self.table.borrow_mut();
+        if !table.iter().any(|(x, _)| x == a) {
+            table.push((a.clone(), b.clone()));
+        }
+        b
+    }
+
+    /// The arguments the function has been called with so far, and their results
+    pub fn table(&self) -> Vec<(A, B)>
+    where A : Clone,
+    B : Clone {
+        self.table.borrow().clone()
+    }
+}
+
+impl<'a, A, B> Clone for Function<'a, A, B> {
+    fn clone(&self) -> Self {
+        Function { apply : self.apply.clone(), table : self.table.clone() }
+    }
+}
+
+impl<'a, A : fmt::Debug, B : fmt::Debug> fmt::Debug for Function<'a, A, B> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+        for (a, b) in self.table.borrow().iter() {
+            write!(f, "{:?} => {:?}, ", a, b)?;
+        }
+        write!(f, "_ => ...}}")
+    }
+}
+
+impl<'a, A, B> Gen<'a, Function<'a, A, B>> {
+    /// Pure functions whose results come from result_gen.
+    /// Each argument is hashed into the seed for result_gen, so the same argument always gives
+    /// the same result, in every build. Shrinks to the function that returns the smallest shrink of each result.
+    pub fn function(result_gen : Gen<'a, B>) -> Gen<'a, Function<'a, A, B>>
+    where A : Hash + 'a,
+    B : Clone + 'a {
+        Gen::new(move |mut r, s| {
+            let seed = r.u128();
+            let gen = result_gen.clone();
+            let simplest = Function::new(move |a : &A| Self::apply(&gen, seed, s, a).first_shrink_leaf());
+            let gen = result_gen.clone();
+            let children = move || vec![Tree::leaf(simplest.clone())];
+            Tree::new(Function::new(move |a : &A| Self::apply(&gen, seed, s, a).value), children)
+        })
+    }
+
+    fn apply(gen : &Gen<'a, B>, seed : u128, size : usize, a : &A) -> Tree<'a, B>
+    where A : Hash {
+        let mut hasher = Fnv1a::default();
+        a.hash(&mut hasher);
+        (*gen.run)(Random::new_from_seed(seed ^ hasher.finish() as u128), size)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::gen::function::*;
+    use crate::property::{Config, Property};
+
+    #[test]
+    fn deterministic_and_shown() {
+        let f : Function<u64, u64> = Gen::function(Gen::u64(0..1000)).sample(0, 10, 1).remove(0);
+        assert_eq!(f.call(&3), f.call(&3));
+        assert_eq!(f.table().len(), 1);
+
+        // Some function maps a different value to 0
+        let gen : Gen<Function<u64, bool>> = Gen::function(Gen::bool());
+        let report = Property::new(gen, |f| f.call(&1) == f.call(&2)).check(&Config::default());
+        let shown = format!("{:?}", report.counterexamples[0].minimal);
+        assert!(shown.starts_with("{1 => ") && shown.ends_with("_ => ...}"), "{}", shown);
+
+        // The simplest function always returns false
+        let (_, shrinks) = Gen::<Function<u64, bool>>::function(Gen::bool()).sample_tree(0, 0);
+        assert!((0..100).all(|x| !shrinks[0].call(&x)));
+    }
+}
@@ -0,0 +1,217 @@
+// Random graphs for testing graph algorithms: trees, DAGs and undirected graphs.
+// The graphs shrink by removing edges, largest chunks first, then by removing nodes. Shrinking
+// keeps the shape of the graph: trees stay trees and DAGs stay acyclic.
+use std::ops::Range;
+
+use crate::hh3_lazy_tree::{Gen, Tree};
+use crate::random::Random;
+
+/// A graph with nodes numbered from 0, as a list of edges
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Graph {
+    pub nodes : usize,
+    /// For undirected graphs, each edge is stored once with the smaller node first
+    pub edges : Vec<(usize, usize)>,
+    pub directed : bool,
+}
+
+impl Graph {
+    /// Adjacency lists indexed by node. Undirected edges appear in both nodes' lists.
+    pub fn adjacency(&self) -> Vec<Vec<usize>> {
+        let mut adj = vec![Vec::new(); self.nodes];
+        for &(a, b) in &self.edges {
+            adj[a].push(b);
+            if !self.directed {
+                adj[b].push(a);
+            }
+        }
+        adj
+    }
+}
+
+/// Configuration for the graph generators
+#[derive(Clone, Debug)]
+pub struct GraphConfig {
+    /// Range for the number of nodes; shrinking doesn't go below the start
+    pub nodes : Range<usize>,
+    /// Maximum number of edges for DAGs and undirected graphs. Trees always have one less edge
+    /// than they have nodes.
+    pub max_edges : usize,
+}
+
+impl Default for GraphConfig {
+    fn default() -> Self {
+        GraphConfig { nodes : 1..10, max_edges : 20 }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Shape {
+    Tree,
+    Dag,
+    Undirected,
+}
+
+/// Graph during shrinking. The nodes are numbered in the order they were generated, which is a
+/// topological order for trees and DAGs; the labels shuffle them so the output graph isn't sorted.
+#[derive(Clone)]
+struct Plan {
+    shape : Shape,
+    min_nodes : usize,
+    edges : Vec<(usize, usize)>,
+    labels : Vec<usize>,
+}
+
+impl<'a> Gen<'a, Graph> {
+    /// Random trees, as directed graphs with edges from parent to child
+    pub fn graph_tree(config : &GraphConfig) -> Gen<'a, Graph> {
+        Self::graph(Shape::Tree, config)
+    }
+
+    /// Random directed acyclic graphs
+    pub fn graph_dag(config : &GraphConfig) -> Gen<'a, Graph> {
+        Self::graph(Shape::Dag, config)
+    }
+
+    /// Random undirected graphs without self-loops or repeated edges
+    pub fn graph_undirected(config : &GraphConfig) -> Gen<'a, Graph> {
+        Self::graph(Shape::Undirected, config)
+    }
+
+    fn graph(shape : Shape, config : &GraphConfig) -> Gen<'a, Graph> {
+        let config = config.clone();
+        assert!(!config.nodes.is_empty(), "Gen::graph: empty range of nodes {:?}", config.nodes);
+        Gen::new(move |mut r, _| {
+            let nodes = r.u64_range(config.nodes.start as u64 .. config.nodes.end as u64) as usize;
+            let edges = match shape {
+                Shape::Tree => (1..nodes).map(|child| (r.u64_range(0..child as u64) as usize, child)).collect(),
+                _ => Self::random_edges(&mut r, nodes, config.max_edges),
+            };
+            let mut labels : Vec<usize> = (0..nodes).collect();
+            for i in (1..nodes).rev() {
+                labels.swap(i, r.u64_range(0..i as u64 + 1) as usize);
+            }
+            Self::shrink_graph(Plan { shape, min_nodes : config.nodes.start, edges, labels })
+        })
+    }
+
+    /// Distinct edges from an earlier node to a later one
+    fn random_edges(r : &mut Random, nodes : usize, max_edges : usize) -> Vec<(usize, usize)> {
+        let mut edges = Vec::new();
+        if nodes < 2 {
+            return edges;
+        }
+        let count = r.u64_range(0..max_edges as u64 + 1);
+        for _ in 0..count {
+            let a = r.u64_range(0..nodes as u64 - 1) as usize;
+            let b = r.u64_range(a as u64 + 1 .. nodes as u64) as usize;
+            if !edges.contains(&(a, b)) {
+                edges.push((a, b));
+            }
+        }
+        edges
+    }
+
+    fn shrink_graph(plan : Plan) -> Tree<'a, Graph> {
+        let value = plan.graph();
+        let children = move || {
+            let mut children = Vec::new();
+            let len = plan.edges.len();
+
+            // Remove chunks of edges, except from trees, which need all of them
+            let mut chunk = if plan.shape == Shape::Tree { 0 } else { len };
+            while chunk > 0 {
+                for start in (0..len).step_by(chunk) {
+                    let mut smaller = plan.clone();
+                    smaller.edges.drain(start..(start + chunk).min(len));
+                    children.push(Self::shrink_graph(smaller));
+                }
+                chunk /= 2;
+            }
+
+            // Remove nodes, along with their edges. Trees can only lose leaves.
+            if plan.labels.len() > plan.min_nodes {
+                for node in (0..plan.labels.len()).rev() {
+                    let degree = plan.edges.iter().filter(|(a, b)| *a == node || *b == node).count();
+                    if plan.shape != Shape::Tree || degree <= 1 {
+                        children.push(Self::shrink_graph(plan.remove_node(node)));
+                    }
+                }
+            }
+            children
+        };
+        Tree::new(value, children)
+    }
+}
+
+impl Plan {
+    fn graph(&self) -> Graph {
+        let directed = self.shape != Shape::Undirected;
+        let edges = self.edges.iter().map(|&(a, b)| {
+            let (a, b) = (self.labels[a], self.labels[b]);
+            if directed { (a, b) } else { (a.min(b), a.max(b)) }
+        }).collect();
+        Graph { nodes : self.labels.len(), edges, directed }
+    }
+
+    fn remove_node(&self, node : usize) -> Plan {
+        let renumber = |x : usize| if x > node { x - 1 } else { x };
+        let edges = self.edges.iter()
+            .filter(|(a, b)| *a != node && *b != node)
+            .map(|&(a, b)| (renumber(a), renumber(b)))
+            .collect();
+        let label = self.labels[node];
+        let labels = self.labels.iter()
+            .filter(|l| **l != label)
+            .map(|&l| if l > label { l - 1 } else { l })
+            .collect();
+        Plan { edges, labels, ..self.clone() }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::gen::graph::*;
+    use crate::property::{Config, Property};
+
+    fn acyclic(g : &Graph) -> bool {
+        // Kahn's algorithm
+        let adj = g.adjacency();
+        let mut in_degree = vec![0; g.nodes];
+        for &(_, b) in &g.edges {
+            in_degree[b] += 1;
+        }
+        let mut ready : Vec<usize> = (0..g.nodes).filter(|n| in_degree[*n] == 0).collect();
+        let mut seen = 0;
+        while let Some(n) = ready.pop() {
+            seen += 1;
+            for &m in &adj[n] {
+                in_degree[m] -= 1;
+                if in_degree[m] == 0 {
+                    ready.push(m);
+                }
+            }
+        }
+        seen == g.nodes
+    }
+
+    #[test]
+    fn shapes_are_kept() {
+        let config = GraphConfig::default();
+        // The predicates also fail on enough edges, so every shrink gets checked too
+        let report = Property::new(Gen::graph_dag(&config), |g| !acyclic(g) || g.edges.len() < 6).check(&Config::default());
+        assert!(acyclic(&report.counterexamples[0].minimal));
+        assert_eq!(report.counterexamples[0].minimal.edges.len(), 6);
+
+        let tree = |g : &Graph| g.edges.len() + 1 == g.nodes && acyclic(g)
+            && (0..g.nodes).filter(|n| g.edges.iter().all(|(_, b)| b != n)).count() == 1;
+        let report = Property::new(Gen::graph_tree(&config), move |g| tree(g) && g.nodes < 4).check(&Config::default());
+        assert!(tree(&report.counterexamples[0].minimal));
+        assert_eq!(report.counterexamples[0].minimal.nodes, 4);
+
+        let config = GraphConfig { nodes : 2..20, max_edges : 30 };
+        Property::check_shrink(Gen::graph_undirected(&config), |g| g.edges.len() < 2,
+            Graph { nodes : 3, edges : vec![(0, 1), (0, 2)], directed : false });
+    }
+}
@@ -0,0 +1,84 @@
+// Adversarial generators for checking that parsers reject bad input cleanly rather than panic.
+// Each generator shrinks while keeping the input invalid, so a failing test shows the smallest
+// bad input the parser mishandles.
+use crate::hh3_lazy_tree::Gen;
+
+/// Byte sequences that are never valid UTF-8 when they follow complete characters
+pub fn invalid_utf8_sequences() -> Vec<Vec<u8>> {
+    vec![
+        // Lone continuation byte, and bytes that never appear in UTF-8
+        vec![0x80], vec![0xFF], vec![0xFE],
+        // Overlong encodings of NUL and '/'
+        vec![0xC0, 0x80], vec![0xE0, 0x80, 0xAF],
+        // Encoded surrogates, high and low
+        vec![0xED, 0xA0, 0x80], vec![0xED, 0xBF, 0xBF],
+        // Past the largest scalar value
+        vec![0xF4, 0x90, 0x80, 0x80],
+        // Truncated multi-byte sequences
+        vec![0xE2, 0x82], vec![0xF0, 0x9F, 0x98],
+    ]
+}
+
+impl<'a> Gen<'a, Vec<u8>> {
+    /// Byte buffers that aren't valid UTF-8: an invalid sequence between two valid strings, each
+    /// with length from len_gen.
+    /// Shrinking removes the surrounding text and moves toward a lone continuation byte.
+    pub fn invalid_utf8(len_gen : Gen<'a, usize>) -> Gen<'a, Vec<u8>> {
+        let text = Gen::string(Gen::char_unicode(), len_gen);
+        let sequences = invalid_utf8_sequences();
+        let bad = Gen::index(sequences.len()).map(move |i| sequences[i].clone());
+        Gen::map3(text.clone(), bad, text, |pre, bad, post| {
+            [pre.as_bytes(), &bad, post.as_bytes()].concat()
+        })
+    }
+
+    /// Length-prefixed buffers where the big-endian u32 prefix claims more bytes than follow.
+    /// Payloads have length from len_gen; the claimed length shrinks toward one more than the payload.
+    pub fn overlong_length_prefix(len_gen : Gen<'a, usize>) -> Gen<'a, Vec<u8>> {
+        Gen::bytes(len_gen).and_then(|payload| {
+            let actual = payload.len() as u32;
+            Gen::u32_biased(actual + 1 ..= u32::MAX).map(move |claimed| {
+                [&claimed.to_be_bytes()[..], &payload].concat()
+            })
+        })
+    }
+}
+
+impl<'a> Gen<'a, String> {
+    /// JSON string literals containing an escaped surrogate without its pair, such as "a\ud800b".
+    /// These are well-formed JSON text, but don't decode to a valid string.
+    /// The surrounding text has length from len_gen and is ASCII alphanumeric, so it can't complete
+    /// the pair; shrinking moves toward a lone high surrogate.
+    pub fn json_unpaired_surrogate(len_gen : Gen<'a, usize>) -> Gen<'a, String> {
+        let text = Gen::string(Gen::char_ascii_alphanumeric(), len_gen);
+        let surrogate = Gen::u32(0xD800..0xE000);
+        Gen::map3(text.clone(), surrogate, text, |pre, surrogate, post| {
+            format!("\"{}\\u{:04x}{}\"", pre, surrogate, post)
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::Gen;
+    use crate::property::{Config, Property};
+
+    #[test]
+    fn always_invalid() {
+        let report = Property::new(Gen::invalid_utf8(Gen::usize(0..10)), |b| std::str::from_utf8(b).is_err())
+            .check(&Config::default());
+        assert!(report.is_success(), "{}", report);
+        Property::check_shrink(Gen::invalid_utf8(Gen::usize(0..10)), |b| b.len() < 3, vec![0x80, 0xC2, 0x80]);
+
+        let claims_too_much = |b : &Vec<u8>| {
+            let claimed = u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize;
+            claimed > b.len() - 4
+        };
+        let report = Property::new(Gen::overlong_length_prefix(Gen::usize(0..10)), claims_too_much)
+            .check(&Config::default());
+        assert!(report.is_success(), "{}", report);
+
+        Property::check_shrink(Gen::json_unpaired_surrogate(Gen::usize(0..5)), |s| s.len() < 9, "\"a\\ud800\"".to_string());
+    }
+}
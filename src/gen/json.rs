@@ -0,0 +1,78 @@
+// Arbitrary JSON values, behind the serde_json feature
+use serde_json::{Map, Number, Value};
+
+use crate::hh3_lazy_tree::Gen;
+
+/// Arrays and objects have at most this many elements
+pub const JSON_MAX_LEN : usize = 5;
+
+impl<'a> Gen<'a, Value> {
+    /// JSON values with arrays and objects nested at most depth_budget deep.
+    /// Half of the values at each level are scalars. Shrinks toward null, by replacing arrays and
+    /// objects with scalars, removing elements and keys, and simplifying what's left.
+    pub fn json(depth_budget : usize) -> Gen<'a, Value> {
+        let scalar = Gen::json_scalar();
+        if depth_budget == 0 {
+            return scalar;
+        }
+        let inner = Gen::json(depth_budget - 1);
+        let array = inner.clone().vec(Gen::usize(0..JSON_MAX_LEN + 1)).map(Value::Array);
+        let key = Gen::string(Gen::char_ascii_alphanumeric(), Gen::usize(0..6));
+        let object = key.zip(inner)
+            .vec_unique_by(Gen::usize(0..JSON_MAX_LEN + 1), |(k, _)| k.clone())
+            .map(|kvs| Value::Object(kvs.into_iter().collect::<Map<String, Value>>()));
+        Gen::u64(0..4).and_then(move |ix| match ix {
+            0 | 1 => scalar.clone(),
+            2 => array.clone(),
+            _ => object.clone(),
+        })
+    }
+
+    /// Null, booleans, numbers and strings; shrinks in that order
+    fn json_scalar() -> Gen<'a, Value> {
+        let bool = Gen::bool().map(Value::Bool);
+        // Integers, or decimals with up to three places
+        let number = Gen::i64(-1_000_000..1_000_000).zip(Gen::bool()).map(|(n, decimal)| {
+            if decimal {
+                Number::from_f64(n as f64 / 1000.0).map_or(Value::Null, Value::Number)
+            } else {
+                Value::Number(Number::from(n))
+            }
+        });
+        let string = Gen::string(Gen::char_unicode(), Gen::usize(0..10)).map(Value::String);
+        Gen::u64(0..4).and_then(move |ix| match ix {
+            0 => Gen::constant(Value::Null),
+            1 => bool.clone(),
+            2 => number.clone(),
+            _ => string.clone(),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use crate::hh3_lazy_tree::Gen;
+    use crate::property::{Config, Property};
+
+    fn depth(v : &Value) -> usize {
+        match v {
+            Value::Array(vs) => 1 + vs.iter().map(depth).max().unwrap_or(0),
+            Value::Object(kvs) => 1 + kvs.values().map(depth).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn depth_and_shrinking() {
+        let report = Property::new(Gen::json(3), |v| {
+            depth(v) <= 3 && serde_json::from_str::<Value>(&v.to_string()).ok().as_ref() == Some(v)
+        }).check(&Config::default());
+        assert!(report.is_success(), "{}", report);
+
+        Property::check_shrink(Gen::json(3), |v| !v.is_object(), json!({}));
+        Property::check_shrink(Gen::json(3), |v| depth(v) < 2, json!([[]]));
+    }
+}
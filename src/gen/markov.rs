@@ -0,0 +1,159 @@
+// Plausible-looking text from a word-level n-gram model of a sample corpus, for testing tokenizers
+// and search indexes with input that has realistic word frequencies.
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::hh3_lazy_tree::{Gen, Tree};
+use crate::random::Random;
+
+/// Words in the corpus, and which words follow each run of up to order words
+struct Model {
+    words : Vec<String>,
+    order : usize,
+    /// Words that start a sentence, with how often they do
+    starts : Vec<(usize, u64)>,
+    /// For each run of words in the corpus, the words that follow it and how often, most common first
+    next : HashMap<Vec<usize>, Vec<(usize, u64)>>,
+}
+
+impl Model {
+    fn new(corpus : &str, order : usize) -> Model {
+        assert!(order > 0, "Gen::markov_text: order must be at least one");
+        let mut ids : HashMap<&str, usize> = HashMap::new();
+        let mut words = Vec::new();
+        let tokens : Vec<usize> = corpus.split_whitespace().map(|w| {
+            *ids.entry(w).or_insert_with(|| {
+                words.push(w.to_string());
+                words.len() - 1
+            })
+        }).collect();
+        assert!(!tokens.is_empty(), "Gen::markov_text: corpus has no words");
+
+        let mut starts = Vec::new();
+        let mut next : HashMap<Vec<usize>, Vec<(usize, u64)>> = HashMap::new();
+        let ends_sentence = |w : usize| words[w].ends_with(['.', '!', '?']);
+        Self::count(&mut starts, tokens[0]);
+        for pair in tokens.windows(2) {
+            if ends_sentence(pair[0]) {
+                Self::count(&mut starts, pair[1]);
+            }
+        }
+        // Shorter runs too, to fall back on when the last order words never occur together
+        for len in 1..=order {
+            for run in tokens.windows(len + 1) {
+                Self::count(next.entry(run[..len].to_vec()).or_default(), run[len]);
+            }
+        }
+
+        // Most common first, so shrinking moves toward the most likely text.
+        // The sort is stable, so ties stay in corpus order.
+        starts.sort_by_key(|(_, n)| Reverse(*n));
+        for followers in next.values_mut() {
+            followers.sort_by_key(|(_, n)| Reverse(*n));
+        }
+        Model { words, order, starts, next }
+    }
+
+    fn count(counts : &mut Vec<(usize, u64)>, word : usize) {
+        match counts.iter_mut().find(|(w, _)| *w == word) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((word, 1)),
+        }
+    }
+
+    /// Options for the word after the text so far, going by the longest run of its last order words
+    /// that occurs in the corpus. Text that the corpus never continues from starts a new sentence.
+    fn options(&self, text : &[usize]) -> &[(usize, u64)] {
+        let recent = &text[text.len().saturating_sub(self.order)..];
+        (0..recent.len())
+            .find_map(|skip| self.next.get(&recent[skip..]))
+            .map_or(&self.starts, |followers| followers)
+    }
+
+    /// Pick a word in proportion to how often it occurs, returning its index in the options
+    fn pick(r : &mut Random, options : &[(usize, u64)]) -> usize {
+        let total : u64 = options.iter().map(|(_, n)| n).sum();
+        let mut x = r.u64_range(0..total);
+        let mut ix = 0;
+        while x >= options[ix].1 {
+            x -= options[ix].1;
+            ix += 1;
+        }
+        ix
+    }
+
+    /// Text from a list of choices. Each choice is an index into the options for the previous word,
+    /// taken modulo the number of options, so any list of choices makes valid text even after some
+    /// of them have been shrunk or removed.
+    fn render(&self, choices : &[usize]) -> String {
+        let mut text = Vec::new();
+        for &choice in choices {
+            let options = self.options(&text);
+            text.push(options[choice % options.len()].0);
+        }
+        text.iter().map(|&w| self.words[w].as_str()).collect::<Vec<&str>>().join(" ")
+    }
+}
+
+impl<'a> Gen<'a, String> {
+    /// Text with len_gen words, where each word follows the previous order words as often as it does
+    /// in the corpus, so an order of one gives bigram frequencies and two gives trigrams.
+    /// Shrinks by removing words, down to the smallest length len_gen would shrink to, and by
+    /// replacing words with the most common word to follow the previous ones.
+    /// Panics if the corpus has no words, or the order is zero.
+    /// > Gen::markov_text(corpus, 2, Gen::usize(0..50))
+    pub fn markov_text(corpus : &str, order : usize, len_gen : Gen<'a, usize>) -> Gen<'a, String> {
+        let model = Rc::new(Model::new(corpus, order));
+        Gen::new(move |mut r, s| {
+            let len_tree = (*len_gen.run)(r.split(), s);
+            let mut text = Vec::new();
+            let choices = (0..len_tree.value).map(|_| {
+                let options = model.options(&text);
+                let ix = Model::pick(&mut r, options);
+                text.push(options[ix].0);
+                Gen::shrink_u128(0, ix as u128).map(|ix| ix as usize)
+            }).collect::<Vec<Tree<'a, usize>>>();
+            let model = model.clone();
+            Tree::interleave(choices, len_tree.first_shrink_leaf()).map(move |choices| model.render(&choices))
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::Gen;
+    use crate::property::{Config, Property};
+
+    const CORPUS : &str = "the cat sat on the mat. the dog sat on the cat. a dog ran.";
+
+    #[test]
+    fn follows_corpus() {
+        let corpus : Vec<&str> = CORPUS.split_whitespace().collect();
+        let pairs : Vec<&[&str]> = corpus.windows(2).collect();
+        let starts = ["the", "a"];
+        // Every pair of words either appears in the corpus or starts a new sentence
+        let plausible = move |s : &String| {
+            let words : Vec<&str> = s.split_whitespace().collect();
+            words.first().is_none_or(|w| starts.contains(w))
+                && words.windows(2).all(|w| pairs.contains(&w) || (w[0] == "ran." && starts.contains(&w[1])))
+        };
+        let report = Property::new(Gen::markov_text(CORPUS, 1, Gen::usize(0..20)), plausible)
+            .check(&Config::default());
+        assert!(report.is_success(), "{}", report);
+
+        Property::check_shrink(Gen::markov_text(CORPUS, 1, Gen::usize(0..20)), |s| !s.contains("dog"),
+            "a dog".to_string());
+    }
+
+    #[test]
+    fn longer_context() {
+        // "the dog" only starts a sentence, so it can follow "on" with bigrams but not trigrams
+        let no_dog_on = |s : &String| !s.contains("on the dog");
+        let gen = |order| Gen::markov_text(CORPUS, order, Gen::usize(0..20));
+        assert!(!Property::new(gen(1), no_dog_on).check(&Config::default()).is_success());
+        assert!(Property::new(gen(2), no_dog_on).check(&Config::default()).is_success());
+        Property::check_shrink(gen(3), |s| s.split_whitespace().count() < 4, "the cat sat on".to_string());
+    }
+}
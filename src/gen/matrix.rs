@@ -0,0 +1,132 @@
+// Rectangular matrices as nested vectors. Generating these with vec of vec would let shrinking
+// remove elements from one row but not the others, so instead the whole matrix is one shrink tree
+// that removes entire rows and columns.
+use crate::hh3_lazy_tree::{Gen, Tree};
+
+/// Chunks of 0..len to try removing, largest first, leaving at least min_len indices
+fn removals(len : usize, min_len : usize) -> Vec<(usize, usize)> {
+    let mut chunks = Vec::new();
+    let mut chunk = len;
+    while chunk > 0 {
+        if len - chunk >= min_len {
+            for start in (0..len).step_by(chunk) {
+                chunks.push((start, (start + chunk).min(len)));
+            }
+        }
+        chunk /= 2;
+    }
+    chunks
+}
+
+impl<'a, A> Gen<'a, Vec<Vec<A>>> {
+    /// Matrices with rows_gen rows and cols_gen columns, as a vector of rows that all have the same
+    /// length. Shrinks by removing rows, then columns, down to the smallest sizes rows_gen and
+    /// cols_gen would shrink to, and then by simplifying each element.
+    pub fn matrix(rows_gen : Gen<'a, usize>, cols_gen : Gen<'a, usize>, elem_gen : Gen<'a, A>) -> Gen<'a, Vec<Vec<A>>>
+    where A : 'a + Clone {
+        Gen::new(move |mut r, s| {
+            let rows_tree = (*rows_gen.run)(r.split(), s);
+            let cols_tree = (*cols_gen.run)(r.split(), s);
+            let cells = (0..rows_tree.value)
+                .map(|_| (0..cols_tree.value).map(|_| (*elem_gen.run)(r.split(), s)).collect())
+                .collect();
+            Self::matrix_tree(cells, rows_tree.first_shrink_leaf(), cols_tree.first_shrink_leaf(), false)
+        })
+    }
+
+    /// Square matrices with size_gen rows and columns.
+    /// Shrinking removes a row along with the column of the same index, so the matrix stays square.
+    pub fn matrix_square(size_gen : Gen<'a, usize>, elem_gen : Gen<'a, A>) -> Gen<'a, Vec<Vec<A>>>
+    where A : 'a + Clone {
+        Gen::new(move |mut r, s| {
+            let size_tree = (*size_gen.run)(r.split(), s);
+            let cells = (0..size_tree.value)
+                .map(|_| (0..size_tree.value).map(|_| (*elem_gen.run)(r.split(), s)).collect())
+                .collect();
+            let min_size = size_tree.first_shrink_leaf();
+            Self::matrix_tree(cells, min_size, min_size, true)
+        })
+    }
+
+    fn matrix_tree(cells : Vec<Vec<Tree<'a, A>>>, min_rows : usize, min_cols : usize, square : bool) -> Tree<'a, Vec<Vec<A>>>
+    where A : 'a + Clone {
+        let value = cells.iter().map(|row| row.iter().map(|t| t.value.clone()).collect()).collect();
+        let children = move || {
+            let mut children = Vec::new();
+            let rows = cells.len();
+            let cols = cells.first().map_or(0, |row| row.len());
+
+            let remove_rows = |cells : &mut Vec<Vec<Tree<'a, A>>>, (start, end)| {
+                cells.drain(start..end);
+            };
+            let remove_cols = |cells : &mut Vec<Vec<Tree<'a, A>>>, (start, end)| {
+                for row in cells.iter_mut() {
+                    row.drain(start..end);
+                }
+            };
+
+            if square {
+                for chunk in removals(rows, min_rows) {
+                    let mut smaller = cells.clone();
+                    remove_rows(&mut smaller, chunk);
+                    remove_cols(&mut smaller, chunk);
+                    children.push(Self::matrix_tree(smaller, min_rows, min_cols, square));
+                }
+            } else {
+                for chunk in removals(rows, min_rows) {
+                    let mut smaller = cells.clone();
+                    remove_rows(&mut smaller, chunk);
+                    children.push(Self::matrix_tree(smaller, min_rows, min_cols, square));
+                }
+                // Removing every row loses the number of columns, so only remove columns from
+                // matrices that have rows
+                if rows > 0 {
+                    for chunk in removals(cols, min_cols) {
+                        let mut smaller = cells.clone();
+                        remove_cols(&mut smaller, chunk);
+                        children.push(Self::matrix_tree(smaller, min_rows, min_cols, square));
+                    }
+                }
+            }
+
+            // Shrink each element, leaving the others as they are
+            for (i, row) in cells.iter().enumerate() {
+                for (j, cell) in row.iter().enumerate() {
+                    for child in (*cell.children)() {
+                        let mut shrunk = cells.clone();
+                        shrunk[i][j] = child;
+                        children.push(Self::matrix_tree(shrunk, min_rows, min_cols, square));
+                    }
+                }
+            }
+            children
+        };
+        Tree::new(value, children)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::Gen;
+    use crate::property::{Config, Property};
+
+    fn rectangular<A>(m : &[Vec<A>]) -> bool {
+        m.iter().all(|row| row.len() == m[0].len())
+    }
+
+    #[test]
+    fn stays_rectangular() {
+        // Failing on large matrices checks the shrinks are rectangular as well
+        let gen = Gen::matrix(Gen::usize(1..6), Gen::usize(2..6), Gen::u64(0..100));
+        let report = Property::new(gen.clone(), |m| rectangular(m) && m.len() * m[0].len() < 12)
+            .check(&Config::default());
+        let minimal = &report.counterexamples[0].minimal;
+        assert!(rectangular(minimal) && minimal[0].len() >= 2);
+        Property::check_shrink(gen, |m| m.iter().flatten().all(|x| *x < 50), vec![vec![0, 50]]);
+
+        let gen = Gen::matrix_square(Gen::usize(0..6), Gen::u64(0..100));
+        Property::check_shrink(gen, |m| m.len() < 3 && m.iter().all(|row| row.len() == m.len()),
+            vec![vec![0, 0, 0], vec![0, 0, 0], vec![0, 0, 0]]);
+    }
+}
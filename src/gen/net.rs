@@ -0,0 +1,90 @@
+// Network addresses and ports. Each generator mixes uniformly random values with a list of
+// well-known special cases, and shrinks toward the special cases first and then toward zero.
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use crate::hh3_lazy_tree::Gen;
+
+/// Special cases are generated this often, out of NET_SPECIAL_WEIGHT + NET_RANDOM_WEIGHT
+pub const NET_SPECIAL_WEIGHT : u64 = 1;
+pub const NET_RANDOM_WEIGHT : u64 = 3;
+
+fn special_or_random<'a, A>(special : Vec<A>, random : Gen<'a, A>) -> Gen<'a, A>
+where A : 'a + Clone {
+    Gen::frequency(vec![(NET_SPECIAL_WEIGHT, Gen::choose(special)), (NET_RANDOM_WEIGHT, random)])
+}
+
+impl<'a> Gen<'a, u16> {
+    /// Port numbers, biased toward zero, privileged ports and the boundaries
+    pub fn port() -> Gen<'a, u16> {
+        special_or_random(
+            vec![0, 1, 22, 80, 443, 1023, 1024, 8080, 49152, 65535],
+            Gen::u16_inclusive(0..=u16::MAX))
+    }
+}
+
+impl<'a> Gen<'a, Ipv4Addr> {
+    /// IPv4 addresses, biased toward unspecified, loopback, broadcast, private and multicast
+    /// addresses. Random addresses shrink each octet toward zero.
+    pub fn ipv4() -> Gen<'a, Ipv4Addr> {
+        special_or_random(
+            vec![
+                Ipv4Addr::UNSPECIFIED, Ipv4Addr::LOCALHOST, Ipv4Addr::BROADCAST,
+                Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(172, 16, 0, 1), Ipv4Addr::new(192, 168, 1, 1),
+                Ipv4Addr::new(169, 254, 0, 1), Ipv4Addr::new(224, 0, 0, 1),
+            ],
+            Gen::u8_inclusive(0..=u8::MAX).array::<4>().map(Ipv4Addr::from))
+    }
+}
+
+impl<'a> Gen<'a, Ipv6Addr> {
+    /// IPv6 addresses, biased toward unspecified, loopback, IPv4-mapped, link-local, multicast and
+    /// documentation addresses. Random addresses shrink each segment toward zero.
+    pub fn ipv6() -> Gen<'a, Ipv6Addr> {
+        special_or_random(
+            vec![
+                Ipv6Addr::UNSPECIFIED, Ipv6Addr::LOCALHOST,
+                Ipv4Addr::LOCALHOST.to_ipv6_mapped(),
+                Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1), Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1),
+                Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
+                Ipv6Addr::new(0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff),
+            ],
+            Gen::u16_inclusive(0..=u16::MAX).array::<8>().map(Ipv6Addr::from))
+    }
+}
+
+impl<'a> Gen<'a, IpAddr> {
+    /// IPv4 or IPv6 addresses with equal probability; shrinks toward IPv4
+    pub fn ip_addr() -> Gen<'a, IpAddr> {
+        Gen::one_of(vec![Gen::ipv4().map(IpAddr::V4), Gen::ipv6().map(IpAddr::V6)])
+    }
+}
+
+impl<'a> Gen<'a, SocketAddr> {
+    /// Socket addresses from Gen::ip_addr and Gen::port
+    pub fn socket_addr() -> Gen<'a, SocketAddr> {
+        Gen::ip_addr().zip(Gen::port()).map(|(ip, port)| SocketAddr::new(ip, port))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use crate::hh3_lazy_tree::Gen;
+    use crate::property::Property;
+    use crate::random::Random;
+
+    #[test]
+    fn special_addresses() {
+        let gen = Gen::ipv4();
+        let values : Vec<Ipv4Addr> = (0..200).map(|i| (*gen.run)(Random::new_from_seed(i), 0).value).collect();
+        assert!(values.iter().any(|a| a.is_loopback()));
+        assert!(values.iter().any(|a| a.is_broadcast()));
+        assert!(values.iter().any(|a| !a.is_private() && !a.is_loopback() && !a.is_broadcast()));
+
+        Property::check_shrink(Gen::port(), |p| *p < 1024, 1024);
+        Property::check_shrink(Gen::ip_addr(), |a| !a.is_loopback(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+        Property::check_shrink(Gen::socket_addr(), |a| a.port() == 0, "0.0.0.0:1".parse().unwrap());
+    }
+}
@@ -0,0 +1,201 @@
+// Email addresses, URLs and semantic version strings, with variants that are slightly invalid.
+// The valid generators are built from the grammar of each format, so shrinking keeps them valid:
+// optional parts shrink away, lists of labels and segments get shorter, and characters get simpler.
+// The invalid generators apply one breaking edit to a valid value, and shrink toward the first edit.
+use crate::hh3_lazy_tree::Gen;
+
+/// Letters for internationalised domain labels
+const IDN_CHARS : &str = "üöéßñøдж日本";
+
+/// Characters allowed unquoted in the local part of an email address, besides letters and digits
+const EMAIL_ATEXT : &str = "!#$%&'*+-/=?^_`{|}~";
+
+fn alphanumeric_or<'a>(extra : &str) -> Gen<'a, char> {
+    Gen::frequency(vec![(4, Gen::char_ascii_alphanumeric()), (1, Gen::char_of(extra.chars().collect()))])
+}
+
+/// DNS label: letters, digits and hyphens, not starting or ending with a hyphen
+fn dns_label<'a>() -> Gen<'a, String> {
+    let rest = Gen::string(alphanumeric_or("-"), Gen::usize(0..6));
+    Gen::map2(Gen::char_ascii_alphanumeric(), rest, |first, rest| {
+        format!("{}{}", first, rest).trim_end_matches('-').to_string()
+    })
+}
+
+/// Domain names with one to three labels, some of them internationalised, and a top-level domain
+fn domain<'a>() -> Gen<'a, String> {
+    let label = Gen::frequency(vec![
+        (3, dns_label()),
+        (1, Gen::string(Gen::char_of(IDN_CHARS.chars().collect()), Gen::usize(1..5))),
+    ]);
+    let tld = Gen::choose(vec!["com", "org", "io", "de", "xn--p1ai"]);
+    Gen::map2(label.vec(Gen::usize(1..4)), tld, |labels, tld| format!("{}.{}", labels.join("."), tld))
+}
+
+/// Characters from the unreserved set, with some percent-encoded bytes
+fn url_text<'a>(len : usize) -> Gen<'a, String> {
+    let piece = Gen::frequency(vec![
+        (8, alphanumeric_or("-._~").map(String::from)),
+        (1, Gen::u8_inclusive(0..=u8::MAX).map(|b| format!("%{:02X}", b))),
+    ]);
+    piece.vec(Gen::usize(0..len + 1)).map(|pieces| pieces.concat())
+}
+
+fn breaking_edit<'a>(valid : Gen<'a, String>, edits : Vec<fn(String) -> String>) -> Gen<'a, String> {
+    Gen::map2(valid, Gen::index(edits.len()), move |s, ix| edits[ix](s))
+}
+
+impl<'a> Gen<'a, String> {
+    /// Email addresses: a dot-separated or quoted local part, and a domain that may have
+    /// internationalised labels
+    pub fn email() -> Gen<'a, String> {
+        let atom = Gen::string(alphanumeric_or(EMAIL_ATEXT), Gen::usize(1..6));
+        let dot_atom = atom.vec(Gen::usize(1..3)).map(|atoms| atoms.join("."));
+        let quoted_char = Gen::char_ascii_printable().map(|c| if c == '"' || c == '\\' { ' ' } else { c });
+        let quoted = Gen::string(quoted_char, Gen::usize(0..6)).map(|s| format!("\"{}\"", s));
+        let local = Gen::frequency(vec![(4, dot_atom), (1, quoted)]);
+        Gen::map2(local, domain(), |local, domain| format!("{}@{}", local, domain))
+    }
+
+    /// Email addresses with one mistake: no @ separator, a leading dot, or an empty domain label
+    pub fn email_invalid() -> Gen<'a, String> {
+        breaking_edit(Gen::email(), vec![
+            |s| {
+                let at = s.rfind('@').expect("email has @");
+                format!("{}{}", &s[..at], &s[at + 1..])
+            },
+            |s| format!(".{}", s),
+            |s| {
+                let at = s.rfind('@').expect("email has @");
+                format!("{}@.{}", &s[..at], &s[at + 1..])
+            },
+        ])
+    }
+
+    /// Absolute URLs, with optional user info, port, path, query and fragment.
+    /// Hosts are domain names, IPv4 addresses or bracketed IPv6 addresses.
+    pub fn url() -> Gen<'a, String> {
+        let scheme = Gen::choose(vec!["http", "https", "ftp"]);
+        let userinfo = Gen::map2(url_text(5), url_text(5).option(), |user, password| match password {
+            None => format!("{}@", user),
+            Some(password) => format!("{}:{}@", user, password),
+        }).option();
+        let host = Gen::frequency(vec![
+            (4, domain()),
+            (1, Gen::ipv4().map(|ip| ip.to_string())),
+            (1, Gen::ipv6().map(|ip| format!("[{}]", ip))),
+        ]);
+        let port = Gen::port().option();
+        let path = url_text(6).vec(Gen::usize(0..4)).map(|segments| {
+            segments.iter().map(|s| format!("/{}", s)).collect::<String>()
+        });
+        let query = Gen::map2(url_text(4), url_text(4), |k, v| format!("{}={}", k, v))
+            .vec(Gen::usize(1..3)).map(|params| params.join("&")).option();
+        let fragment = url_text(6).option();
+        let end = Gen::map3(path, query, fragment, |path, query, fragment| {
+            let query = query.map(|q| format!("?{}", q)).unwrap_or_default();
+            let fragment = fragment.map(|f| format!("#{}", f)).unwrap_or_default();
+            format!("{}{}{}", path, query, fragment)
+        });
+        Gen::map5(scheme, userinfo, host, port, end, |scheme, userinfo, host, port, end| {
+            let port = port.map(|p| format!(":{}", p)).unwrap_or_default();
+            format!("{}://{}{}{}{}", scheme, userinfo.unwrap_or_default(), host, port, end)
+        })
+    }
+
+    /// URLs with one mistake: a space in the authority, a bad percent escape, or no scheme
+    pub fn url_invalid() -> Gen<'a, String> {
+        breaking_edit(Gen::url(), vec![
+            |s| s.replacen("://", ":// ", 1),
+            |s| format!("{}%G0", s),
+            |s| s[s.find("://").expect("url has scheme")..].to_string(),
+        ])
+    }
+
+    /// Semantic versions, with optional pre-release identifiers and build metadata
+    pub fn semver() -> Gen<'a, String> {
+        let number = Gen::u64_biased(0..1000);
+        // Alphanumeric identifiers start with a letter or hyphen so they can't be mistaken for numbers
+        let alphanumeric = Gen::map2(
+            Gen::char_of("abcxyz-".chars().collect()),
+            Gen::string(alphanumeric_or("-"), Gen::usize(0..5)),
+            |first, rest| format!("{}{}", first, rest));
+        let pre = Gen::frequency(vec![(1, number.clone().map(|n| n.to_string())), (1, alphanumeric)])
+            .vec(Gen::usize(1..4)).map(|ids| format!("-{}", ids.join("."))).option();
+        let build = Gen::string(alphanumeric_or("-"), Gen::usize(1..6))
+            .vec(Gen::usize(1..3)).map(|ids| format!("+{}", ids.join("."))).option();
+        Gen::map5(number.clone(), number.clone(), number, pre, build, |major, minor, patch, pre, build| {
+            format!("{}.{}.{}{}{}", major, minor, patch, pre.unwrap_or_default(), build.unwrap_or_default())
+        })
+    }
+
+    /// Semantic versions with one mistake: a "v" prefix, a leading zero, or no patch number
+    pub fn semver_invalid() -> Gen<'a, String> {
+        breaking_edit(Gen::semver(), vec![
+            |s| format!("v{}", s),
+            |s| format!("0{}", s),
+            |s| {
+                let minor_end = s.match_indices('.').nth(1).expect("semver has patch").0;
+                let patch_end = s[minor_end + 1..].find(|c : char| !c.is_ascii_digit())
+                    .map_or(s.len(), |i| minor_end + 1 + i);
+                format!("{}{}", &s[..minor_end], &s[patch_end..])
+            },
+        ])
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::Gen;
+    use crate::property::{Config, Property};
+
+    fn numeric(s : &str) -> bool {
+        !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) && (s == "0" || !s.starts_with('0'))
+    }
+
+    fn identifiers(s : &str, allow_leading_zero : bool) -> bool {
+        s.split('.').all(|id| {
+            !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                && (allow_leading_zero || !id.chars().all(|c| c.is_ascii_digit()) || numeric(id))
+        })
+    }
+
+    fn valid_semver(s : &str) -> bool {
+        let (rest, build) = match s.split_once('+') {
+            Some((rest, build)) => (rest, Some(build)),
+            None => (s, None),
+        };
+        let (core, pre) = match rest.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (rest, None),
+        };
+        let parts : Vec<&str> = core.split('.').collect();
+        parts.len() == 3 && parts.iter().all(|p| numeric(p))
+            && pre.is_none_or(|p| identifiers(p, false))
+            && build.is_none_or(|b| identifiers(b, true))
+    }
+
+    #[test]
+    fn semver_validity() {
+        let report = Property::new(Gen::semver(), |s| valid_semver(s)).check(&Config::default());
+        assert!(report.is_success(), "{}", report);
+        let report = Property::new(Gen::semver_invalid(), |s| !valid_semver(s)).check(&Config::default());
+        assert!(report.is_success(), "{}", report);
+        Property::check_shrink(Gen::semver(), |s| !s.contains('+'), "0.0.0+a".to_string());
+    }
+
+    #[test]
+    fn emails_and_urls() {
+        let report = Property::new(Gen::email(), |s| s.matches('@').count() >= 1 && !s.starts_with('.'))
+            .check(&Config::default());
+        assert!(report.is_success(), "{}", report);
+        Property::check_shrink(Gen::email(), |s| s.is_ascii(), "a@ü.com".to_string());
+        Property::check_shrink(Gen::email_invalid(), |s| s.contains('@'), "aa.com".to_string());
+
+        let report = Property::new(Gen::url(), |s| !s.contains(' ') && s.contains("://"))
+            .check(&Config::default());
+        assert!(report.is_success(), "{}", report);
+        Property::check_shrink(Gen::url(), |s| !s.contains('?'), "http://a.com?=".to_string());
+    }
+}
@@ -0,0 +1,80 @@
+// File names and paths, including the kinds of names that trip up path handling code.
+use std::path::{PathBuf, MAIN_SEPARATOR};
+
+use crate::hh3_lazy_tree::Gen;
+
+/// Length of the longest generated component, the usual limit on file name length
+pub const PATH_LONG_COMPONENT : usize = 255;
+
+/// Configuration for Gen::path_buf
+#[derive(Clone, Debug)]
+pub struct PathConfig {
+    /// Whether to start from the root directory
+    pub absolute : bool,
+    /// Maximum number of components, at least one
+    pub max_depth : usize,
+}
+
+impl Default for PathConfig {
+    fn default() -> Self {
+        PathConfig { absolute : false, max_depth : 4 }
+    }
+}
+
+/// Names that are valid but often mishandled: dots, spaces, unicode, leading dashes, and names
+/// that are reserved on Windows
+fn tricky_file_names() -> Vec<String> {
+    let names = vec![
+        ".", "..", ".hidden", "...", "trailing.", "with space", " leading space", "trailing space ",
+        "-dash", "a.tar.gz", "ünïcödé", "日本語", "emoji😀", "e\u{301}",
+        "CON", "PRN", "AUX", "NUL", "COM1", "LPT1", "con.txt",
+    ];
+    let mut names : Vec<String> = names.into_iter().map(String::from).collect();
+    names.push("a".repeat(PATH_LONG_COMPONENT));
+    names
+}
+
+impl<'a> Gen<'a, String> {
+    /// File names: mostly short ASCII alphanumeric names, sometimes one of the tricky cases.
+    /// Shrinks toward short ASCII names.
+    pub fn file_name() -> Gen<'a, String> {
+        Gen::frequency(vec![
+            (3, Gen::string(Gen::char_ascii_alphanumeric(), Gen::usize(1..9))),
+            (1, Gen::choose(tricky_file_names())),
+        ])
+    }
+}
+
+impl<'a> Gen<'a, PathBuf> {
+    /// Paths made of one to max_depth components from Gen::file_name.
+    /// Shrinks by removing components and then by simplifying each one.
+    pub fn path_buf(config : &PathConfig) -> Gen<'a, PathBuf> {
+        let absolute = config.absolute;
+        Gen::file_name().vec(Gen::usize(1..config.max_depth.max(1) + 1)).map(move |names| {
+            let mut path = if absolute { PathBuf::from(MAIN_SEPARATOR.to_string()) } else { PathBuf::new() };
+            path.extend(names);
+            path
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use crate::gen::path::*;
+    use crate::property::{Config, Property};
+
+    #[test]
+    fn paths_respect_config() {
+        let config = PathConfig { absolute : true, max_depth : 3 };
+        let report = Property::new(Gen::path_buf(&config), |p| p.is_absolute() && p.iter().count() <= 4)
+            .check(&Config::default());
+        assert!(report.is_success(), "{}", report);
+
+        let tricky = |p : &PathBuf| p.to_str().is_none_or(|s| s.is_ascii() && !s.contains(' '));
+        Property::check_shrink(Gen::path_buf(&PathConfig::default()), tricky, PathBuf::from("with space"));
+        Property::check_shrink(Gen::path_buf(&PathConfig::default()), |p| p.iter().count() < 2, PathBuf::from("a/a"));
+    }
+}
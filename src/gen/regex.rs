@@ -0,0 +1,76 @@
+use regex_syntax::hir::{Class, Hir, HirKind};
+
+use crate::hh3_lazy_tree::{discard, Gen};
+
+/// Unbounded repetitions like `a*` and `a+` generate at most this many extra repetitions
+pub const REGEX_MAX_EXTRA_REPEATS : u32 = 10;
+
+impl<'a> Gen<'a, String> {
+    /// Strings matching a regular expression.
+    /// The generator is built out of the structure of the regular expression, so shrinking keeps the
+    /// match: repetitions shrink toward their minimum count, alternations toward the first
+    /// alternative, and classes toward simple characters.
+    ///
+    /// Anchors and other look-around assertions are ignored, so word boundaries such as `\b` are not
+    /// guaranteed to hold. Byte classes outside of ASCII can't be put in a String and are skipped.
+    pub fn from_regex(pattern : &str) -> Result<Gen<'a, String>, Box<regex_syntax::Error>> {
+        let hir = regex_syntax::Parser::new().parse(pattern).map_err(Box::new)?;
+        Ok(Self::from_hir(&hir))
+    }
+
+    fn from_hir(hir : &Hir) -> Gen<'a, String> {
+        match hir.kind() {
+            HirKind::Empty | HirKind::Look(_) => Gen::constant(String::new()),
+            HirKind::Literal(lit) => Gen::constant(String::from_utf8_lossy(&lit.0).into_owned()),
+            HirKind::Class(class) => {
+                let ranges : Vec<Gen<'a, char>> = match class {
+                    Class::Unicode(c) => c.ranges().iter()
+                        .map(|r| Gen::char_range(r.start()..=r.end()))
+                        .collect(),
+                    Class::Bytes(c) => c.ranges().iter()
+                        .filter(|r| r.start().is_ascii())
+                        .map(|r| Gen::char_range(r.start() as char ..= r.end().min(0x7F) as char))
+                        .collect(),
+                };
+                if ranges.is_empty() {
+                    // Class that can't match anything
+                    return Gen::from_fn(|_r, _s| discard());
+                }
+                Gen::one_of(ranges).map(String::from)
+            }
+            HirKind::Repetition(rep) => {
+                let min = rep.min as usize;
+                let max = rep.max.unwrap_or(rep.min + REGEX_MAX_EXTRA_REPEATS) as usize;
+                Self::from_hir(&rep.sub).vec(Gen::usize(min..max + 1)).map(|parts| parts.concat())
+            }
+            HirKind::Capture(cap) => Self::from_hir(&cap.sub),
+            HirKind::Concat(subs) => {
+                Gen::collect(subs.iter().map(Self::from_hir)).map(|parts| parts.concat())
+            }
+            HirKind::Alternation(subs) => Gen::one_of(subs.iter().map(Self::from_hir).collect()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::Gen;
+    use crate::property::{Config, Property};
+
+    #[test]
+    fn matches_and_shrinks() {
+        let gen = Gen::from_regex(r"^[a-c]{2,4}-(foo|ba+r)[0-9]?$").unwrap();
+        let valid = |s : &String| {
+            let (pre, post) = s.split_at(s.find('-').unwrap());
+            let post = post[1..].trim_end_matches(|c : char| c.is_ascii_digit());
+            pre.len() >= 2 && pre.len() <= 4 && pre.chars().all(|c| ('a'..='c').contains(&c))
+                && (post == "foo" || (post.starts_with('b') && post.ends_with('r')))
+        };
+        let report = Property::new(gen.clone(), valid).check(&Config::default());
+        assert!(report.is_success(), "{}", report);
+
+        Property::check_shrink(gen, |s| !s.contains("bar"), "aa-bar".to_string());
+        assert!(Gen::from_regex("(").is_err());
+    }
+}
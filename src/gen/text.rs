@@ -0,0 +1,122 @@
+use std::ops::RangeInclusive;
+
+use crate::hh3_lazy_tree::{Gen, Tree};
+
+/// Surrogates are not valid chars, so we skip over them to get a contiguous index space
+const SURROGATE_START : u32 = 0xD800;
+const SURROGATE_COUNT : u32 = 0x800;
+
+fn char_to_index(c : char) -> u32 {
+    let c = c as u32;
+    if c >= SURROGATE_START {
+        c - SURROGATE_COUNT
+    } else {
+        c
+    }
+}
+
+fn index_to_char(i : u32) -> char {
+    let c = if i >= SURROGATE_START {
+        i + SURROGATE_COUNT
+    } else {
+        i
+    };
+    std::char::from_u32(c).expect("index_to_char: not a scalar value")
+}
+
+impl<'a> Gen<'a, char> {
+    /// Chars in an inclusive range, shrinking toward the origin.
+    /// The index space skips surrogates, so ranges spanning them are fine.
+    fn char_range_toward(range : RangeInclusive<char>, origin : char) -> Gen<'a, char> {
+        let start = char_to_index(*range.start()) as i128;
+        let last = char_to_index(*range.end()) as i128;
+        let origin = char_to_index(origin) as i128;
+        Gen::i128(start - origin .. last - origin + 1).map(move |offset| index_to_char((origin + offset) as u32))
+    }
+
+    /// Chars in an inclusive range.
+    /// Shrinks toward 'a' if the range includes it, otherwise '0', otherwise the start of the range.
+    pub fn char_range(range : RangeInclusive<char>) -> Gen<'a, char> {
+        let origin = if range.contains(&'a') {
+            'a'
+        } else if range.contains(&'0') {
+            '0'
+        } else {
+            *range.start()
+        };
+        Gen::char_range_toward(range, origin)
+    }
+
+    /// Any char from the set, shrinking toward the earlier ones
+    pub fn char_of(chars : Vec<char>) -> Gen<'a, char> {
+        Gen::choose(chars)
+    }
+
+    /// Any ASCII char including control characters, shrinking toward 'a'
+    pub fn char_ascii() -> Gen<'a, char> {
+        Gen::char_range('\0'..='\x7F')
+    }
+
+    /// Printable ASCII chars from space to tilde, shrinking toward 'a'
+    pub fn char_ascii_printable() -> Gen<'a, char> {
+        Gen::char_range(' '..='~')
+    }
+
+    /// Letters and digits, shrinking toward 'a'
+    pub fn char_ascii_alphanumeric() -> Gen<'a, char> {
+        let chars = ('a'..='z').chain('0'..='9').chain('A'..='Z').collect();
+        Gen::char_of(chars)
+    }
+
+    /// Any unicode scalar value, shrinking toward 'a'
+    pub fn char_unicode() -> Gen<'a, char> {
+        Gen::char_range('\0'..=std::char::MAX)
+    }
+}
+
+impl<'a> Gen<'a, String> {
+    /// Strings with length from len_gen and characters from char_gen.
+    /// Shrinking removes characters, down to the smallest length len_gen can shrink to, and
+    /// simplifies the individual characters.
+    pub fn string(char_gen : Gen<'a, char>, len_gen : Gen<'a, usize>) -> Gen<'a, String> {
+        Gen::new(move |mut r, s| {
+            let len_tree = (*len_gen.run)(r.split(), s);
+            let min_len = len_tree.first_shrink_leaf();
+            let chars = (0..len_tree.value).map(|_| (*char_gen.run)(r.split(), s)).collect();
+            Tree::interleave(chars, min_len).map(|cs| cs.into_iter().collect())
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::Gen;
+    use crate::property::Property;
+
+    #[test]
+    fn shrinks_toward_simple() {
+        Property::check_shrink(Gen::char_unicode(), |c| c.is_ascii_lowercase(), '{');
+        Property::check_shrink(Gen::char_ascii_printable(), |c| *c < 'b', 'b');
+        Property::check_shrink(Gen::char_range('0'..='9'), |c| *c < '5', '5');
+        Property::check_shrink(Gen::char_ascii_alphanumeric(), |c| c.is_ascii_lowercase(), '0');
+    }
+
+    #[test]
+    fn string_shrinks() {
+        let gen = Gen::string(Gen::char_ascii_printable(), Gen::usize(0..20));
+        Property::check_shrink(gen.clone(), |s| !s.contains('x'), "x".to_string());
+        Property::check_shrink(gen, |s| s.len() < 5, "aaaaa".to_string());
+
+        let gen = Gen::string(Gen::char_ascii_printable(), Gen::usize(3..20));
+        Property::check_shrink(gen, |s| !s.contains('x'), "axa".to_string());
+    }
+
+    #[test]
+    fn skips_surrogates() {
+        let gen = Gen::char_range('\u{D7FE}'..='\u{E001}');
+        let values = (*gen.enumerate)(10).unwrap().to_vec();
+        assert_eq!(values.len(), 4);
+        assert!(values.contains(&'\u{D7FF}') && values.contains(&'\u{E000}'));
+    }
+}
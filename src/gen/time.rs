@@ -0,0 +1,247 @@
+// Calendar dates, timestamps and durations. The dates are generated as a number of days since the Unix epoch,
+// which makes every date valid (leap years, month lengths) and means they shrink toward 1970-01-01.
+// The chrono and time features add generators for those crates' types, built the same way.
+use std::ops::Range;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::hh3_lazy_tree::Gen;
+
+/// A proleptic Gregorian calendar date, for when you don't want to depend on a date library.
+/// Months and days start from 1.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year : i64, pub month : u32, pub day : u32
+}
+
+impl Date {
+    pub const EPOCH : Date = Date { year : 1970, month : 1, day : 1 };
+
+    pub fn new(year : i64, month : u32, day : u32) -> Option<Date> {
+        if (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month) {
+            Some(Date { year, month, day })
+        } else {
+            None
+        }
+    }
+
+    /// Number of days since 1970-01-01, negative for earlier dates.
+    /// This is Howard Hinnant's days_from_civil.
+    pub fn days_since_epoch(&self) -> i64 {
+        let y = if self.month <= 2 { self.year - 1 } else { self.year };
+        let era = y.div_euclid(400);
+        let yoe = y.rem_euclid(400);
+        let m = self.month as i64;
+        let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+
+    /// Inverse of days_since_epoch
+    pub fn from_days_since_epoch(days : i64) -> Date {
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        Date { year, month, day }
+    }
+}
+
+pub fn is_leap_year(year : i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+/// Number of days in the month, which must be between 1 and 12
+pub fn days_in_month(year : i64, month : u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Dates shrink toward the epoch, or the end of the range nearest to it
+fn toward_epoch<'a>(range : Range<i64>) -> Gen<'a, i64> {
+    Gen::i64(range)
+}
+
+impl<'a> Gen<'a, Date> {
+    /// Valid dates in the range, shrinking toward 1970-01-01
+    pub fn date(range : Range<Date>) -> Gen<'a, Date> {
+        toward_epoch(range.start.days_since_epoch()..range.end.days_since_epoch())
+            .map(Date::from_days_since_epoch)
+    }
+}
+
+impl<'a> Gen<'a, Duration> {
+    /// Durations in the range with nanosecond precision, shrinking toward zero
+    pub fn duration(range : Range<Duration>) -> Gen<'a, Duration> {
+        Gen::duration_with_precision(range, Duration::from_nanos(1))
+    }
+
+    /// Durations in the range that are whole multiples of the precision, eg Duration::from_millis(1).
+    /// Shrinks toward zero, or the start of the range.
+    /// Panics if the precision is zero.
+    pub fn duration_with_precision(range : Range<Duration>, precision : Duration) -> Gen<'a, Duration> {
+        let p = precision.as_nanos() as i128;
+        assert!(p > 0, "Gen::duration_with_precision: precision must be non-zero");
+        let units = multiples(range.start.as_nanos() as i128..range.end.as_nanos() as i128, p);
+        Gen::i128(units).map(move |n| duration_from_nanos((n * p) as u128))
+    }
+}
+
+impl<'a> Gen<'a, SystemTime> {
+    /// Times in the range with nanosecond precision, shrinking toward the Unix epoch
+    pub fn system_time(range : Range<SystemTime>) -> Gen<'a, SystemTime> {
+        Gen::system_time_with_precision(range, Duration::from_nanos(1))
+    }
+
+    /// Times in the range that are a whole multiple of the precision away from the Unix epoch.
+    /// Shrinks toward the epoch, or the end of the range nearest to it.
+    /// Panics if the precision is zero.
+    pub fn system_time_with_precision(range : Range<SystemTime>, precision : Duration) -> Gen<'a, SystemTime> {
+        let p = precision.as_nanos() as i128;
+        assert!(p > 0, "Gen::system_time_with_precision: precision must be non-zero");
+        let units = multiples(nanos_since_epoch(range.start)..nanos_since_epoch(range.end), p);
+        Gen::i128(units).map(move |n| {
+            let nanos = n * p;
+            if nanos >= 0 {
+                UNIX_EPOCH + duration_from_nanos(nanos as u128)
+            } else {
+                UNIX_EPOCH - duration_from_nanos(nanos.unsigned_abs())
+            }
+        })
+    }
+}
+
+/// The multipliers k such that k * precision is in the range
+fn multiples(range : Range<i128>, precision : i128) -> Range<i128> {
+    let ceil = |x : i128| -(-x).div_euclid(precision);
+    ceil(range.start)..ceil(range.end)
+}
+
+fn nanos_since_epoch(time : SystemTime) -> i128 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_nanos() as i128,
+        Err(e) => -(e.duration().as_nanos() as i128),
+    }
+}
+
+fn duration_from_nanos(nanos : u128) -> Duration {
+    Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32)
+}
+
+#[cfg(feature = "chrono")]
+impl<'a> Gen<'a, chrono::NaiveDate> {
+    /// Dates in the range, shrinking toward 1970-01-01
+    pub fn naive_date(range : Range<chrono::NaiveDate>) -> Gen<'a, chrono::NaiveDate> {
+        let epoch = chrono::NaiveDate::from_ymd_opt(1970, 1, 1).expect("epoch is a valid date");
+        let days = |d : chrono::NaiveDate| d.signed_duration_since(epoch).num_days();
+        toward_epoch(days(range.start)..days(range.end))
+            .map(move |d| epoch + chrono::Duration::days(d))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<'a> Gen<'a, chrono::DateTime<chrono::Utc>> {
+    /// Times in the range, with whole seconds, shrinking toward 1970-01-01T00:00:00Z
+    pub fn date_time_utc(range : Range<chrono::DateTime<chrono::Utc>>) -> Gen<'a, chrono::DateTime<chrono::Utc>> {
+        toward_epoch(range.start.timestamp()..range.end.timestamp())
+            .map(|s| chrono::DateTime::from_timestamp(s, 0).expect("timestamp within the range is valid"))
+    }
+}
+
+#[cfg(feature = "time")]
+impl<'a> Gen<'a, ::time::Date> {
+    /// Dates in the range, shrinking toward 1970-01-01
+    pub fn time_date(range : Range<::time::Date>) -> Gen<'a, ::time::Date> {
+        let epoch = ::time::Date::from_julian_day(2440588).expect("epoch is a valid date");
+        let days = move |d : ::time::Date| (d - epoch).whole_days();
+        toward_epoch(days(range.start)..days(range.end))
+            .map(move |d| epoch + ::time::Duration::days(d))
+    }
+}
+
+#[cfg(feature = "time")]
+impl<'a> Gen<'a, ::time::OffsetDateTime> {
+    /// Times in the range, with whole seconds and UTC offset, shrinking toward 1970-01-01T00:00:00Z
+    pub fn offset_date_time(range : Range<::time::OffsetDateTime>) -> Gen<'a, ::time::OffsetDateTime> {
+        toward_epoch(range.start.unix_timestamp()..range.end.unix_timestamp())
+            .map(|s| ::time::OffsetDateTime::from_unix_timestamp(s).expect("timestamp within the range is valid"))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::gen::time::*;
+    use crate::property::{Config, Property};
+
+    #[test]
+    fn dates_are_valid() {
+        for days in -800000..800000 {
+            let date = Date::from_days_since_epoch(days);
+            assert_eq!(Date::new(date.year, date.month, date.day), Some(date));
+            assert_eq!(date.days_since_epoch(), days);
+        }
+        assert_eq!(Date::from_days_since_epoch(0), Date::EPOCH);
+        assert_eq!(Date::new(2000, 2, 29).map(|d| d.days_since_epoch()), Some(11016));
+        assert_eq!(Date::new(1900, 2, 29), None);
+
+        let range = Date::new(1900, 1, 1).unwrap()..Date::new(2100, 1, 1).unwrap();
+        let report = Property::new(Gen::date(range.clone()), move |d| range.contains(d)).check(&Config::default());
+        assert!(report.is_success(), "{}", report);
+
+        let range = Date::new(1900, 1, 1).unwrap()..Date::new(2100, 1, 1).unwrap();
+        Property::check_shrink(Gen::date(range), |d| d.year < 2000, Date::new(2000, 1, 1).unwrap());
+    }
+
+    /// Generated values for a given seed are fixed, as well as the raw random numbers
+    #[test]
+    fn golden_dates() {
+        let range = Date::new(1900, 1, 1).unwrap()..Date::new(2100, 1, 1).unwrap();
+        let dates : Vec<(i64, u32, u32)> = Gen::date(range).sample(42, 30, 5).iter().map(|d| (d.year, d.month, d.day)).collect();
+        assert_eq!(dates, vec![(2019, 6, 24), (1959, 8, 24), (2036, 6, 11), (1972, 5, 23), (1943, 6, 7)]);
+    }
+
+    #[test]
+    fn durations_and_system_times() {
+        let range = Duration::from_millis(5)..Duration::from_secs(10);
+        let gen = Gen::duration_with_precision(range.clone(), Duration::from_millis(1));
+        let report = Property::new(gen.clone(), move |d| range.contains(d) && d.subsec_nanos() % 1_000_000 == 0)
+            .check(&Config::default());
+        assert!(report.is_success(), "{}", report);
+        Property::check_shrink(gen, |d| d.as_millis() < 1000, Duration::from_secs(1));
+
+        let start = UNIX_EPOCH - Duration::from_secs(1000);
+        let end = UNIX_EPOCH + Duration::from_secs(1000);
+        let gen = Gen::system_time_with_precision(start..end, Duration::from_secs(1));
+        Property::check_shrink(gen, |t| *t < UNIX_EPOCH + Duration::from_secs(100), UNIX_EPOCH + Duration::from_secs(100));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_shrinks_toward_epoch() {
+        use chrono::Datelike;
+        let start = chrono::NaiveDate::from_ymd_opt(1800, 1, 1).unwrap();
+        let end = chrono::NaiveDate::from_ymd_opt(2200, 1, 1).unwrap();
+        Property::check_shrink(Gen::naive_date(start..end), |d| d.year() < 2000,
+            chrono::NaiveDate::from_ymd_opt(2000, 1, 1).unwrap());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time_shrinks_toward_epoch() {
+        let start = ::time::OffsetDateTime::from_unix_timestamp(-1_000_000_000).unwrap();
+        let end = ::time::OffsetDateTime::from_unix_timestamp(1_000_000_000).unwrap();
+        Property::check_shrink(Gen::offset_date_time(start..end), |t| t.unix_timestamp().abs() < 1000,
+            ::time::OffsetDateTime::from_unix_timestamp(-1000).unwrap());
+    }
+}
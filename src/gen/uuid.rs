@@ -0,0 +1,46 @@
+// UUIDs, behind the uuid feature
+use ::uuid::{Builder, Uuid};
+
+use crate::hh3_lazy_tree::{Gen, Tree};
+
+impl<'a> Gen<'a, Uuid> {
+    /// Random version 4 UUIDs from the generator's seed, with the nil and max UUIDs as occasional
+    /// edge cases. Shrinks toward the nil UUID.
+    pub fn uuid() -> Gen<'a, Uuid> {
+        let v4 = Gen::new(|mut r, _s| {
+            let mut bytes = [0; 16];
+            r.fill_bytes(&mut bytes);
+            Tree::leaf(Builder::from_random_bytes(bytes).into_uuid())
+        });
+        // The smallest v4 UUID still has its version bits set, so every UUID can also shrink straight
+        // to nil, whichever edge case the seed would have picked
+        Gen::frequency(vec![(1, Gen::choose(vec![Uuid::nil(), Uuid::max()])), (8, v4)])
+            .with_shrinker(|u| if u.is_nil() { vec![] } else { vec![Uuid::nil()] })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use ::uuid::{Uuid, Version};
+
+    use crate::hh3_lazy_tree::Gen;
+    use crate::property::{Config, Property};
+    use crate::random::Random;
+
+    #[test]
+    fn v4_and_edge_cases() {
+        let values : Vec<Uuid> = (0..100).map(|i| (*Gen::uuid().run)(Random::new_from_seed(i), 0).value).collect();
+        assert!(values.iter().all(|u| u.is_nil() || u.is_max() || u.get_version() == Some(Version::Random)));
+        assert!(values.iter().any(|u| u.is_nil()));
+        assert_eq!(values, (0..100).map(|i| (*Gen::uuid().run)(Random::new_from_seed(i), 0).value).collect::<Vec<_>>());
+
+        Property::check_shrink(Gen::uuid(), |u| u.is_max(), Uuid::nil());
+        // Shrinks to nil from any seed, not just the ones where the edge case generator picks it
+        for seed in 0..20 {
+            let config = Config { seed, ..Config::default() };
+            let report = Property::new(Gen::uuid(), |u| u.is_max()).check(&config);
+            assert_eq!(report.counterexamples[0].minimal, Uuid::nil());
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::fmt;
+
+use crate::hh3_lazy_tree::Gen;
+
+/// A generated value along with the name of the variant that generated it.
+/// The Debug rendering includes the name, so it shows up in failure reports, and the name can be
+/// used to classify test cases with Property::classify.
+#[derive(Clone, PartialEq)]
+pub struct Variant<A> {
+    pub name : &'static str,
+    pub value : A,
+}
+
+impl<A : fmt::Debug> fmt::Debug for Variant<A> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {:?}", self.name, self.value)
+    }
+}
+
+impl<'a, A> Gen<'a, Variant<A>> {
+    /// Choose between named generators with equal probability, eg one for each constructor of an
+    /// enum. Shrinks toward the first variant.
+    /// > Gen::variants(vec![("Leaf", gen_leaf), ("Node", gen_node)])
+    pub fn variants(gens : Vec<(&'static str, Gen<'a, A>)>) -> Gen<'a, Variant<A>>
+    where A : 'a + Clone {
+        Gen::variants_weighted(gens.into_iter().map(|(name, gen)| (name, 1, gen)).collect())
+    }
+
+    /// Choose between named generators, each with probability proportional to its weight.
+    /// Shrinks toward the first variant.
+    pub fn variants_weighted(gens : Vec<(&'static str, u64, Gen<'a, A>)>) -> Gen<'a, Variant<A>>
+    where A : 'a + Clone {
+        Gen::frequency(gens.into_iter()
+            .map(|(name, weight, gen)| (weight, gen.map(move |value| Variant { name, value })))
+            .collect())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::gen::variant::*;
+    use crate::property::{Config, Property};
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Shape {
+        Circle(u64),
+        Square(u64),
+    }
+
+    fn shapes<'a>() -> Gen<'a, Variant<Shape>> {
+        Gen::variants_weighted(vec![
+            ("Circle", 1, Gen::u64(0..100).map(Shape::Circle)),
+            ("Square", 3, Gen::u64(0..100).map(Shape::Square)),
+        ])
+    }
+
+    #[test]
+    fn names_and_labels() {
+        let report = Property::new(shapes(), |_| true).classify(|v| v.name.to_string()).check(&Config::default());
+        assert_eq!(report.labels.values().sum::<usize>(), 100);
+        assert!(report.labels["Square"] > report.labels["Circle"]);
+        assert!(report.to_string().contains("% Circle"));
+
+        assert_eq!(format!("{:?}", Variant { name : "Circle", value : Shape::Circle(1) }), "Circle: Circle(1)");
+        Property::check_shrink(shapes(), |v| v.name != "Square", Variant { name : "Square", value : Shape::Square(0) });
+    }
+}
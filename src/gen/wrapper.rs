@@ -0,0 +1,73 @@
+// Smart pointers and wrapper types, built from a generator for the wrapped value.
+// These all shrink the same way as the inner generator.
+use std::borrow::Cow;
+use std::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize,
+    NonZeroU128, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+    Wrapping,
+};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::hh3_lazy_tree::Gen;
+
+impl<'a, A : 'a> Gen<'a, A> {
+    pub fn boxed(self) -> Gen<'a, Box<A>> {
+        self.map(Box::new)
+    }
+
+    pub fn rc(self) -> Gen<'a, Rc<A>> {
+        self.map(Rc::new)
+    }
+
+    pub fn arc(self) -> Gen<'a, Arc<A>> {
+        self.map(Arc::new)
+    }
+
+    /// Owned copy-on-write values, eg Gen<String> to Gen<Cow<str>>
+    pub fn cow<B>(self) -> Gen<'a, Cow<'a, B>>
+    where B : ToOwned<Owned = A> + ?Sized + 'a {
+        self.map(Cow::Owned)
+    }
+
+    pub fn wrapping(self) -> Gen<'a, Wrapping<A>> {
+        self.map(Wrapping)
+    }
+}
+
+macro_rules! impl_non_zero {
+    ($($t:ty => $nz:ident),*) => {
+        $(
+            impl<'a> Gen<'a, $t> {
+                /// Non-zero values of the generator. Zeros are filtered out, so shrinking stops
+                /// just short of zero.
+                pub fn non_zero(self) -> Gen<'a, $nz> {
+                    self.filter(|x| *x != 0).map(|x| $nz::new(x).expect("filtered out zero"))
+                }
+            }
+        )*
+    }
+}
+
+impl_non_zero!(
+    u8 => NonZeroU8, u16 => NonZeroU16, u32 => NonZeroU32, u64 => NonZeroU64, u128 => NonZeroU128, usize => NonZeroUsize,
+    i8 => NonZeroI8, i16 => NonZeroI16, i32 => NonZeroI32, i64 => NonZeroI64, i128 => NonZeroI128, isize => NonZeroIsize
+);
+
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+    use std::num::NonZeroI32;
+
+    use crate::hh3_lazy_tree::Gen;
+    use crate::property::Property;
+
+    #[test]
+    fn wrappers_shrink_like_inner() {
+        Property::check_shrink(Gen::u64(0..100).boxed(), |x| **x < 10, Box::new(10));
+        Property::check_shrink(Gen::i32(-100..100).non_zero(), |x| x.get() > 0, NonZeroI32::new(-1).unwrap());
+        let gen : Gen<Cow<str>> = Gen::string(Gen::char_ascii_alphanumeric(), Gen::usize(0..5)).cow();
+        Property::check_shrink(gen, |s| s.is_empty(), Cow::Borrowed("a"));
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::hh3_lazy_tree::{depth_for_size, Gen, Tree};
+
+/// Repetitions with Expr::Many generate at most this many copies
+pub const GRAMMAR_MAX_REPEATS : usize = 4;
+
+/// Right-hand side of a grammar rule, a bit like EBNF
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    /// Literal terminal string
+    Lit(String),
+    /// Any char in the inclusive range
+    Chars(char, char),
+    /// Reference to a rule by name
+    Rule(String),
+    /// Each expression in turn
+    Seq(Vec<Expr>),
+    /// One of the alternatives; shrinks toward the first
+    Choice(Vec<Expr>),
+    /// Zero or one
+    Opt(Box<Expr>),
+    /// Zero or more
+    Many(Box<Expr>),
+}
+
+pub fn lit(s : &str) -> Expr {
+    Expr::Lit(s.to_string())
+}
+
+pub fn chars(lo : char, hi : char) -> Expr {
+    Expr::Chars(lo, hi)
+}
+
+pub fn rule(name : &str) -> Expr {
+    Expr::Rule(name.to_string())
+}
+
+pub fn seq(exprs : Vec<Expr>) -> Expr {
+    Expr::Seq(exprs)
+}
+
+pub fn choice(exprs : Vec<Expr>) -> Expr {
+    Expr::Choice(exprs)
+}
+
+pub fn opt(expr : Expr) -> Expr {
+    Expr::Opt(Box::new(expr))
+}
+
+pub fn many(expr : Expr) -> Expr {
+    Expr::Many(Box::new(expr))
+}
+
+/// A set of mutually recursive rules, with a start rule.
+/// > Grammar::new("expr")
+/// >   .rule("expr", seq(vec![rule("term"), many(seq(vec![lit("+"), rule("term")]))]))
+/// >   .rule("term", choice(vec![rule("num"), seq(vec![lit("("), rule("expr"), lit(")")])]))
+/// >   .rule("num", seq(vec![chars('0', '9'), many(chars('0', '9'))]))
+/// >   .gen()
+#[derive(Clone, Debug)]
+pub struct Grammar {
+    start : String,
+    rules : HashMap<String, Expr>,
+}
+
+/// Everything the generators need to look up while expanding rules
+struct Rules {
+    rules : HashMap<String, Expr>,
+    /// The smallest number of nested rule expansions needed to finish each rule
+    min_depth : HashMap<String, usize>,
+}
+
+impl Grammar {
+    pub fn new(start : &str) -> Grammar {
+        Grammar { start : start.to_string(), rules : HashMap::new() }
+    }
+
+    /// Add a rule, replacing any existing rule with the same name
+    pub fn rule(mut self, name : &str, expr : Expr) -> Grammar {
+        self.rules.insert(name.to_string(), expr);
+        self
+    }
+
+    /// Generator for sentences of the grammar.
+    /// The depth budget (maximum number of nested rule expansions) grows with the log of the size.
+    pub fn gen<'a>(&self) -> Gen<'a, String> {
+        let grammar = self.clone();
+        Gen::sized(move |s| grammar.gen_with_depth(depth_for_size(s)))
+    }
+
+    /// Generator for sentences of the grammar with at most depth nested rule expansions.
+    /// Panics if a rule is missing, or if some rule can never finish.
+    pub fn gen_with_depth<'a>(&self, depth : usize) -> Gen<'a, String> {
+        let rules = Rc::new(Rules { rules : self.rules.clone(), min_depth : self.min_depths() });
+        let start = rule(&self.start);
+        assert!(rules.expr_depth(&start) <= depth,
+            "Grammar: start rule {} needs depth at least {}", self.start, rules.expr_depth(&start));
+        Rules::gen(&rules, &start, depth)
+    }
+
+    /// Fixed point of the minimum depth of each rule, starting from "never finishes"
+    fn min_depths(&self) -> HashMap<String, usize> {
+        let mut rules = Rules {
+            rules : self.rules.clone(),
+            min_depth : self.rules.keys().map(|k| (k.clone(), usize::MAX)).collect(),
+        };
+        loop {
+            let next : HashMap<String, usize> = self.rules.iter()
+                .map(|(k, e)| (k.clone(), rules.expr_depth(e)))
+                .collect();
+            if next == rules.min_depth {
+                break;
+            }
+            rules.min_depth = next;
+        }
+
+        for (name, depth) in &rules.min_depth {
+            assert!(*depth != usize::MAX, "Grammar: rule {} can never finish", name);
+        }
+        rules.min_depth
+    }
+}
+
+impl Rules {
+    /// Smallest number of rule expansions needed to finish an expression
+    fn expr_depth(&self, expr : &Expr) -> usize {
+        match expr {
+            Expr::Lit(_) | Expr::Chars(_, _) | Expr::Opt(_) | Expr::Many(_) => 0,
+            Expr::Rule(name) => {
+                let depth = *self.min_depth.get(name)
+                    .unwrap_or_else(|| panic!("Grammar: no rule named {}", name));
+                depth.saturating_add(1)
+            }
+            Expr::Seq(exprs) => exprs.iter().map(|e| self.expr_depth(e)).max().unwrap_or(0),
+            Expr::Choice(exprs) => exprs.iter().map(|e| self.expr_depth(e)).min().unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Generator for an expression that can finish within the depth.
+    /// Rules are expanded lazily when the generator runs, so recursive grammars are fine.
+    /// These are built from interleaved lists and and_then rather than combine, so that deeply
+    /// nested sentences don't need to force their shrinks just to be generated.
+    fn gen<'a>(rules : &Rc<Rules>, expr : &Expr, depth : usize) -> Gen<'a, String> {
+        match expr {
+            Expr::Lit(s) => Gen::constant(s.clone()),
+            Expr::Chars(lo, hi) => Gen::char_range(*lo..=*hi).map(String::from),
+            Expr::Rule(name) => {
+                let rules = rules.clone();
+                let name = name.clone();
+                Gen::new(move |r, s| {
+                    let gen = Rules::gen(&rules, &rules.rules[&name], depth - 1);
+                    (*gen.run)(r, s)
+                })
+            }
+            Expr::Seq(exprs) => {
+                let parts : Vec<Gen<'a, String>> = exprs.iter().map(|e| Rules::gen(rules, e, depth)).collect();
+                Gen::new(move |mut r, s| {
+                    let trees = parts.iter().map(|g| (*g.run)(r.split(), s)).collect();
+                    Tree::interleave(trees, parts.len()).map(|v| v.concat())
+                })
+            }
+            Expr::Choice(exprs) => {
+                let alts : Vec<Gen<'a, String>> = exprs.iter()
+                    .filter(|e| rules.expr_depth(e) <= depth)
+                    .map(|e| Rules::gen(rules, e, depth))
+                    .collect();
+                Gen::u64(0..alts.len() as u64).and_then(move |ix| alts[ix as usize].clone())
+            }
+            Expr::Opt(e) if rules.expr_depth(e) <= depth => {
+                let some = Rules::gen(rules, e, depth);
+                Gen::bool().and_then(move |b| if b { some.clone() } else { Gen::constant(String::new()) })
+            }
+            Expr::Many(e) if rules.expr_depth(e) <= depth => {
+                let elem = Rules::gen(rules, e, depth);
+                Gen::new(move |mut r, s| {
+                    let len = r.u64_range(0..GRAMMAR_MAX_REPEATS as u64 + 1);
+                    let trees = (0..len).map(|_| (*elem.run)(r.split(), s)).collect();
+                    Tree::interleave(trees, 0).map(|v| v.concat())
+                })
+            }
+            // Not enough depth left for any copies
+            Expr::Opt(_) | Expr::Many(_) => Gen::constant(String::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::grammar::*;
+    use crate::property::{Config, Property};
+
+    fn arith() -> Grammar {
+        Grammar::new("expr")
+            .rule("expr", seq(vec![rule("term"), many(seq(vec![lit("+"), rule("term")]))]))
+            .rule("term", choice(vec![rule("num"), seq(vec![lit("("), rule("expr"), lit(")")])]))
+            .rule("num", seq(vec![chars('0', '9'), many(chars('0', '9'))]))
+    }
+
+    fn balanced(s : &str) -> bool {
+        let mut depth = 0i32;
+        for c in s.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            if depth < 0 {
+                return false;
+            }
+        }
+        depth == 0
+    }
+
+    #[test]
+    fn sentences_are_valid() {
+        let report = Property::new(arith().gen_with_depth(5), |s| {
+            balanced(s) && !s.contains("++") && !s.starts_with('+') && !s.ends_with('+') && !s.contains("()")
+        }).check(&Config { tests : 30, ..Config::default() });
+        assert!(report.is_success(), "{}", report);
+
+        Property::check_shrink(arith().gen_with_depth(5), |s| !s.contains('('), "(0)".to_string());
+    }
+
+    #[test]
+    #[should_panic]
+    fn unproductive() {
+        Grammar::new("a").rule("a", seq(vec![lit("x"), rule("a")])).gen_with_depth(5);
+    }
+}
@@ -59,7 +59,7 @@ pub struct Extract {
 impl Extract {
     fn new(rand : Random, shrink : Shrink, expected_extraction_count : usize) -> Extract {
         Extract {
-            rand : rand,
+            rand,
             extract_shrink : shrink,
             child_shrinks : Self::shrink_vec(shrink, expected_extraction_count),
             index : 0,
@@ -78,7 +78,7 @@ impl Extract {
             } else {
                 0
             };
-            v.push(Shrink { size: size, shrinks: s});
+            v.push(Shrink { size, shrinks: s});
         }
         v
     }
@@ -1,39 +1,482 @@
-use std::ops::Range;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::Hasher;
+use std::ops::RangeInclusive;
+use std::panic::Location;
 use std::rc::Rc;
+use std::str::FromStr;
 
-use crate::random::Random;
+use crate::nondet;
+use crate::nondet::Witness;
+use crate::nonempty::NonEmpty;
+use crate::random::{Fnv1a, Random};
+use crate::range::{GenRange, IntoGenRange};
+use crate::shrinker::Lengths;
 
 
-/// A strict rose tree for holding a generate value and its possible shrinks.
-/// The children should probably be lazy
+/// A lazy rose tree for holding a generated value and its possible shrinks.
+/// The children are a lazy iterator, so the shrink search can stop at the first child that still
+/// fails without computing its siblings.
+/// Trees built with Tree::new compute each child at most once, the first time it's reached.
 #[derive(Clone)]
 pub struct Tree<'a, A> {
     pub value : A,
-    pub children : Rc<dyn Fn() -> Vec<Tree<'a, A>> + 'a>,
+    pub children : Rc<dyn Fn() -> Children<'a, A> + 'a>,
+    /// The cache of the children, for trees built with Tree::new
+    memo : Option<Rc<RefCell<Memo<'a, A>>>>,
+}
+
+/// Lazy sequence of the subtrees of a tree
+pub type Children<'a, A> = Box<dyn Iterator<Item = Tree<'a, A>> + 'a>;
+
+/// Children of a Tree::new that have been forced so far, and the iterator for the rest
+struct Memo<'a, A> {
+    forced : Vec<Tree<'a, A>>,
+    rest : Option<Children<'a, A>>,
+    started : bool,
+}
+
+/// Dropping a memo drops its forced children, which drops their memos, and so on down the tree, so a
+/// long chain of forced shrinks would overflow the stack. Instead, take the forced children of any
+/// child that is about to be dropped for good and drop them here, one at a time.
+impl<'a, A> Drop for Memo<'a, A> {
+    fn drop(&mut self) {
+        let mut orphans = std::mem::take(&mut self.forced);
+        while let Some(mut tree) = orphans.pop() {
+            // The memo is shared by the tree and its children closure. If anything else has either of
+            // them, it might still need the forced children.
+            if Rc::strong_count(&tree.children) > 1 {
+                continue;
+            }
+            if let Some(memo) = tree.memo.take() {
+                if Rc::strong_count(&memo) == 2 {
+                    if let Ok(mut memo) = memo.try_borrow_mut() {
+                        orphans.append(&mut memo.forced);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Iterator over a Memo that forces more children when it gets past the ones already cached
+struct MemoIter<'a, A> {
+    memo : Rc<RefCell<Memo<'a, A>>>,
+    ix : usize,
+}
+
+impl<'a, A : Clone> Iterator for MemoIter<'a, A> {
+    type Item = Tree<'a, A>;
+
+    fn next(&mut self) -> Option<Tree<'a, A>> {
+        if let Some(child) = self.memo.borrow().forced.get(self.ix) {
+            self.ix += 1;
+            return Some(Tree::clone(child));
+        }
+        // Don't hold the borrow while forcing, in case computing this child forces other
+        // iterators over the same children
+        let mut rest = self.memo.borrow_mut().rest.take()?;
+        let child = rest.next();
+        let mut memo = self.memo.borrow_mut();
+        if let Some(child) = &child {
+            memo.forced.push(Tree::clone(child));
+            memo.rest = Some(rest);
+            self.ix = memo.forced.len();
+        }
+        child
+    }
 }
 
 impl<'a, A> Tree<'a, A> {
+    /// Tree with lazy children, which can be any iterator, including a Vec.
+    /// The children are cached as they're forced, so iterating over them again only clones the
+    /// subtrees, which are themselves reference counted.
+    pub fn new<F, I>(value : A, children : F) -> Tree<'a, A>
+    where F : Fn() -> I + 'a,
+    I : IntoIterator<Item = Tree<'a, A>>,
+    I::IntoIter : 'a,
+    A : 'a + Clone {
+        let memo = Rc::new(RefCell::new(Memo { forced : Vec::new(), rest : None, started : false }));
+        let cache = memo.clone();
+        let children_clo = move || -> Children<'a, A> {
+            if !cache.borrow().started {
+                cache.borrow_mut().started = true;
+                let rest : Children<'a, A> = Box::new(children().into_iter());
+                cache.borrow_mut().rest = Some(rest);
+            }
+            Box::new(MemoIter { memo : cache.clone(), ix : 0 })
+        };
+        Tree { value, children : Rc::new(children_clo), memo : Some(memo) }
+    }
+
+    /// Tree with children that aren't cached, so forcing them again calls the closure again.
+    /// This is for wrapping the children of another tree, which can cache them itself.
+    pub fn from_children(value : A, children : Rc<dyn Fn() -> Children<'a, A> + 'a>) -> Tree<'a, A> {
+        Tree { value, children, memo : None }
+    }
+
+    /// Tree without any shrinks
+    pub fn leaf(value : A) -> Tree<'a, A>
+    where A : 'a {
+        Tree::from_children(value, Rc::new(|| Box::new(std::iter::empty())))
+    }
+
     /// Try to look up a given path to a child subtree.
     /// If at any point the path leads to a child that does not exist, return instead the deepest tree
     /// in the path that does exist.
     pub fn get_path_or_closest(&self, path : &TreePath) -> Tree<'a, A>
+    where A : Clone {
+        self.get_path_prefix(path).0
+    }
+
+    /// Like get_path_or_closest, but also returns how many steps of the path were followed.
+    /// If that's less than the length of the path, the path doesn't fit this tree any more.
+    pub fn get_path_prefix(&self, path : &TreePath) -> (Tree<'a, A>, usize)
     where A : Clone {
         let mut here = Tree::clone(self);
-        for &ix in &path.indices {
-            let children = (*here.children)();
-            if ix < children.len() {
-                here = Tree::clone(&children[ix]);
-            } else {
+        for (depth, &ix) in path.indices.iter().enumerate() {
+            match (*here.children)().nth(ix) {
+                Some(child) => here = child,
                 // Out of bounds - give up and return the last leaf we got to
-                return here;
+                None => return (here, depth),
+            }
+        }
+        (here, path.len())
+    }
+
+    /// Apply a function to the value at every node of the tree.
+    /// The children are mapped lazily when they are forced.
+    pub fn map<B, F>(self, f : F) -> Tree<'a, B>
+    where F : Fn(A) -> B + 'a,
+    A : 'a, B : 'a {
+        self.map_rc(Rc::new(f))
+    }
+
+    fn map_rc<B>(self, f : Rc<dyn Fn(A) -> B + 'a>) -> Tree<'a, B>
+    where A : 'a, B : 'a {
+        let children = self.children;
+        let value = f(self.value);
+        let children_clo = move || -> Children<'a, B> {
+            let f = f.clone();
+            Box::new((*children)().map(move |c| c.map_rc(f.clone())))
+        };
+        // Not cached, so the original children are only cached once. Forcing these again only
+        // re-applies f to each child.
+        Tree::from_children(value, Rc::new(children_clo))
+    }
+
+    /// Replace the value at every node with a new tree that depends on it.
+    /// The shrinks of the outer tree come first, and each of them rebuilds the inner tree, followed
+    /// by the shrinks of the inner tree with the outer value fixed.
+    pub fn bind<B, F>(self, f : F) -> Tree<'a, B>
+    where F : Fn(A) -> Tree<'a, B> + 'a,
+    A : 'a, B : 'a {
+        self.bind_rc(Rc::new(f))
+    }
+
+    fn bind_rc<B>(self, f : Rc<dyn Fn(A) -> Tree<'a, B> + 'a>) -> Tree<'a, B>
+    where A : 'a, B : 'a {
+        let outer_children = self.children;
+        let inner = f(self.value);
+        let inner_children = inner.children;
+        let children_clo = move || -> Children<'a, B> {
+            let f = f.clone();
+            let inner_children = inner_children.clone();
+            let inner = lazily(move || (*inner_children)());
+            Box::new((*outer_children)().map(move |c| c.bind_rc(f.clone())).chain(inner))
+        };
+        Tree::from_children(inner.value, Rc::new(children_clo))
+    }
+
+    /// Pair up the values of two trees.
+    /// The shrinks are interleaved: first shrink the left value with the right fixed, then shrink the
+    /// right value with the left fixed.
+    pub fn zip<B>(self, other : Tree<'a, B>) -> Tree<'a, (A, B)>
+    where A : 'a + Clone, B : 'a + Clone {
+        let value = (self.value.clone(), other.value.clone());
+        let children_clo = move || {
+            let right = Tree::clone(&other);
+            let lefts = (*self.children)().map(move |a| a.zip(Tree::clone(&right)));
+            let (left, right) = (Tree::clone(&self), Tree::clone(&other));
+            let rights = lazily(move || (*right.children)().map(move |b| Tree::clone(&left).zip(b)));
+            lefts.chain(rights)
+        };
+        Tree::new(value, children_clo)
+    }
+
+    /// Add extra shrinks to every node of the tree, eg to enrich a generator's shrinking without
+    /// rewriting it. The existing children come first, then the values from the shrink function.
+    /// The shrink function is applied recursively, to both kinds of shrinks.
+    /// > Tree::leaf(vec![1, 2, 3]).expand(|v| if v.is_empty() { vec![] } else { vec![v[1..].to_vec()] })
+    pub fn expand<F>(self, shrink : F) -> Tree<'a, A>
+    where F : Fn(&A) -> Vec<A> + 'a,
+    A : 'a + Clone {
+        self.expand_rc(Rc::new(shrink))
+    }
+
+    fn expand_rc(self, shrink : ShrinkFn<'a, A>) -> Tree<'a, A>
+    where A : 'a + Clone {
+        let value = self.value.clone();
+        let children = move || {
+            let (shrink_extra, value) = (shrink.clone(), self.value.clone());
+            let extra = lazily(move || {
+                shrink_extra(&value).into_iter().map(move |a| Tree::leaf(a).expand_rc(shrink_extra.clone()))
+            });
+            let shrink = shrink.clone();
+            (*self.children)().map(move |c| c.expand_rc(shrink.clone())).chain(extra)
+        };
+        Tree::new(value, children)
+    }
+
+    /// Lazily remove all children that don't satisfy the predicate, along with their subtrees.
+    /// The root is kept whether or not it satisfies the predicate, so check it before filtering.
+    pub fn filter<P>(self, pred : P) -> Tree<'a, A>
+    where P : Fn(&A) -> bool + 'a,
+    A : 'a {
+        self.filter_rc(Rc::new(pred))
+    }
+
+    fn filter_rc(self, pred : Predicate<'a, A>) -> Tree<'a, A>
+    where A : 'a {
+        let children = self.children;
+        let children_clo = move || -> Children<'a, A> {
+            let (keep, pred) = (pred.clone(), pred.clone());
+            Box::new((*children)()
+                .filter(move |c| keep(&c.value))
+                .map(move |c| c.filter_rc(pred.clone())))
+        };
+        Tree::from_children(self.value, Rc::new(children_clo))
+    }
+
+    /// Combine a list of trees into a tree of lists, in the style of Hedgehog's list shrinking.
+    /// The shrinks first try removing chunks of elements, largest chunks first, as long as there
+    /// are at least min_len elements left. After that they try shrinking each element in turn.
+    pub fn interleave(trees : Vec<Tree<'a, A>>, min_len : usize) -> Tree<'a, Vec<A>>
+    where A : 'a + Clone {
+        let value = trees.iter().map(|t| t.value.clone()).collect();
+        let children_clo = move || {
+            let len = trees.len();
+
+            // Remove chunks of size len, len/2, len/4, ... 1
+            let mut chunks = Vec::new();
+            let mut chunk = len;
+            while chunk > 0 {
+                if len - chunk >= min_len {
+                    for start in (0..len).step_by(chunk) {
+                        chunks.push((start, (start + chunk).min(len)));
+                    }
+                }
+                chunk /= 2;
+            }
+            // The last chunk of each size can be shorter, so sort to keep the biggest removals first.
+            // The sort is stable, so chunks of the same size stay in order.
+            chunks.sort_by_key(|(start, end)| std::cmp::Reverse(end - start));
+            let whole = trees.clone();
+            let removals = chunks.into_iter().map(move |(start, end)| {
+                let mut rest = whole.clone();
+                rest.drain(start..end);
+                Tree::interleave(rest, min_len)
+            });
+
+            // Shrink each element, leaving the others as they are
+            let whole = trees.clone();
+            let shrinks = (0..len).flat_map(move |ix| {
+                let whole = whole.clone();
+                (*whole[ix].children)().map(move |child| {
+                    let mut shrunk = whole.clone();
+                    shrunk[ix] = child;
+                    Tree::interleave(shrunk, min_len)
+                })
+            });
+
+            removals.chain(shrinks)
+        };
+        Tree::new(value, children_clo)
+    }
+
+    /// Follow the first shrink of each node until there are no more shrinks.
+    /// For the integer generators, which try halving first, this quickly finds the smallest value.
+    pub fn first_shrink_leaf(&self) -> A
+    where A : Clone {
+        let mut here = Tree::clone(self);
+        loop {
+            match (*here.children)().next() {
+                None => return here.value,
+                Some(child) => here = child,
+            }
+        }
+    }
+
+    /// Number of nodes down to the given depth, where the root is at depth zero.
+    /// This forces every node above that depth, so it can take a long time for big trees.
+    pub fn count_to_depth(&self, depth : usize) -> usize {
+        let mut count = 1;
+        // Explicit stack of the children still to count, so deep trees don't overflow
+        let mut todo : Vec<(Children<'a, A>, usize)> = Vec::new();
+        if depth > 0 {
+            todo.push(((*self.children)(), 1));
+        }
+        while let Some((children, level)) = todo.last_mut() {
+            let level = *level;
+            match children.next() {
+                None => {
+                    todo.pop();
+                }
+                Some(child) => {
+                    count += 1;
+                    if level < depth {
+                        todo.push(((*child.children)(), level + 1));
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    /// Number of nodes in the tree, forcing at most budget nodes breadth-first.
+    /// Trees that are bigger than the budget, including infinite ones, only get a lower bound.
+    pub fn estimate_size(&self, budget : usize) -> SizeEstimate {
+        if budget == 0 {
+            return SizeEstimate::AtLeast(0);
+        }
+        let mut count = 1;
+        // Children that haven't been counted yet, a level at a time
+        let mut queue : VecDeque<Children<'a, A>> = VecDeque::new();
+        queue.push_back((*self.children)());
+        while let Some(children) = queue.front_mut() {
+            match children.next() {
+                None => {
+                    queue.pop_front();
+                }
+                Some(child) => {
+                    if count == budget {
+                        return SizeEstimate::AtLeast(count);
+                    }
+                    count += 1;
+                    queue.push_back((*child.children)());
+                }
+            }
+        }
+        SizeEstimate::Exact(count)
+    }
+
+    /// Length of the longest chain of shrinks, looking at most bound levels down.
+    /// Stops as soon as some path reaches the bound, but otherwise forces every node above it.
+    pub fn max_depth(&self, bound : usize) -> usize {
+        let mut deepest = 0;
+        // The children of each node on the current path, so the path length is the depth
+        let mut path : Vec<Children<'a, A>> = Vec::new();
+        if bound > 0 {
+            path.push((*self.children)());
+        }
+        while let Some(children) = path.last_mut() {
+            match children.next() {
+                None => {
+                    path.pop();
+                }
+                Some(child) => {
+                    deepest = deepest.max(path.len());
+                    if deepest == bound {
+                        break;
+                    }
+                    path.push((*child.children)());
+                }
+            }
+        }
+        deepest
+    }
+
+    /// Call the visitor on every node down to max_depth, depth first, with the node's value, its depth
+    /// and how many children it has. The root is at depth zero.
+    /// Counting the children forces them, so this forces one level below max_depth.
+    /// > let stats = tree.visit(3, |value, depth, children| println!("{:w$}{:?} ({})", "", value, children, w = depth * 2));
+    pub fn visit<F>(&self, max_depth : usize, mut visitor : F) -> VisitStats
+    where F : FnMut(&A, usize, usize) {
+        let mut stats = VisitStats::default();
+        // Explicit stack rather than recursion, so deep trees don't overflow
+        // The root is None, so that it doesn't need cloning
+        let mut todo : Vec<(Option<Tree<'a, A>>, usize)> = vec![(None, 0)];
+        while let Some((node, depth)) = todo.pop() {
+            let node = node.as_ref().unwrap_or(self);
+            let children : Vec<Tree<'a, A>> = (*node.children)().collect();
+            visitor(&node.value, depth, children.len());
+            stats.nodes += 1;
+            stats.max_depth = stats.max_depth.max(depth);
+            if children.is_empty() {
+                stats.leaves += 1;
+            } else if depth == max_depth {
+                stats.truncated += 1;
+            } else {
+                todo.extend(children.into_iter().rev().map(|c| (Some(c), depth + 1)));
+            }
+        }
+        stats
+    }
+
+    /// Indented outline of the tree down to max_depth, with each value on its own line; see
+    /// tree::render::to_text
+    pub fn render(&self, max_depth : usize) -> String
+    where A : fmt::Debug {
+        crate::tree::render::to_text(self, max_depth)
+    }
+}
+
+/// Shows the value but not the children, eg
+/// > Tree { value: 10, children: <not forced> }
+/// Forcing the children could run generators, or unwind with Discard, so formatting never does it;
+/// use render to see them.
+impl<'a, A : fmt::Debug> fmt::Debug for Tree<'a, A> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        struct NotForced;
+        impl fmt::Debug for NotForced {
+            fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "<not forced>")
             }
         }
-        here
+        f.debug_struct("Tree")
+            .field("value", &self.value)
+            .field("children", &NotForced)
+            .finish()
     }
 }
 
+/// Counts from Tree::visit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VisitStats {
+    /// Nodes visited
+    pub nodes : usize,
+    /// Visited nodes without any children
+    pub leaves : usize,
+    /// Depth of the deepest node visited
+    pub max_depth : usize,
+    /// Nodes at max_depth whose children weren't visited
+    pub truncated : usize,
+}
+
+/// Result of Tree::estimate_size
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizeEstimate {
+    /// The whole tree fit in the budget
+    Exact(usize),
+    /// The budget ran out, so there are at least this many nodes
+    AtLeast(usize),
+}
+
+/// Iterator that only calls f once it's first polled, eg to force the children of a tree only after
+/// the shrinks before them have been tried
+pub(crate) fn lazily<'a, I, F>(f : F) -> impl Iterator<Item = I::Item> + 'a
+where F : FnOnce() -> I + 'a,
+I : IntoIterator + 'a,
+I::IntoIter : 'a {
+    std::iter::once(f).flat_map(|f| f())
+}
+
 /// Path to a value in a rose tree - each element of indices is the index of a child node
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TreePath {
     indices : Vec<usize>
 }
@@ -42,208 +485,2039 @@ impl TreePath {
     pub fn empty() -> TreePath {
         TreePath { indices : Vec::new() }
     }
-}
 
-/// Generator is a function from RNG and gen size to a tree
-#[derive(Clone)]
-pub struct Gen<'a, A> {
-    pub run : Rc<dyn Fn(Random, usize) -> Tree<'a, A> + 'a>
-}
+    /// The child index at each step from the root
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
 
-impl<'a, A> Gen<'a, A> {
-    /// Helper for constructing Gen<A> from closure
-    pub fn new<F>(f : F) -> Gen<'a, A>
-    where F : Fn(Random, usize) -> Tree<'a, A> + 'a {
-        Gen { run : Rc::new(f) }
+    /// Number of steps from the root
+    pub fn len(&self) -> usize {
+        self.indices.len()
     }
 
-    /// Joining together generators, comparable to the monad and applicative instances.
-    /// Monads are about making sure values that depend on effects are encapsulated and don't escape.
-    /// Linear types can achieve similar things without monads, so borrowing should be able to do the same thing.
-    /// 
-    /// As an example, here is how you might generate a date struct with separate year, month and day:
-    /// > Gen::combine(|c| {
-    /// >  Date {
-    /// >    year:  c.of(Gen::u64(0..2021)),
-    /// >    month: c.of(Gen::u64(0..12)),
-    /// >    day:   c.of(Gen::u64(0..32)),
-    /// >  }
-    /// > })
-    /// 
-    /// The combine function takes a closure of type "&mut Chooser -> A", and wraps the result in a Gen<A>.
-    /// This "Chooser" represents the ability to extract a value from a Gen, using the Chooser method
-    /// > Chooser::of<A>(&mut self, gen : Gen<A>) -> A
-    /// Having a Chooser is a bit like being inside a monadic bind, in that you can use the chooser
-    /// to extract a value from a Gen<A> and then manipulate it.
-    /// The main complication is that a Chooser can only be used within the context of this 'combine' function,
-    /// because otherwise one could arbitrarily pull values out of a Gen.
-    /// The trick here is that the borrow checker ensures that the mutable reference to the Chooser
-    /// cannot escape the closure: it cannot be stored in a larger structure or returned or even used
-    /// in a nested call to combine, because these cases might allow the Chooser to be used after
-    /// combine has dropped it.
-    ///
-    /// The closure to combine needs to implement Clone --- presumably this means all values in its
-    /// closure environment need to also implement Clone. This is required because the closure is
-    /// stored in the lazy children of the tree.
-    pub fn combine<F>(f : F) -> Gen<'a, A>
-    where F : Fn(&mut Chooser) -> A + 'a + Clone,
-    A : 'a {
-        Gen::new(move |r, s| {
-            Self::combine_go(f.clone(), r, s, Vec::new())
-        })
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
     }
 
-    /// Worker function for combine, recursively generates the shrink tree
-    fn combine_go<F>(f : F, r : Random, s : usize, mut paths : Vec<TreePath>) -> Tree<'a, A>
-    where F : Fn(&mut Chooser) -> A + 'a + Clone,
-    A : 'a {
-        // println!("Gen::combine_go {:#?}", paths);
-        // Run with given shrink paths to get result value & check how many further shrinks are possible
-        let mut c = Chooser::new(r, s, paths.clone());
-        let value = f(&mut c);
+    /// Go one step further down, to child ix
+    pub fn push(&mut self, ix : usize) {
+        self.indices.push(ix);
+    }
+
+    /// Go back up to the parent, returning the index of the child that was removed
+    pub fn pop(&mut self) -> Option<usize> {
+        self.indices.pop()
+    }
+
+    /// Path that follows this one and then other, starting from where this one ends
+    pub fn concat(&self, other : &TreePath) -> TreePath {
+        TreePath { indices : self.indices.iter().chain(&other.indices).copied().collect() }
+    }
 
-        // Make sure that the paths vector contains an empty shrink path for each generator
-        let gen_count = c.gen_child_count.len();
-        while paths.len() < gen_count {
-            paths.push(TreePath::empty());
+    /// Longest path that both paths start with
+    pub fn common_prefix(&self, other : &TreePath) -> TreePath {
+        let len = self.indices.iter().zip(&other.indices).take_while(|(a, b)| a == b).count();
+        TreePath { indices : self.indices[..len].to_vec() }
+    }
+
+    /// Whether this path starts with prefix
+    pub fn starts_with(&self, prefix : &TreePath) -> bool {
+        self.indices.starts_with(&prefix.indices)
+    }
+
+    /// Keep only the first depth steps
+    pub fn truncate(&mut self, depth : usize) {
+        self.indices.truncate(depth);
+    }
+
+    /// Every prefix of the path, from the empty path up to the whole path
+    /// > "3.0".parse::<TreePath>()?.prefixes() yields "", "3", "3.0"
+    pub fn prefixes(&self) -> impl Iterator<Item = TreePath> + '_ {
+        (0 ..= self.indices.len()).map(move |len| TreePath { indices : self.indices[..len].to_vec() })
+    }
+
+    /// Compact binary encoding, with each index as an unsigned LEB128 varint
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for &ix in &self.indices {
+            let mut ix = ix as u64;
+            while ix >= 0x80 {
+                bytes.push((ix as u8 & 0x7F) | 0x80);
+                ix >>= 7;
+            }
+            bytes.push(ix as u8);
         }
+        bytes
+    }
 
-        let children_clo = move || {
-            let mut children : Vec<Tree<A>> = Vec::new();
-
-            // Loop through all the generators that the closure used.
-            // child_count denotes how many options this generator has for shrinking.
-            for (gen_ix, &child_count) in c.gen_child_count.iter().enumerate() {
-                // println!("Gen::combine_go.gen_ix: {}", gen_ix);
-                // Loop over the shrink options for this generator
-                for child_ix in 0 .. child_count {
-                    // println!("Gen::combine_go.child_ix: {}/{}", child_ix, child_count);
-                    // Add this shrink option to the path and compute it
-                    // This does a bunch more clones than really necessary, but whatever
-                    let mut paths_copy = paths.clone();
-                    paths_copy[gen_ix].indices.push(child_ix);
-                    children.push(Self::combine_go(f.clone(), r, s, paths_copy));
-                }
+    pub fn from_bytes(bytes : &[u8]) -> Result<TreePath, ParsePathError> {
+        let mut indices = Vec::new();
+        let mut ix : u64 = 0;
+        let mut shift = 0;
+        for &b in bytes {
+            if shift >= 64 || (shift > 0 && u64::from(b & 0x7F) >> (64 - shift) != 0) {
+                return Err(ParsePathError("index too large"));
             }
+            ix |= u64::from(b & 0x7F) << shift;
+            shift += 7;
+            if b & 0x80 == 0 {
+                indices.push(usize::try_from(ix).map_err(|_| ParsePathError("index too large"))?);
+                ix = 0;
+                shift = 0;
+            }
+        }
+        if shift > 0 {
+            return Err(ParsePathError("truncated index"));
+        }
+        Ok(TreePath { indices })
+    }
 
-            children
-        };
+    /// The binary encoding as URL-safe base64 without padding, for pasting into bug reports
+    /// > TreePath::from_str("3.0.7.1").unwrap().to_base64() == "AwAHAQ"
+    pub fn to_base64(&self) -> String {
+        let bytes = self.to_bytes();
+        let mut out = String::new();
+        for chunk in bytes.chunks(3) {
+            let bits = chunk.iter().enumerate().fold(0u32, |acc, (i, &b)| acc | u32::from(b) << (16 - 8 * i));
+            for i in 0 ..= chunk.len() {
+                out.push(BASE64[(bits >> (18 - 6 * i)) as usize & 0x3F] as char);
+            }
+        }
+        out
+    }
 
-        Tree {
-            value, children : Rc::new(children_clo)
+    pub fn from_base64(text : &str) -> Result<TreePath, ParsePathError> {
+        let mut bytes = Vec::new();
+        let mut bits : u32 = 0;
+        let mut count = 0;
+        for c in text.bytes() {
+            let digit = BASE64.iter().position(|&d| d == c).ok_or(ParsePathError("invalid base64 character"))?;
+            bits = bits << 6 | digit as u32;
+            count += 6;
+            if count >= 8 {
+                count -= 8;
+                bytes.push((bits >> count) as u8);
+            }
+        }
+        // Leftover bits are padding, and must be zero for the encoding to be canonical
+        if count >= 6 || bits & ((1 << count) - 1) != 0 {
+            return Err(ParsePathError("invalid base64 length"));
         }
+        TreePath::from_bytes(&bytes)
     }
+}
+
+/// URL-safe base64 alphabet
+const BASE64 : &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
 
+/// Child indices separated by dots, eg "3.0.7.1". The empty path is the empty string.
+impl fmt::Display for TreePath {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        for (i, ix) in self.indices.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", ix)?;
+        }
+        Ok(())
+    }
 }
 
-/// At an abstract level, Chooser is a capability or evidence that you're allowed to
-/// get the values out of a Gen.
-/// In terms of implementation, a particular Chooser describes how much to shrink each generator
-/// and records how each generator can be shrunk further.
-pub struct Chooser {
-    /// State: random generator with seed
-    rand : Random,
-    /// Input argument to Gen: generator size
-    // XXX: is there any way to specify const/immutable fields in Rust?
-    size : usize,
-    /// Input: path describing how to shrink each generator
-    gen_paths : Vec<TreePath>,
-    /// State: how many children (ie potential shrinks) for each generator we've seen so far
-    gen_child_count : Vec<usize>,
+impl From<Vec<usize>> for TreePath {
+    fn from(indices : Vec<usize>) -> TreePath {
+        TreePath { indices }
+    }
 }
 
-impl Chooser {
-    fn new(rand : Random, size : usize, gen_paths: Vec<TreePath>) -> Chooser {
-        Chooser {
-            rand, size, gen_paths,
-            gen_child_count: Vec::new()
+impl FromStr for TreePath {
+    type Err = ParsePathError;
+
+    fn from_str(text : &str) -> Result<TreePath, ParsePathError> {
+        if text.is_empty() {
+            return Ok(TreePath::empty());
         }
+        let indices = text.split('.')
+            .map(|ix| ix.parse().map_err(|_| ParsePathError("expected indices separated by dots")))
+            .collect::<Result<_, _>>()?;
+        Ok(TreePath { indices })
     }
+}
 
-    pub fn of<A>(&mut self, gen : Gen<A>) -> A
-    where A : Clone {
-        // println!("Chooser::of");
-        let child_rand = self.rand.split();
-        let tree = (*gen.run)(child_rand, self.size);
+/// Error from parsing a TreePath, with a description of what was wrong
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParsePathError(&'static str);
 
-        let ix = self.gen_child_count.len();
-        let shrunk = match self.gen_paths.get(ix) {
-            None => tree,
-            Some(p) => tree.get_path_or_closest(p)
-        };
+impl fmt::Display for ParsePathError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid tree path: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParsePathError {}
+
+/// Payload used to unwind out of a generator that could not produce a value, such as a filter that
+/// ran out of retries.
+/// The runner catches these and counts the test case as discarded rather than failed.
+#[derive(Debug)]
+pub struct Discard;
+
+/// Give up on generating the current test case
+pub fn discard() -> ! {
+    // resume_unwind doesn't call the panic hook, so discards don't print anything
+    std::panic::resume_unwind(Box::new(Discard))
+}
 
-        // XXX: maybe could put length as strict field in Tree to avoid forcing here, probably not a big deal
-        let children = (*shrunk.children)();
+/// How many times Gen::filter tries to generate a value before discarding the test case
+pub const FILTER_RETRIES : usize = 100;
 
-        self.gen_child_count.push(children.len());
+/// The biased integer generators pick edge cases this often
+pub const BIASED_EDGE_PERCENT : u64 = 25;
 
-        shrunk.value.clone()
+/// Gen::from_corpus returns an unmodified sample this often
+pub const CORPUS_PERCENT : u64 = 50;
+
+thread_local! {
+    /// Depth budget for combine generators run by Chooser::with_smaller_depth.
+    /// None outside of with_smaller_depth, where the depth comes from the size instead.
+    static COMBINE_DEPTH : Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Default depth budget for recursive generators, which grows with the log of the size
+pub fn depth_for_size(size : usize) -> usize {
+    (usize::BITS - size.leading_zeros()) as usize + 1
+}
+
+/// Distances from the origin to try when shrinking a value diff away from it, like Hedgehog's
+/// towards. The first is the origin itself, then each one halves the distance left to the value,
+/// ending one step away from it. They're all distinct, so a node never has duplicate shrinks, and
+/// shrinking does a binary search toward the smallest failing value.
+/// > towards(10) == vec![0, 5, 8, 9]
+pub(crate) fn towards(diff : u128) -> Vec<u128> {
+    let mut distances = Vec::new();
+    let mut half = diff;
+    while half > 0 {
+        distances.push(diff - half);
+        half /= 2;
     }
+    distances
 }
 
+/// User-provided shrink function for Tree::expand and Gen::with_shrinker
+type ShrinkFn<'a, A> = Rc<dyn Fn(&A) -> Vec<A> + 'a>;
 
+/// Predicate for Tree::filter and Gen::filter
+type Predicate<'a, A> = Rc<dyn Fn(&A) -> bool + 'a>;
 
+/// The node of its shrink tree that a generator used by a combine closure got to, with the value type
+/// left out so that a combine node can keep the nodes of all of its generators
+trait UsedNode<'a> {
+    /// The node's value, if it came from Chooser::of_cached and so can be taken back out without
+    /// running the generator again
+    fn value(&self) -> Option<&dyn Any>;
+    fn children(&self) -> Box<dyn Iterator<Item = Rc<dyn UsedNode<'a> + 'a>> + 'a>;
+}
 
+/// Node of a generator used by Chooser::of. The value might not be 'static, so it can't be taken
+/// back out, and the generator is run again instead.
+impl<'a, A : Clone + 'a> UsedNode<'a> for Tree<'a, A> {
+    fn value(&self) -> Option<&dyn Any> {
+        None
+    }
 
-impl<'a> Gen<'a, u64> {
-    pub fn u64(range : Range<u64>) -> Gen<'a, u64> {
-        // println!("Gen::u64 {:#?}", range);
-        // XXX: Range is not Copy (for reasons), so need to clone it.
-        // Maybe shrink_u64 should be by-ref.
-        // Probably want a different Range type with more information anyway (eg midpoint/shrink-to)
-        Gen::new(move |mut r, _s| {
-            // println!("Gen::u64.new {:#?}", range);
-            let value = r.u64_range(range.clone());
-            Self::shrink_u64(range.clone(), value)
-        })
+    fn children(&self) -> Box<dyn Iterator<Item = Rc<dyn UsedNode<'a> + 'a>> + 'a> {
+        Box::new((*self.children)().map(|child| Rc::new(child) as Rc<dyn UsedNode<'a> + 'a>))
     }
+}
 
-    fn shrink_u64(range : Range<u64>, value : u64) -> Tree<'a, u64> {
-        let v0 = value - range.start;
-        // println!("Gen::shrink_u64 {:#?} {} diff {}", range, value, v0);
-        let children = move || {
-            if v0 > 4 {
-                vec![
-                    Self::shrink_u64(range.clone(), range.start + v0 / 2),
-                    Self::shrink_u64(range.clone(), value - 1)
-                ]
-            } else if v0 > 0 {
-                vec![Self::shrink_u64(range.clone(), value - 1)]
-            } else {
-                vec![]
-            }
-        };
-        Tree { value, children: Rc::new(children) }
+/// Node of a generator used by Chooser::of_cached, whose value is 'static so it can be downcast
+struct CachedNode<'a, A>(Tree<'a, A>);
+
+impl<'a, A : Clone + 'static> UsedNode<'a> for CachedNode<'a, A> {
+    fn value(&self) -> Option<&dyn Any> {
+        Some(&self.0.value)
     }
 
-    pub fn usize(range : Range<usize>) -> Gen<'a, usize> {
-        // println!("Gen::usize {:#?}", range);
-        Gen::combine(move |c| {
-            // println!("Gen::usize.combine {:#?}", range);
-            c.of(Gen::u64(range.start as u64 .. range.end as u64)) as usize
-        })
+    fn children(&self) -> Box<dyn Iterator<Item = Rc<dyn UsedNode<'a> + 'a>> + 'a> {
+        Box::new((*self.0.children)().map(|child| Rc::new(CachedNode(child)) as Rc<dyn UsedNode<'a> + 'a>))
     }
 }
 
-impl<'a, A> Gen<'a, A> {
-    pub fn choose(v : Vec<A>) -> Gen<'a, A>
-    where A : 'a + Clone {
-        Gen::combine(move |c| {
-            let ix = c.of(Gen::usize(0..v.len()));
-            v[ix].clone()
-        })
+/// Shrink path of a generator used by a combine closure, as a list from the last step back to the
+/// root. A child of a combine node adds a step to the path of the generator it shrinks without
+/// copying it, so the paths of a whole chain of shrinks share their steps.
+#[derive(Clone, Default)]
+struct SharedPath(Option<Rc<PathStep>>);
+
+struct PathStep {
+    ix : usize,
+    len : usize,
+    parent : SharedPath,
+}
+
+impl SharedPath {
+    fn len(&self) -> usize {
+        self.0.as_ref().map_or(0, |step| step.len)
     }
 
-    pub fn vec(self, gen_len : Gen<'a, usize>) -> Gen<'a, Vec<A>>
-    where A : 'a + Clone {
-        Gen::combine(move |c| {
-            let len = c.of(gen_len.clone());
-            let mut vec = Vec::new();
-            for _ in 0..len {
-                vec.push(c.of(self.clone()));
-            }
-            vec
-        })
+    fn push(&self, ix : usize) -> SharedPath {
+        SharedPath(Some(Rc::new(PathStep { ix, len : self.len() + 1, parent : self.clone() })))
+    }
+
+    /// The first len steps of the path, which are shared with it
+    fn truncate(&self, len : usize) -> SharedPath {
+        let mut path = self.clone();
+        while path.len() > len {
+            path = path.0.as_ref().map(|step| step.parent.clone()).unwrap_or_default();
+        }
+        path
+    }
+
+    fn to_path(&self) -> TreePath {
+        let mut indices = vec![0; self.len()];
+        let mut here = &self.0;
+        while let Some(step) = here {
+            indices[step.len - 1] = step.ix;
+            here = &step.parent.0;
+        }
+        TreePath::from(indices)
+    }
+}
+
+/// Dropping the last reference to a long path would drop its steps recursively, so drop them one at a
+/// time instead
+impl Drop for PathStep {
+    fn drop(&mut self) {
+        let mut parent = self.parent.0.take();
+        while let Some(step) = parent {
+            parent = match Rc::try_unwrap(step) {
+                Ok(mut step) => step.parent.0.take(),
+                Err(_) => None,
+            };
+        }
     }
 }
 
+/// A generator used by a combine closure: which call it was, the random generator it was run with,
+/// the shrink path taken in its tree, and the node the path led to
+#[derive(Clone)]
+struct GenUse<'a> {
+    id : CallId,
+    rand : Random,
+    path : SharedPath,
+    node : Rc<dyn UsedNode<'a> + 'a>,
+}
+
+/// Each generator used by a combine closure, in the order it used them.
+/// The paths and nodes are shared between the nodes of the shrink tree, and a child node only adds a
+/// step to the path of the generator that it shrinks.
+type GenPaths<'a> = Vec<GenUse<'a>>;
+
+/// Where a combine closure got a value from a generator: the source location of the Chooser::of call,
+/// or a label given to Chooser::of_labelled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum CallSite {
+    Location(&'static Location<'static>),
+    Label(&'static str),
+}
+
+/// Identifies a generator used by a combine closure, so that its seed and shrink path stay with it even
+/// when an earlier shrink changes which generators the closure uses.
+/// The occurrence counts calls from the same site, eg for each element of a list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct CallId {
+    site : CallSite,
+    occurrence : usize,
+}
+
+impl CallId {
+    /// Key for forking the random generator of a call the closure hasn't made before, given how many
+    /// calls came before it. Labelled calls hash the label, so they get the same seed wherever they
+    /// are in the closure; other calls go by the order they're made in, since their source locations
+    /// change from one platform or edit to the next.
+    fn seed_key(&self, call : usize) -> u64 {
+        match self.site {
+            CallSite::Location(_) => call as u64,
+            CallSite::Label(label) => {
+                let mut hasher = Fnv1a::default();
+                hasher.write(label.as_bytes());
+                hasher.write_u64(self.occurrence as u64);
+                hasher.finish()
+            }
+        }
+    }
+}
+
+/// Generator is a function from RNG and gen size to a tree.
+///
+/// Shrink order contract: for a fixed seed and size, the generators here produce the same tree
+/// every time, and the children of every node come in the same order, with the most aggressive
+/// shrinks first, eg the origin before values closer to the original, or removing half a list before
+/// removing single elements. Greedy shrinking, deduplication and replaying a TreePath all rely on
+/// this. Custom generators can check it with the laws module.
+#[derive(Clone)]
+pub struct Gen<'a, A> {
+    pub run : Rc<dyn Fn(Random, usize) -> Tree<'a, A> + 'a>,
+    /// Enumerate every value the generator can produce, if there are at most `limit` of them.
+    /// Returns None if the domain is too big or can't be enumerated.
+    /// Values should be in shrink order, so the first value is the one everything shrinks to.
+    pub enumerate : Rc<dyn Fn(usize) -> Option<NonEmpty<A>> + 'a>,
+}
+
+/// Generators are functions, so there's nothing to show but the type
+impl<'a, A> fmt::Debug for Gen<'a, A> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Gen<{}> {{ .. }}", std::any::type_name::<A>())
+    }
+}
+
+impl<'a, A> Gen<'a, A> {
+    /// Helper for constructing Gen<A> from closure.
+    /// The generator is not enumerable; use with_enumerate to add an enumeration.
+    pub fn new<F>(f : F) -> Gen<'a, A>
+    where F : Fn(Random, usize) -> Tree<'a, A> + 'a {
+        Gen { run : Rc::new(f), enumerate : Rc::new(|_limit| None) }
+    }
+
+    /// Always generate the same value, with no shrinks
+    pub fn constant(a : A) -> Gen<'a, A>
+    where A : 'a + Clone {
+        let a_enum = a.clone();
+        Gen::new(move |_r, _s| {
+            Tree::leaf(a.clone())
+        }).with_enumerate(move |_limit| {
+            Some(NonEmpty { zero : a_enum.clone(), vec : Vec::new() })
+        })
+    }
+
+    /// Generator from a function of the random generator and size, for writing generators without
+    /// building a shrink tree. The generated values don't shrink.
+    pub fn from_fn<F>(f : F) -> Gen<'a, A>
+    where F : Fn(&mut Random, usize) -> A + 'a,
+    A : 'a {
+        Gen::new(move |mut r, s| {
+            Tree::leaf(f(&mut r, s))
+        })
+    }
+
+    /// Generator that depends on the size, for example to pick a collection length.
+    /// Sized generators can't be enumerated.
+    pub fn sized<F>(f : F) -> Gen<'a, A>
+    where F : Fn(usize) -> Gen<'a, A> + 'a {
+        Gen::new(move |r, s| {
+            (*f(s).run)(r, s)
+        })
+    }
+
+    /// Run the generator with a fixed size, ignoring the size from the runner
+    pub fn resize(self, size : usize) -> Gen<'a, A>
+    where A : 'a {
+        self.scale(move |_s| size)
+    }
+
+    /// Adjust the size the generator is run with
+    /// > gen.scale(|s| s / 2)
+    pub fn scale<F>(self, f : F) -> Gen<'a, A>
+    where F : Fn(usize) -> usize + 'a,
+    A : 'a {
+        let run = self.run;
+        Gen {
+            run : Rc::new(move |r, s| (*run)(r, f(s))),
+            enumerate : self.enumerate,
+        }
+    }
+
+    /// Apply a function to the generated value.
+    /// This maps over the shrink tree directly, so unlike a combine closure it doesn't need to re-run
+    /// the generator for each shrink.
+    pub fn map<B, F>(self, f : F) -> Gen<'a, B>
+    where F : Fn(A) -> B + 'a,
+    A : 'a, B : 'a {
+        let f : Rc<dyn Fn(A) -> B + 'a> = Rc::new(f);
+        let f_enum = f.clone();
+        let run = self.run;
+        let enumerate = self.enumerate;
+        Gen::new(move |r, s| {
+            (*run)(r, s).map_rc(f.clone())
+        }).with_enumerate(move |limit| {
+            (*enumerate)(limit).map(|values| values.map(|a| f_enum(a)))
+        })
+    }
+
+    /// Use the generated value to pick the next generator, like monadic bind.
+    /// Prefer combine where possible; this is for when the choice of generator genuinely depends on
+    /// an earlier value, such as generating an index into a generated vector.
+    /// The dependent generator's shrink tree is grafted onto each node of this generator's tree, so
+    /// shrinking the first value re-runs the dependent generator with the same seed.
+    pub fn and_then<B, F>(self, f : F) -> Gen<'a, B>
+    where F : Fn(A) -> Gen<'a, B> + 'a,
+    A : 'a, B : 'a {
+        let f = Rc::new(f);
+        let f_enum = f.clone();
+        let run = self.run;
+        let enumerate = self.enumerate;
+        Gen::new(move |mut r, s| {
+            let r_inner = r.split();
+            let f = f.clone();
+            (*run)(r, s).bind_rc(Rc::new(move |a| (*f(a).run)(r_inner, s)))
+        }).with_enumerate(move |limit| {
+            let mut values = Vec::new();
+            for a in (*enumerate)(limit)?.to_vec() {
+                values.extend((*f_enum(a).enumerate)(limit)?.to_vec());
+                if values.len() > limit {
+                    return None;
+                }
+            }
+            let mut values = values.into_iter();
+            Some(NonEmpty { zero : values.next()?, vec : values.collect() })
+        })
+    }
+
+    /// Generate a pair of values from two independent generators.
+    /// Each component keeps its own shrinks, see Tree::zip.
+    pub fn zip<B>(self, other : Gen<'a, B>) -> Gen<'a, (A, B)>
+    where A : 'a + Clone, B : 'a + Clone {
+        let (run_a, run_b) = (self.run, other.run);
+        let (enumerate_a, enumerate_b) = (self.enumerate, other.enumerate);
+        Gen::new(move |mut r, s| {
+            let r_b = r.split();
+            (*run_a)(r, s).zip((*run_b)(r_b, s))
+        }).with_enumerate(move |limit| {
+            let values_a = (*enumerate_a)(limit)?;
+            let values_b = (*enumerate_b)(limit)?;
+            if values_a.len() * values_b.len() > limit {
+                return None;
+            }
+            let values_b = values_b.to_vec();
+            let mut pairs = values_a.to_vec().into_iter()
+                .flat_map(|a| values_b.iter().map(move |b| (a.clone(), b.clone())));
+            Some(NonEmpty { zero : pairs.next()?, vec : pairs.collect() })
+        })
+    }
+
+    /// Combine two independent generators with a function.
+    /// This zips the shrink trees directly, so unlike combine the function doesn't need to be Clone.
+    pub fn map2<X, Y, F>(gx : Gen<'a, X>, gy : Gen<'a, Y>, f : F) -> Gen<'a, A>
+    where F : Fn(X, Y) -> A + 'a,
+    X : 'a + Clone, Y : 'a + Clone, A : 'a {
+        gx.zip(gy).map(move |(x, y)| f(x, y))
+    }
+
+    /// See map2
+    pub fn map3<X, Y, Z, F>(gx : Gen<'a, X>, gy : Gen<'a, Y>, gz : Gen<'a, Z>, f : F) -> Gen<'a, A>
+    where F : Fn(X, Y, Z) -> A + 'a,
+    X : 'a + Clone, Y : 'a + Clone, Z : 'a + Clone, A : 'a {
+        gx.zip(gy).zip(gz).map(move |((x, y), z)| f(x, y, z))
+    }
+
+    /// See map2
+    pub fn map4<W, X, Y, Z, F>(gw : Gen<'a, W>, gx : Gen<'a, X>, gy : Gen<'a, Y>, gz : Gen<'a, Z>, f : F) -> Gen<'a, A>
+    where F : Fn(W, X, Y, Z) -> A + 'a,
+    W : 'a + Clone, X : 'a + Clone, Y : 'a + Clone, Z : 'a + Clone, A : 'a {
+        gw.zip(gx).zip(gy).zip(gz).map(move |(((w, x), y), z)| f(w, x, y, z))
+    }
+
+    /// See map2
+    #[allow(clippy::too_many_arguments)]
+    pub fn map5<V, W, X, Y, Z, F>(gv : Gen<'a, V>, gw : Gen<'a, W>, gx : Gen<'a, X>, gy : Gen<'a, Y>, gz : Gen<'a, Z>, f : F) -> Gen<'a, A>
+    where F : Fn(V, W, X, Y, Z) -> A + 'a,
+    V : 'a + Clone, W : 'a + Clone, X : 'a + Clone, Y : 'a + Clone, Z : 'a + Clone, A : 'a {
+        gv.zip(gw).zip(gx).zip(gy).zip(gz).map(move |((((v, w), x), y), z)| f(v, w, x, y, z))
+    }
+
+    /// Keep the generated values but never shrink them, eg for random nonces and keys, or values
+    /// that are too expensive to shrink.
+    pub fn no_shrink(self) -> Gen<'a, A>
+    where A : 'a {
+        let run = self.run;
+        Gen {
+            run : Rc::new(move |r, s| Tree::leaf((*run)(r, s).value)),
+            enumerate : self.enumerate,
+        }
+    }
+
+    /// Add domain-specific shrinks, eg simplifying an AST.
+    /// The generator's own shrinks are tried first, then the values from the shrink function; see
+    /// Tree::expand.
+    pub fn with_shrinker<F>(self, shrink : F) -> Gen<'a, A>
+    where F : Fn(&A) -> Vec<A> + 'a,
+    A : 'a + Clone {
+        let shrink : ShrinkFn<'a, A> = Rc::new(shrink);
+        let run = self.run;
+        Gen {
+            run : Rc::new(move |r, s| (*run)(r, s).expand_rc(shrink.clone())),
+            enumerate : self.enumerate,
+        }
+    }
+
+    /// Replace the generator's shrinks with a shrink function, applied recursively
+    pub fn replace_shrinker<F>(self, shrink : F) -> Gen<'a, A>
+    where F : Fn(&A) -> Vec<A> + 'a,
+    A : 'a + Clone {
+        self.no_shrink().with_shrinker(shrink)
+    }
+
+    /// Generate n values for debugging, eg to see what a generator's values look like at a size.
+    /// Each value uses a different split of the seed.
+    pub fn sample(&self, seed : u128, size : usize, n : usize) -> Vec<A> {
+        let mut r = Random::new_from_seed(seed);
+        (0..n).map(|_| (*self.run)(r.split(), size).value).collect()
+    }
+
+    /// Generate one value, along with its immediate shrinks, for debugging
+    pub fn sample_tree(&self, seed : u128, size : usize) -> (A, Vec<A>) {
+        let tree = (*self.run)(Random::new_from_seed(seed), size);
+        let shrinks = (*tree.children)().map(|t| t.value).collect();
+        (tree.value, shrinks)
+    }
+
+    /// Replace the enumeration of the generator's domain
+    pub fn with_enumerate<F>(self, f : F) -> Gen<'a, A>
+    where F : Fn(usize) -> Option<NonEmpty<A>> + 'a {
+        Gen { run : self.run, enumerate : Rc::new(f) }
+    }
+
+    /// Joining together generators, comparable to the monad and applicative instances.
+    /// Monads are about making sure values that depend on effects are encapsulated and don't escape.
+    /// Linear types can achieve similar things without monads, so borrowing should be able to do the same thing.
+    /// 
+    /// As an example, here is how you might generate a date struct with separate year, month and day:
+    /// > Gen::combine(|c| {
+    /// >  Date {
+    /// >    year:  c.of(Gen::u64(0..2021)),
+    /// >    month: c.of(Gen::u64(0..12)),
+    /// >    day:   c.of(Gen::u64(0..32)),
+    /// >  }
+    /// > })
+    /// 
+    /// The combine function takes a closure of type "&mut Chooser -> A", and wraps the result in a Gen<A>.
+    /// This "Chooser" represents the ability to extract a value from a Gen, using the Chooser method
+    /// > Chooser::of<A>(&mut self, gen : Gen<A>) -> A
+    /// Having a Chooser is a bit like being inside a monadic bind, in that you can use the chooser
+    /// to extract a value from a Gen<A> and then manipulate it.
+    /// The main complication is that a Chooser can only be used within the context of this 'combine' function,
+    /// because otherwise one could arbitrarily pull values out of a Gen.
+    /// The trick here is that the borrow checker ensures that the mutable reference to the Chooser
+    /// cannot escape the closure: it cannot be stored in a larger structure or returned or even used
+    /// in a nested call to combine, because these cases might allow the Chooser to be used after
+    /// combine has dropped it.
+    ///
+    /// Each generator is identified by where Chooser::of was called, so when shrinking one generator
+    /// changes which of the others the closure uses, the others keep their own values and shrinks.
+    /// Shrinking one generator only runs the generators used after it again, since their values might
+    /// depend on the new value; the ones before it keep their trees.
+    ///
+    /// The closure to combine needs to implement Clone --- presumably this means all values in its
+    /// closure environment need to also implement Clone. This is required because the closure is
+    /// stored in the lazy children of the tree.
+    pub fn combine<F>(f : F) -> Gen<'a, A>
+    where F : Fn(&mut Chooser<'a>) -> A + 'a + Clone,
+    A : 'a + Clone {
+        let f_enum = f.clone();
+        Gen::new(move |r, s| {
+            let depth = COMBINE_DEPTH.with(|d| d.get()).unwrap_or_else(|| depth_for_size(s));
+            Self::combine_go(f.clone(), r, s, depth, Vec::new(), 0)
+        }).with_enumerate(move |limit| {
+            Self::combine_enumerate(&f_enum, limit)
+        })
+    }
+
+    /// Enumerate all the values of a combined generator, by enumerating each generator the closure
+    /// uses with the non-deterministic witness.
+    /// Gives up if any of the generators can't be enumerated, or there are more than limit values.
+    fn combine_enumerate<F>(f : &F, limit : usize) -> Option<NonEmpty<A>>
+    where F : Fn(&mut Chooser<'a>) -> A {
+        let mut count = 0;
+        let depth = COMBINE_DEPTH.with(|d| d.get());
+        nondet::enumerate(|w| {
+            let mut c = Chooser::enumerating(std::mem::replace(w, Witness::new()), limit, depth);
+            let value = f(&mut c);
+            let (witness, enumerable) = c.witness.take().expect("Chooser::enumerating has a witness");
+            *w = witness;
+
+            count += 1;
+            if enumerable && count <= limit {
+                Some(value)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Worker function for combine, recursively generates the shrink tree.
+    /// The first `reuse` generators in paths give the closure the same values as they did for the
+    /// parent node, so their nodes are reused rather than running the generators again.
+    fn combine_go<F>(f : F, r : Random, s : usize, depth : usize, paths : GenPaths<'a>, reuse : usize) -> Tree<'a, A>
+    where F : Fn(&mut Chooser<'a>) -> A + 'a + Clone,
+    A : 'a + Clone {
+        // println!("Gen::combine_go {:#?}", paths);
+        // Run with given shrink paths to get result value & the further shrinks of each generator
+        let mut c = Chooser::new(r, s, depth, paths, reuse);
+        let value = f(&mut c);
+        // Each generator the closure used, in the order it used them. Generators that weren't used
+        // this time are dropped, and any paths that no longer fit were cut short.
+        // Only keep what the children need, rather than the whole Chooser in every node of the tree.
+        let paths = std::mem::take(&mut c.gen_used);
+
+        let children_clo = move || {
+            let (f, all_paths) = (f.clone(), paths.clone());
+            // Loop through all the generators that the closure used, and each of their shrinks in turn.
+            // The shrinks of a generator are only forced as the search reaches them, so a generator
+            // with infinitely many shrinks still gets tried, and the ones after it are never reached.
+            // Each child re-runs the closure, so they're only computed as the shrink search reaches them.
+            paths.clone().into_iter().enumerate()
+                .flat_map(|(gen_ix, used)| used.node.children().enumerate().map(move |(child_ix, child)| (gen_ix, child_ix, child)))
+                .map(move |(gen_ix, child_ix, child)| {
+                    // The shrunk generator carries on from the child node, and the ones before it keep
+                    // their nodes, which are shared with this node and its other children. The ones
+                    // after it might depend on the new value, so they're run again.
+                    let mut paths_copy = all_paths.clone();
+                    paths_copy[gen_ix].path = paths_copy[gen_ix].path.push(child_ix);
+                    paths_copy[gen_ix].node = child;
+                    Self::combine_go(f.clone(), r, s, depth, paths_copy, gen_ix + 1)
+                })
+        };
+
+        Tree::new(value, children_clo)
+    }
+
+}
+
+/// At an abstract level, Chooser is a capability or evidence that you're allowed to
+/// get the values out of a Gen.
+/// In terms of implementation, a particular Chooser describes how much to shrink each generator
+/// and records how each generator can be shrunk further.
+pub struct Chooser<'a> {
+    /// Input: random generator, which each generator's seed is forked from
+    rand : Random,
+    /// Input argument to Gen: generator size
+    // XXX: is there any way to specify const/immutable fields in Rust?
+    size : usize,
+    /// Input: random generator, path describing how to shrink each generator, and the node the path
+    /// led to last time
+    gen_paths : HashMap<CallId, GenUse<'a>>,
+    /// Input: how many of the first generators the closure used last time will give it the same
+    /// values, so their nodes can be used again without running them
+    reuse : usize,
+    /// State: each generator we've seen so far, and the path taken in its tree
+    gen_used : GenPaths<'a>,
+    /// State: how many calls there have been from each site
+    occurrences : HashMap<CallSite, usize>,
+    /// Only when enumerating instead of generating: witness saying which value to take from each
+    /// generator, and whether all generators so far could be enumerated
+    witness : Option<(Witness, bool)>,
+    /// Input: maximum domain size to enumerate
+    limit : usize,
+    /// Input: how many more levels of with_smaller_depth are allowed
+    depth : usize,
+    /// Whether depth is meaningful. Top-level enumeration has no size to take the depth from.
+    depth_known : bool,
+}
+
+impl<'a> Chooser<'a> {
+    fn new(rand : Random, size : usize, depth : usize, gen_paths: GenPaths<'a>, reuse : usize) -> Chooser<'a> {
+        Chooser {
+            rand, size,
+            gen_paths: gen_paths.into_iter().map(|used| (used.id, used)).collect(),
+            reuse,
+            gen_used: Vec::new(),
+            occurrences: HashMap::new(),
+            witness: None,
+            limit: 0,
+            depth,
+            depth_known: true,
+        }
+    }
+
+    fn enumerating(witness : Witness, limit : usize, depth : Option<usize>) -> Chooser<'a> {
+        Chooser {
+            witness: Some((witness, true)),
+            limit,
+            depth_known: depth.is_some(),
+            ..Chooser::new(Random::new_from_seed(0), 0, depth.unwrap_or(0), Vec::new(), 0)
+        }
+    }
+
+    /// Remaining depth budget for recursive generators.
+    /// This starts at depth_for_size of the generator size, and goes down by one inside each
+    /// with_smaller_depth, so recursive generators can use the base case once it reaches zero.
+    pub fn depth(&mut self) -> usize {
+        if !self.depth_known {
+            // The values would depend on a depth we don't have, so give up on enumerating
+            if let Some((_, enumerable)) = &mut self.witness {
+                *enumerable = false;
+            }
+        }
+        self.depth
+    }
+
+    /// Get a value from a generator, where any combine generators inside it get one less depth.
+    /// Panics if the depth is already zero.
+    /// > Gen::combine(|c| if c.depth() == 0 { Expr::Lit(c.of(Gen::u64(0..10))) } else {
+    /// >   Expr::Neg(Box::new(c.with_smaller_depth(expr())))
+    /// > })
+    #[track_caller]
+    pub fn with_smaller_depth<A>(&mut self, gen : Gen<'a, A>) -> A
+    where A : Clone + 'a {
+        assert!(self.depth > 0 || !self.depth_known, "Chooser::with_smaller_depth: no depth left");
+        // Without a known depth, the generator's values depend on a depth we don't have either, so give
+        // up on enumerating and leave its depth unknown too
+        let inner = if self.depth_known {
+            Some(self.depth - 1)
+        } else {
+            self.depth();
+            None
+        };
+        let outer = COMBINE_DEPTH.with(|d| d.replace(inner));
+        // Restore the outer depth even if the generator discards by unwinding
+        struct Restore(Option<usize>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                COMBINE_DEPTH.with(|d| d.set(self.0));
+            }
+        }
+        let _restore = Restore(outer);
+        self.of(gen)
+    }
+
+    /// Get a value from a generator.
+    /// The generator is identified by where this is called from, and how many times it has been
+    /// called from there, so that the generator keeps its random seed and shrink path when shrinking
+    /// changes which other generators the closure uses.
+    /// The seed of a generator comes from how many calls the closure made before it, so the values
+    /// don't depend on where the closure is in the source code.
+    #[track_caller]
+    pub fn of<A>(&mut self, gen : Gen<'a, A>) -> A
+    where A : Clone + 'a {
+        self.of_site(CallSite::Location(Location::caller()), gen, |_| None, |tree| Rc::new(tree))
+    }
+
+    /// Get a value from a generator, like Chooser::of, but without running the generator again while
+    /// shrinking. Each node of the shrink tree keeps the node of the generator's tree that it got to,
+    /// so its shrinks carry on from there instead of following the shrink path from the root; this
+    /// is why the values have to be 'static.
+    #[track_caller]
+    pub fn of_cached<A>(&mut self, gen : Gen<'a, A>) -> A
+    where A : Clone + 'static {
+        self.of_site(
+            CallSite::Location(Location::caller()), gen,
+            |node| node.value()?.downcast_ref::<A>().cloned(),
+            |tree| Rc::new(CachedNode(tree)))
+    }
+
+    /// Get a value from a generator that is identified by a label instead of where it's called from.
+    /// The seed comes from the label too, rather than from the order of the calls.
+    /// This is for helper functions that call Chooser::of on behalf of different parts of the closure,
+    /// where the helper's call site would mix up their shrink paths:
+    /// > fn field(c : &mut Chooser, name : &'static str) -> u64 { c.of_labelled(name, Gen::u64(0..10)) }
+    pub fn of_labelled<A>(&mut self, label : &'static str, gen : Gen<'a, A>) -> A
+    where A : Clone + 'a {
+        self.of_site(CallSite::Label(label), gen, |_| None, |tree| Rc::new(tree))
+    }
+
+    /// Get a value from a generator for one of the call sites above. `cached` takes the value back out
+    /// of the node from last time, if that's possible, and `keep` is the node to keep for next time.
+    fn of_site<A>(
+        &mut self, site : CallSite, gen : Gen<'a, A>,
+        cached : fn(&dyn UsedNode<'a>) -> Option<A>,
+        keep : fn(Tree<'a, A>) -> Rc<dyn UsedNode<'a> + 'a>) -> A
+    where A : Clone + 'a {
+        // println!("Chooser::of");
+        if let Some((w, enumerable)) = &mut self.witness {
+            match (*gen.enumerate)(self.limit) {
+                Some(values) => return w.choose(&values),
+                // Can't enumerate this one: remember that, but keep going with a generated value
+                // so that the closure can still finish
+                None => *enumerable = false,
+            }
+        }
+
+        let occurrence = self.occurrences.entry(site).or_insert(0);
+        let id = CallId { site, occurrence : *occurrence };
+        *occurrence += 1;
+
+        let planned = self.gen_paths.get(&id);
+        if self.gen_used.len() < self.reuse {
+            // Nothing the generator depends on has changed, so carry on from the node it got to before
+            if let Some(used) = planned {
+                if let Some(value) = cached(&*used.node) {
+                    self.gen_used.push(used.clone());
+                    return value;
+                }
+            }
+        }
+
+        // A generator that the closure used before keeps its seed, even if the calls before it changed
+        let (rand, mut path) = match planned {
+            Some(used) => (used.rand, used.path.clone()),
+            None => (self.rand.fork(id.seed_key(self.gen_used.len())), SharedPath::default()),
+        };
+        let tree = (*gen.run)(rand, self.size);
+
+        let (shrunk, consumed) = tree.get_path_prefix(&path.to_path());
+        // If an earlier generator has shrunk, this generator's tree might have changed shape so that
+        // the path no longer fits. Keep only the part that was followed, so that further shrinks
+        // extend the path from the node we actually got to.
+        if consumed < path.len() {
+            path = path.truncate(consumed);
+        }
+        let value = shrunk.value.clone();
+        self.gen_used.push(GenUse { id, rand, path, node : keep(shrunk) });
+        value
+    }
+}
+
+
+
+
+
+impl<'a> Gen<'a, u64> {
+    /// Unsigned integers in a range, which can be an ordinary Range, an inclusive range, `..` for
+    /// every value, or a GenRange with an explicit origin.
+    /// Shrinks toward the origin, which is the start of the range by default.
+    pub fn u64<R : IntoGenRange<u64>>(range : R) -> Gen<'a, u64> {
+        Gen::integer(range, |range| Gen::unsigned(range.map(u128::from)).map(|x| x as u64))
+    }
+
+    /// Index into the weights, where each index is chosen with probability proportional to its
+    /// weight. Shrinks toward index 0.
+    fn weighted_index(weights : Vec<u64>) -> Gen<'a, usize> {
+        let len = weights.len() as u64;
+        Gen::new(move |mut r, _s| {
+            let ix = r.weighted_index(&weights);
+            Gen::shrink_u128(0, ix as u128).map(|i| i as usize)
+        }).with_enumerate(move |limit| {
+            (*Gen::usize(0..len as usize).enumerate)(limit)
+        })
+    }
+
+    /// Indices into a collection of length len, shrinking toward zero.
+    /// Panics if len is zero.
+    pub fn index(len : usize) -> Gen<'a, usize> {
+        Gen::usize(0..len)
+    }
+
+    pub fn usize<R : IntoGenRange<usize>>(range : R) -> Gen<'a, usize> {
+        Gen::integer(range, |range| Gen::u64(range.map(|x| x as u64)).map(|x| x as usize))
+    }
+}
+
+impl<'a> Gen<'a, u128> {
+    /// Unsigned integers in the range, shrinking toward its origin.
+    /// All the unsigned generators are implemented in terms of this one.
+    fn unsigned(range : GenRange<u128>) -> Gen<'a, u128> {
+        let (start, last, origin) = (range.start(), range.last(), range.origin());
+        Gen::new(move |mut r, _s| {
+            let value = match (last - start).checked_add(1) {
+                Some(width) => start + r.u128_range(0..width),
+                // Full range of u128
+                None => r.u128(),
+            };
+            Self::shrink_u128(origin, value)
+        }).with_enumerate(move |limit| {
+            if last - start >= limit as u128 {
+                return None;
+            }
+            // Shrink order: closest to the origin first
+            let mut values : Vec<u128> = (start ..= last).collect();
+            values.sort_by_key(|&x| (x.abs_diff(origin), x));
+            let mut values = values.into_iter();
+            Some(NonEmpty { zero : values.next()?, vec : values.collect() })
+        })
+    }
+
+    /// Shrink toward the origin, trying the origin itself first and then halving the distance
+    /// back to the value; see towards
+    pub(crate) fn shrink_u128(origin : u128, value : u128) -> Tree<'a, u128> {
+        let children = move || {
+            towards(value.abs_diff(origin)).into_iter()
+                .map(move |d| Self::shrink_u128(origin, if value > origin { origin + d } else { origin - d }))
+        };
+        Tree::new(value, children)
+    }
+
+    pub fn u8<R : IntoGenRange<u8>>(range : R) -> Gen<'a, u8> {
+        Gen::integer(range, |range| Gen::unsigned(range.map(u128::from)).map(|x| x as u8))
+    }
+
+    pub fn u16<R : IntoGenRange<u16>>(range : R) -> Gen<'a, u16> {
+        Gen::integer(range, |range| Gen::unsigned(range.map(u128::from)).map(|x| x as u16))
+    }
+
+    pub fn u32<R : IntoGenRange<u32>>(range : R) -> Gen<'a, u32> {
+        Gen::integer(range, |range| Gen::unsigned(range.map(u128::from)).map(|x| x as u32))
+    }
+
+    pub fn u128<R : IntoGenRange<u128>>(range : R) -> Gen<'a, u128> {
+        Gen::integer(range, Gen::unsigned)
+    }
+
+    /// Inclusive ranges can include the maximum value, eg u8::MIN..=u8::MAX
+    pub fn u8_inclusive(range : RangeInclusive<u8>) -> Gen<'a, u8> {
+        Gen::u8(GenRange::inclusive(range))
+    }
+
+    pub fn u16_inclusive(range : RangeInclusive<u16>) -> Gen<'a, u16> {
+        Gen::u16(GenRange::inclusive(range))
+    }
+
+    pub fn u32_inclusive(range : RangeInclusive<u32>) -> Gen<'a, u32> {
+        Gen::u32(GenRange::inclusive(range))
+    }
+
+    pub fn u64_inclusive(range : RangeInclusive<u64>) -> Gen<'a, u64> {
+        Gen::u64(GenRange::inclusive(range))
+    }
+
+    pub fn u128_inclusive(range : RangeInclusive<u128>) -> Gen<'a, u128> {
+        Gen::u128(GenRange::inclusive(range))
+    }
+
+    pub fn usize_inclusive(range : RangeInclusive<usize>) -> Gen<'a, usize> {
+        Gen::usize(GenRange::inclusive(range))
+    }
+
+    /// Every value of the type, including MAX, shrinking toward zero
+    pub fn u8_any() -> Gen<'a, u8> {
+        Gen::u8(..)
+    }
+
+    pub fn u16_any() -> Gen<'a, u16> {
+        Gen::u16(..)
+    }
+
+    pub fn u32_any() -> Gen<'a, u32> {
+        Gen::u32(..)
+    }
+
+    pub fn u64_any() -> Gen<'a, u64> {
+        Gen::u64(..)
+    }
+
+    pub fn u128_any() -> Gen<'a, u128> {
+        Gen::u128(..)
+    }
+
+    pub fn usize_any() -> Gen<'a, usize> {
+        Gen::usize(..)
+    }
+
+    /// Like Gen::unsigned, but sometimes picks an edge case instead: either end of the range,
+    /// zero, one, powers of two and one less, or the maximum of a bits-wide type.
+    /// Edge cases shrink the same way as other values.
+    fn unsigned_biased(range : GenRange<u128>, bits : u32) -> Gen<'a, u128> {
+        let max = u128::MAX >> (128 - bits);
+        let mut edges = vec![range.start(), range.last(), 0, 1, max];
+        for k in 1..bits {
+            edges.push(1 << k);
+            edges.push((1 << k) - 1);
+        }
+        edges.retain(|e| range.contains(e));
+        edges.sort_unstable();
+        edges.dedup();
+        Self::biased(Gen::unsigned(range), edges, move |e| Self::shrink_u128(range.origin(), e))
+    }
+
+    pub fn u8_biased<R : IntoGenRange<u8>>(range : R) -> Gen<'a, u8> {
+        Gen::integer(range, |range| Gen::unsigned_biased(range.map(u128::from), 8).map(|x| x as u8))
+    }
+
+    pub fn u16_biased<R : IntoGenRange<u16>>(range : R) -> Gen<'a, u16> {
+        Gen::integer(range, |range| Gen::unsigned_biased(range.map(u128::from), 16).map(|x| x as u16))
+    }
+
+    pub fn u32_biased<R : IntoGenRange<u32>>(range : R) -> Gen<'a, u32> {
+        Gen::integer(range, |range| Gen::unsigned_biased(range.map(u128::from), 32).map(|x| x as u32))
+    }
+
+    pub fn u64_biased<R : IntoGenRange<u64>>(range : R) -> Gen<'a, u64> {
+        Gen::integer(range, |range| Gen::unsigned_biased(range.map(u128::from), 64).map(|x| x as u64))
+    }
+
+    pub fn u128_biased<R : IntoGenRange<u128>>(range : R) -> Gen<'a, u128> {
+        Gen::integer(range, |range| Gen::unsigned_biased(range, 128))
+    }
+
+    pub fn usize_biased<R : IntoGenRange<usize>>(range : R) -> Gen<'a, usize> {
+        Gen::integer(range, |range| Gen::unsigned_biased(range.map(|x| x as u128), usize::BITS).map(|x| x as usize))
+    }
+}
+
+impl<'a> Gen<'a, bool> {
+    /// True or false with equal probability, shrinking toward false
+    pub fn bool() -> Gen<'a, bool> {
+        Gen::bool_ratio(1, 2)
+    }
+
+    /// True with probability num / denom, shrinking toward false
+    pub fn bool_ratio(num : u64, denom : u64) -> Gen<'a, bool> {
+        assert!(num <= denom && denom > 0, "Gen::bool_ratio: bad ratio {}/{}", num, denom);
+        Gen::new(move |mut r, _s| {
+            let value = r.ratio(num, denom);
+            let children = move || {
+                if value {
+                    vec![Tree::leaf(false)]
+                } else {
+                    vec![]
+                }
+            };
+            Tree::new(value, children)
+        }).with_enumerate(move |limit| {
+            if num == 0 {
+                Some(NonEmpty { zero : false, vec : vec![] })
+            } else if num == denom {
+                Some(NonEmpty { zero : true, vec : vec![] })
+            } else if limit >= 2 {
+                Some(NonEmpty { zero : false, vec : vec![true] })
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl<'a> Gen<'a, i128> {
+    /// Signed integers in a range, which can be an ordinary Range, an inclusive range, `..` for
+    /// every value, or a GenRange with an explicit origin.
+    /// By default this shrinks toward zero, or toward whichever end of the range is closest to zero
+    /// if the range doesn't include it.
+    /// All the signed generators are implemented in terms of this one.
+    pub fn i128<R : IntoGenRange<i128>>(range : R) -> Gen<'a, i128> {
+        Gen::integer(range, Gen::signed)
+    }
+
+    fn signed(range : GenRange<i128>) -> Gen<'a, i128> {
+        let (start, last, origin) = (range.start(), range.last(), range.origin());
+        Gen::new(move |mut r, _s| {
+            let value = match (last.wrapping_sub(start) as u128).checked_add(1) {
+                Some(width) => start.wrapping_add(r.u128_range(0..width) as i128),
+                // Full range of i128
+                None => r.u128() as i128,
+            };
+            Self::shrink_i128(origin, value)
+        }).with_enumerate(move |limit| {
+            if last.wrapping_sub(start) as u128 >= limit as u128 {
+                return None;
+            }
+            // Shrink order: closest to the origin first
+            let mut values : Vec<i128> = (start ..= last).collect();
+            values.sort_by_key(|&x| (x.abs_diff(origin), x));
+            let mut values = values.into_iter();
+            Some(NonEmpty { zero : values.next()?, vec : values.collect() })
+        })
+    }
+
+    /// Shrink toward the origin; see shrink_u128
+    fn shrink_i128(origin : i128, value : i128) -> Tree<'a, i128> {
+        let children = move || {
+            // The distance can be too big for i128, but wrapping arithmetic still gets there
+            towards(value.abs_diff(origin)).into_iter()
+                .map(move |d| Self::shrink_i128(origin,
+                    if value > origin { origin.wrapping_add(d as i128) } else { origin.wrapping_sub(d as i128) }))
+        };
+        Tree::new(value, children)
+    }
+
+    pub fn i8<R : IntoGenRange<i8>>(range : R) -> Gen<'a, i8> {
+        Gen::integer(range, |range| Gen::i128(range.map(|x| x as i128)).map(|x| x as i8))
+    }
+
+    pub fn i16<R : IntoGenRange<i16>>(range : R) -> Gen<'a, i16> {
+        Gen::integer(range, |range| Gen::i128(range.map(|x| x as i128)).map(|x| x as i16))
+    }
+
+    pub fn i32<R : IntoGenRange<i32>>(range : R) -> Gen<'a, i32> {
+        Gen::integer(range, |range| Gen::i128(range.map(|x| x as i128)).map(|x| x as i32))
+    }
+
+    pub fn i64<R : IntoGenRange<i64>>(range : R) -> Gen<'a, i64> {
+        Gen::integer(range, |range| Gen::i128(range.map(|x| x as i128)).map(|x| x as i64))
+    }
+
+    pub fn isize<R : IntoGenRange<isize>>(range : R) -> Gen<'a, isize> {
+        Gen::integer(range, |range| Gen::i128(range.map(|x| x as i128)).map(|x| x as isize))
+    }
+
+    /// Every value of the type, including MIN and MAX, shrinking toward zero
+    pub fn i8_any() -> Gen<'a, i8> {
+        Gen::i8(..)
+    }
+
+    pub fn i16_any() -> Gen<'a, i16> {
+        Gen::i16(..)
+    }
+
+    pub fn i32_any() -> Gen<'a, i32> {
+        Gen::i32(..)
+    }
+
+    pub fn i64_any() -> Gen<'a, i64> {
+        Gen::i64(..)
+    }
+
+    pub fn i128_any() -> Gen<'a, i128> {
+        Gen::i128(..)
+    }
+
+    pub fn isize_any() -> Gen<'a, isize> {
+        Gen::isize(..)
+    }
+
+    /// Like Gen::i128, but sometimes picks an edge case instead: either end of the range, zero,
+    /// plus or minus one, plus or minus powers of two and one less, or the minimum and maximum of
+    /// a bits-wide type. Edge cases shrink the same way as other values.
+    fn signed_biased(range : GenRange<i128>, bits : u32) -> Gen<'a, i128> {
+        let max = i128::MAX >> (128 - bits);
+        let mut edges = vec![range.start(), range.last(), 0, 1, -1, max, -max - 1];
+        for k in 1..bits - 1 {
+            let pow = 1i128 << k;
+            edges.extend_from_slice(&[pow, pow - 1, -pow, -pow + 1]);
+        }
+        edges.retain(|e| range.contains(e));
+        edges.sort_unstable();
+        edges.dedup();
+        Self::biased(Gen::i128(range), edges, move |e| Self::shrink_i128(range.origin(), e))
+    }
+
+    pub fn i8_biased<R : IntoGenRange<i8>>(range : R) -> Gen<'a, i8> {
+        Gen::integer(range, |range| Gen::signed_biased(range.map(i128::from), 8).map(|x| x as i8))
+    }
+
+    pub fn i16_biased<R : IntoGenRange<i16>>(range : R) -> Gen<'a, i16> {
+        Gen::integer(range, |range| Gen::signed_biased(range.map(i128::from), 16).map(|x| x as i16))
+    }
+
+    pub fn i32_biased<R : IntoGenRange<i32>>(range : R) -> Gen<'a, i32> {
+        Gen::integer(range, |range| Gen::signed_biased(range.map(i128::from), 32).map(|x| x as i32))
+    }
+
+    pub fn i64_biased<R : IntoGenRange<i64>>(range : R) -> Gen<'a, i64> {
+        Gen::integer(range, |range| Gen::signed_biased(range.map(i128::from), 64).map(|x| x as i64))
+    }
+
+    pub fn i128_biased<R : IntoGenRange<i128>>(range : R) -> Gen<'a, i128> {
+        Gen::integer(range, |range| Gen::signed_biased(range, 128))
+    }
+
+    pub fn isize_biased<R : IntoGenRange<isize>>(range : R) -> Gen<'a, isize> {
+        Gen::integer(range, |range| Gen::signed_biased(range.map(|x| x as i128), isize::BITS).map(|x| x as isize))
+    }
+}
+
+impl<'a, A> Gen<'a, A> {
+    /// Choose uniformly between values, shrinking toward the first one
+    pub fn choose(v : Vec<A>) -> Gen<'a, A>
+    where A : 'a + Clone {
+        Gen::index(v.len()).map(move |ix| v[ix].clone())
+    }
+
+    /// Pick one of the edges BIASED_EDGE_PERCENT of the time, otherwise use the generator.
+    /// Enumeration is the same as the generator's, as the edges should be part of its domain.
+    fn biased<F>(gen : Gen<'a, A>, edges : Vec<A>, tree : F) -> Gen<'a, A>
+    where F : Fn(A) -> Tree<'a, A> + 'a,
+    A : 'a + Clone {
+        let run = gen.run;
+        Gen {
+            run : Rc::new(move |mut r, s| {
+                if r.u64_range(0..100) < BIASED_EDGE_PERCENT {
+                    tree(edges[r.u64_range(0..edges.len() as u64) as usize].clone())
+                } else {
+                    (*run)(r, s)
+                }
+            }),
+            enumerate : gen.enumerate,
+        }
+    }
+
+    /// Elements of a borrowed slice, shrinking toward the first element.
+    /// Unlike choose, the slice isn't copied into the generator; only the chosen element is cloned.
+    /// Panics if the slice is empty.
+    pub fn element(slice : &'a [A]) -> Gen<'a, A>
+    where A : Clone {
+        Gen::index(slice.len()).map(move |ix| slice[ix].clone())
+    }
+
+    /// Choose uniformly between generators.
+    /// Unlike choose, which picks between values, each alternative keeps its own shrinks.
+    /// Also shrinks toward earlier generators in the list.
+    pub fn one_of(gens : Vec<Gen<'a, A>>) -> Gen<'a, A>
+    where A : 'a + Clone {
+        Gen::frequency(gens.into_iter().map(|g| (1, g)).collect())
+    }
+
+    /// Choose between generators with the given relative weights.
+    /// Shrinks toward the first generator in the list.
+    /// > Gen::frequency(vec![(3, Gen::u64(0..10)), (1, Gen::u64(100..200))])
+    pub fn frequency(gens : Vec<(u64, Gen<'a, A>)>) -> Gen<'a, A>
+    where A : 'a + Clone {
+        // Entries with zero weight can never be chosen, and shouldn't be shrunk to either
+        let (weights, gens) : (Vec<u64>, Vec<Gen<'a, A>>) = gens.into_iter().filter(|(w, _)| *w > 0).unzip();
+        assert!(!gens.is_empty(), "Gen::frequency: needs at least one generator with positive weight");
+        Gen::<u64>::weighted_index(weights).and_then(move |ix| gens[ix].clone())
+    }
+
+    /// Choose between values with the given relative weights.
+    /// Shrinks toward the first value in the list, so put the most common one first.
+    /// > Gen::weighted(vec![(10, "GET"), (5, "POST"), (1, "DELETE")])
+    pub fn weighted(values : Vec<(u64, A)>) -> Gen<'a, A>
+    where A : 'a + Clone {
+        let (weights, values) : (Vec<u64>, Vec<A>) = values.into_iter().filter(|(w, _)| *w > 0).unzip();
+        assert!(!values.is_empty(), "Gen::weighted: needs at least one value with positive weight");
+        Gen::<u64>::weighted_index(weights).map(move |ix| values[ix].clone())
+    }
+
+    /// Values seeded from a corpus of real-world samples. Picks a sample, then returns it as-is
+    /// CORPUS_PERCENT of the time, otherwise runs the mutation generator on it.
+    /// The mutation can ignore the sample to generate something new instead.
+    /// Shrinks toward earlier samples, and mutated values toward the unmodified sample.
+    /// > Gen::from_corpus(requests, |req| Gen::bytes(Gen::usize(0..4)).map(move |b| [&req[..], &b].concat()))
+    pub fn from_corpus<F>(samples : Vec<A>, mutation_gen : F) -> Gen<'a, A>
+    where F : Fn(A) -> Gen<'a, A> + 'a,
+    A : 'a + Clone {
+        Gen::from_corpus_with_percent(samples, CORPUS_PERCENT, mutation_gen)
+    }
+
+    /// Like from_corpus, but returns an unmodified sample corpus_percent of the time
+    pub fn from_corpus_with_percent<F>(samples : Vec<A>, corpus_percent : u64, mutation_gen : F) -> Gen<'a, A>
+    where F : Fn(A) -> Gen<'a, A> + 'a,
+    A : 'a + Clone {
+        assert!(!samples.is_empty(), "Gen::from_corpus: needs at least one sample");
+        assert!(corpus_percent <= 100, "Gen::from_corpus: percent {} is over 100", corpus_percent);
+        Gen::index(samples.len()).and_then(move |ix| {
+            let sample = samples[ix].clone();
+            Gen::frequency(vec![
+                (corpus_percent, Gen::constant(sample.clone())),
+                (100 - corpus_percent, mutation_gen(sample)),
+            ])
+        })
+    }
+
+    /// Generator for recursive structures such as syntax trees.
+    /// The closure gets a generator for the recursive occurrences, which runs with half the size.
+    /// Once the size reaches zero, the base generator is used instead, so recursion always
+    /// terminates:
+    /// > Gen::recursive(Gen::u64(0..10).map(Expr::Lit), |rec| Gen::one_of(vec![
+    /// >   Gen::u64(0..10).map(Expr::Lit),
+    /// >   rec.clone().zip(rec).map(|(a, b)| Expr::Add(Box::new(a), Box::new(b))),
+    /// > ]))
+    pub fn recursive<F>(base : Gen<'a, A>, f : F) -> Gen<'a, A>
+    where F : Fn(Gen<'a, A>) -> Gen<'a, A> + 'a,
+    A : 'a + Clone {
+        Self::recursive_go(base, Rc::new(f))
+    }
+
+    fn recursive_go(base : Gen<'a, A>, f : Rc<dyn Fn(Gen<'a, A>) -> Gen<'a, A> + 'a>) -> Gen<'a, A>
+    where A : 'a + Clone {
+        Gen::new(move |r, s| {
+            if s == 0 {
+                return (*base.run)(r, s);
+            }
+            // The self-reference is only constructed when it's needed, so this isn't an infinite loop
+            let recurse = Self::recursive_go(base.clone(), f.clone());
+            let rec = Gen::new(move |r, s| (*recurse.run)(r, s / 2));
+            (*f(rec).run)(r, s)
+        })
+    }
+
+    /// Optional values, shrinking toward None.
+    /// None has weight 2 and Some has weight 1 + size, so larger sizes generate mostly Some.
+    pub fn option(self) -> Gen<'a, Option<A>>
+    where A : 'a + Clone {
+        self.option_ratio(2)
+    }
+
+    /// Optional values, where None has weight none_weight and Some has weight 1 + size.
+    /// Shrinks toward None.
+    pub fn option_ratio(self, none_weight : u64) -> Gen<'a, Option<A>>
+    where A : 'a + Clone {
+        let enumerate = self.enumerate.clone();
+        let some = self.map(Some);
+        Gen::new(move |r, s| {
+            let gen = Gen::frequency(vec![(none_weight, Gen::constant(None)), (1 + s as u64, some.clone())]);
+            (*gen.run)(r, s)
+        }).with_enumerate(move |limit| {
+            let values = (*enumerate)(limit.checked_sub(1)?)?;
+            Some(NonEmpty { zero : None, vec : values.to_vec().into_iter().map(Some).collect() })
+        })
+    }
+
+    /// Ok or Err values, with equal probability. Shrinks toward Ok.
+    pub fn result<E>(ok : Gen<'a, A>, err : Gen<'a, E>) -> Gen<'a, Result<A, E>>
+    where A : 'a + Clone, E : 'a + Clone {
+        Gen::one_of(vec![ok.map(Ok), err.map(Err)])
+    }
+
+    /// Lists with length from gen_len.
+    /// Shrinks by removing chunks of elements from the generated list, down to the smallest length
+    /// gen_len would shrink to, and then by shrinking each element. Small domains are still
+    /// enumerated, by choosing a length and then each element.
+    /// The shrink tree is built directly with Tree::interleave rather than with combine: a combine
+    /// closure can only shrink the generators it uses one at a time, so removing an element from the
+    /// middle would shift every later element onto another generator's value and shrink path.
+    /// The lengths can also have their own shrinker for the whole list; see Lengths::shrink_with.
+    pub fn vec<L>(self, gen_len : L) -> Gen<'a, Vec<A>>
+    where A : 'a + Clone,
+    L : Into<Lengths<'a, A>> {
+        let Lengths { gen : gen_len, shrinker } = gen_len.into();
+        if let Some(shrinker) = shrinker {
+            return self.vec_shrunk_by(gen_len, shrinker);
+        }
+        let (enumerate_elem, enumerate_len) = (self.enumerate.clone(), gen_len.enumerate.clone());
+
+        Gen::new(move |mut r, s| {
+            let len_tree = (*gen_len.run)(r.split(), s);
+            let min_len = len_tree.first_shrink_leaf();
+            let trees = (0..len_tree.value).map(|_| (*self.run)(r.split(), s)).collect();
+            Tree::interleave(trees, min_len)
+        }).with_enumerate(move |limit| {
+            let lens = (*enumerate_len)(limit)?;
+            let elems = (*enumerate_elem)(limit)?;
+            let mut count = 0;
+            nondet::enumerate(|w| {
+                let len = w.choose(&lens);
+                let vec : Vec<A> = (0..len).map(|_| w.choose(&elems)).collect();
+                count += 1;
+                if count <= limit {
+                    Some(vec)
+                } else {
+                    None
+                }
+            })
+        })
+    }
+
+    /// Only generate values that satisfy the predicate.
+    /// Generation is retried with new seeds up to FILTER_RETRIES times, after which the test case is
+    /// discarded. Shrinks that don't satisfy the predicate are removed from the tree, so shrinking
+    /// never produces an invalid value.
+    pub fn filter<P>(self, pred : P) -> Gen<'a, A>
+    where P : Fn(&A) -> bool + 'a,
+    A : 'a + Clone {
+        let pred : Predicate<'a, A> = Rc::new(pred);
+        let pred_enum = pred.clone();
+        let run = self.run;
+        let enumerate = self.enumerate;
+        Gen::new(move |mut r, s| {
+            for _ in 0..FILTER_RETRIES {
+                let tree = (*run)(r.split(), s);
+                if pred(&tree.value) {
+                    return tree.filter_rc(pred.clone());
+                }
+            }
+            discard()
+        }).with_enumerate(move |limit| {
+            let mut values = (*enumerate)(limit)?.to_vec().into_iter().filter(|a| pred_enum(a));
+            Some(NonEmpty { zero : values.next()?, vec : values.collect() })
+        })
+    }
+}
+
+impl<'a, A> Gen<'a, Gen<'a, A>> {
+    /// Run the generated generator.
+    /// Shrinks the outer generator first, re-running the inner one with the same seed, and then
+    /// shrinks the inner value; see and_then.
+    pub fn flatten(self) -> Gen<'a, A>
+    where A : 'a {
+        self.and_then(|gen| gen)
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::*;
+    use crate::property::{Config, Property, SizeSchedule};
+
+    fn values<A : Clone>(tree : &Tree<A>) -> Vec<A> {
+        (*tree.children)().map(|c| c.value).collect()
+    }
+
+    #[test]
+    fn map_keeps_shrinks() {
+        let gen = Gen::u64(0..100);
+        let mapped = Gen::u64(0..100).map(|x| x * 2);
+        let tree = (*gen.run)(Random::new_from_seed(3), 0);
+        let tree_mapped = (*mapped.run)(Random::new_from_seed(3), 0);
+
+        assert_eq!(tree_mapped.value, tree.value * 2);
+        let doubled : Vec<u64> = values(&tree).iter().map(|x| x * 2).collect();
+        assert_eq!(values(&tree_mapped), doubled);
+        assert_eq!((*mapped.enumerate)(100).unwrap().len(), 100);
+    }
+
+    fn all_to_depth<A : Clone, P : Fn(&A) -> bool>(tree : &Tree<A>, depth : usize, p : &P) -> bool {
+        p(&tree.value) && (depth == 0 || (*tree.children)().all(|c| all_to_depth(&c, depth - 1, p)))
+    }
+
+    #[test]
+    fn and_then_shrinks_stay_dependent() {
+        let gen = Gen::u64(1..100).and_then(|n| Gen::u64(0..n).map(move |x| (n, x)));
+        for seed in 0..10 {
+            let tree = (*gen.run)(Random::new_from_seed(seed), 0);
+            assert!(all_to_depth(&tree, 3, &|&(n, x)| x < n));
+        }
+        assert_eq!((*gen.enumerate)(100).map(|v| v.len()), None);
+        // 1 + 2 + 3 + 4 + 5 values
+        let small = Gen::u64(1..6).and_then(|n| Gen::u64(0..n));
+        assert_eq!((*small.enumerate)(100).map(|v| v.len()), Some(15));
+    }
+
+    #[test]
+    fn filter_prunes_shrinks() {
+        let gen = Gen::u64(0..1000).filter(|x| x % 3 == 0);
+        for seed in 0..10 {
+            let tree = (*gen.run)(Random::new_from_seed(seed), 0);
+            assert!(all_to_depth(&tree, 4, &|x| x % 3 == 0));
+        }
+        assert_eq!((*Gen::u64(0..10).filter(|x| x % 3 == 0).enumerate)(100).unwrap().to_vec(), vec![0, 3, 6, 9]);
+    }
+
+    #[test]
+    fn tree_filter() {
+        let tree = Tree::new(9, || vec![Tree::new(4, || vec![Tree::leaf(2), Tree::leaf(3)]), Tree::leaf(6)])
+            .filter(|x| x % 2 == 0);
+        assert_eq!(values(&tree), vec![4, 6]);
+        assert_eq!(values(&(*tree.children)().next().unwrap()), vec![2]);
+    }
+
+    #[test]
+    fn frequency_weights() {
+        let gen = Gen::frequency(vec![(0, Gen::u64(0..10)), (3, Gen::u64(10..20)), (1, Gen::u64(20..30))]);
+        let mut counts = [0; 3];
+        for seed in 0..1000 {
+            let tree = (*gen.run)(Random::new_from_seed(seed), 0);
+            counts[(tree.value / 10) as usize] += 1;
+            assert!(all_to_depth(&tree, 3, &|&x| x >= 10));
+        }
+        assert_eq!(counts[0], 0);
+        assert!(counts[1] > 2 * counts[2]);
+    }
+
+    #[test]
+    fn weighted_values() {
+        let gen = Gen::weighted(vec![(10, "GET"), (5, "POST"), (0, "PUT"), (1, "DELETE")]);
+        let values = gen.sample(0, 0, 1000);
+        let count = |m| values.iter().filter(|v| **v == m).count();
+        assert!(count("GET") > count("POST") && count("POST") > count("DELETE") && count("DELETE") > 0);
+        assert_eq!(count("PUT"), 0);
+        Property::check_shrink(gen, |m| *m == "GET", "POST");
+    }
+
+    #[test]
+    fn from_corpus() {
+        let gen = Gen::from_corpus(vec![10, 20, 30], |x| Gen::u64(0..5).map(move |d| x + d));
+        let values = gen.sample(0, 0, 1000);
+        assert!(values.iter().all(|v| (10..35).contains(v)));
+        assert!(values.contains(&30) && values.iter().any(|v| v % 10 != 0));
+        Property::check_shrink(gen, |x| x % 10 == 0 || *x < 20, 21);
+    }
+
+    fn expr<'a>() -> Gen<'a, Expr> {
+        Gen::combine(|c| {
+            // Shrinks toward literals, then toward negation
+            if c.depth() == 0 || !c.of(Gen::bool()) {
+                return Expr::Lit(c.of(Gen::u64(0..10)));
+            }
+            if !c.of(Gen::bool()) {
+                Expr::Neg(Box::new(c.with_smaller_depth(expr())))
+            } else {
+                Expr::Add(Box::new(c.with_smaller_depth(expr())), Box::new(c.with_smaller_depth(expr())))
+            }
+        })
+    }
+
+    #[test]
+    fn depth_budget() {
+        // Size 4 gives a depth budget of 4
+        assert!((0..200).all(|seed| depth(&(*expr().run)(Random::new_from_seed(seed), 4).value) <= 4));
+        assert!((0..200).all(|seed| depth(&(*expr().run)(Random::new_from_seed(seed), 0).value) <= 1));
+        let config = Config { size : SizeSchedule::Constant(1), ..Config::default() };
+        let report = Property::new(expr(), |e| depth(e) < 2).check(&config);
+        assert_eq!(report.counterexamples[0].minimal, Expr::Add(Box::new(Expr::Lit(0)), Box::new(Expr::Neg(Box::new(Expr::Lit(0))))));
+        // The budget depends on the size, so it can't be enumerated
+        assert!((*expr().enumerate)(1000).is_none());
+    }
+
+    #[test]
+    fn smaller_depth_without_budget() {
+        // Enumerating the inner generator would only find its base case
+        let inner = || Gen::combine(|c| if c.depth() == 0 { 0 } else { 100 + c.of(Gen::u64(0..3)) });
+        let outer = Gen::combine(move |c| c.with_smaller_depth(inner()));
+        assert!((*outer.enumerate)(1000).is_none());
+        let report = Property::new(outer, |&x| x < 100).check(&Config::default());
+        assert_eq!(report.counterexamples[0].minimal, 100);
+    }
+
+    /// Every number has infinitely many "shrinks", to all the bigger numbers
+    fn nat<'a>(n : u64) -> Tree<'a, u64> {
+        Tree::new(n, move || (0..).map(move |k| nat(n + k + 1)))
+    }
+
+    #[test]
+    fn combine_infinite_children() {
+        let gen = Gen::combine(|c| (c.of(Gen::new(|_, _| nat(5))), c.of(Gen::u64(0..10))));
+        let tree = (*gen.run)(Random::new_from_seed(0), 0);
+        let shrinks : Vec<u64> = (*tree.children)().take(3).map(|t| t.value.0).collect();
+        assert_eq!(shrinks, vec![6, 7, 8]);
+        let grandchild = (*tree.children)().nth(2).and_then(|t| (*t.children)().nth(1)).unwrap();
+        assert_eq!(grandchild.value, (10, tree.value.1));
+    }
+
+    #[test]
+    fn one_of_shrinks_to_earlier() {
+        let gen = Gen::one_of(vec![Gen::u64(0..10), Gen::u64(10..20), Gen::u64(20..30)]);
+        let tree = (0..).map(|seed| (*gen.run)(Random::new_from_seed(seed), 0)).find(|t| t.value >= 20).unwrap();
+        // Some shrink moves to the previous generator
+        assert!(values(&tree).iter().any(|&x| (10..20).contains(&x)));
+        assert_eq!((*gen.enumerate)(30).unwrap().len(), 30);
+    }
+
+    #[test]
+    fn option_grows_with_size() {
+        let gen = Gen::u64(0..10).option();
+        let somes = |size| (0..1000).filter(|&seed| (*gen.run)(Random::new_from_seed(seed), size).value.is_some()).count();
+        assert!(somes(0) < 500);
+        assert!(somes(100) > 900);
+
+        let tree = (0..).map(|seed| (*gen.run)(Random::new_from_seed(seed), 0)).find(|t| t.value.is_some()).unwrap();
+        assert_eq!(values(&tree)[0], None);
+        assert_eq!((*gen.enumerate)(11).unwrap().len(), 11);
+    }
+
+    #[test]
+    fn constant_and_from_fn() {
+        let tree = (*Gen::constant(5).run)(Random::new_from_seed(0), 0);
+        assert_eq!((tree.value, values(&tree).len()), (5, 0));
+
+        let gen = Gen::from_fn(|r, s| r.u64_range(0..10) + s as u64 * 100);
+        let tree = (*gen.run)(Random::new_from_seed(0), 3);
+        assert!((300..310).contains(&tree.value));
+        assert!(values(&tree).is_empty());
+    }
+
+    #[allow(dead_code)]
+    #[derive(Clone, Debug, PartialEq)]
+    enum Expr {
+        Lit(u64),
+        Neg(Box<Expr>),
+        Add(Box<Expr>, Box<Expr>),
+    }
+
+    fn depth(e : &Expr) -> usize {
+        match e {
+            Expr::Lit(_) => 0,
+            Expr::Neg(a) => 1 + depth(a),
+            Expr::Add(a, b) => 1 + depth(a).max(depth(b)),
+        }
+    }
+
+    #[test]
+    fn recursive_terminates() {
+        let gen = Gen::recursive(Gen::u64(0..10).map(Expr::Lit), |rec| Gen::one_of(vec![
+            Gen::u64(0..10).map(Expr::Lit),
+            rec.clone().zip(rec).map(|(a, b)| Expr::Add(Box::new(a), Box::new(b))),
+        ]));
+        let depths : Vec<usize> = (0..20).map(|seed| depth(&(*gen.run)(Random::new_from_seed(seed), 8).value)).collect();
+        // 8, 4, 2, 1, 0
+        assert!(depths.iter().all(|&d| d <= 4));
+        assert!(depths.iter().any(|&d| d >= 2));
+        assert_eq!(depth(&(*gen.run)(Random::new_from_seed(0), 0).value), 0);
+    }
+
+    #[test]
+    fn sized_and_scale() {
+        let gen = Gen::sized(Gen::constant);
+        assert_eq!((*gen.run)(Random::new_from_seed(0), 10).value, 10);
+        assert_eq!((*gen.clone().resize(3).run)(Random::new_from_seed(0), 10).value, 3);
+        assert_eq!((*gen.scale(|s| s / 2).run)(Random::new_from_seed(0), 10).value, 5);
+    }
+
+    #[test]
+    fn shrinks_are_distinct() {
+        assert_eq!(towards(10), vec![0, 5, 8, 9]);
+        assert_eq!(towards(0), Vec::<u128>::new());
+        for value in [1u128, 2, 7, 100, 12345, u128::MAX] {
+            let mut shrinks = values(&Gen::shrink_u128(0, value));
+            assert_eq!(shrinks.len(), (128 - value.leading_zeros()) as usize);
+            shrinks.dedup();
+            assert_eq!(shrinks.len(), (128 - value.leading_zeros()) as usize);
+        }
+        let shrinks = values(&Gen::shrink_i128(0, -100));
+        assert_eq!(shrinks, vec![0, -50, -75, -88, -94, -97, -99]);
+
+        // Binary search, so shrinking from far away doesn't step down one at a time
+        Property::check_shrink(Gen::u64_any(), |x| *x < 1_000_000_007, 1_000_000_007);
+        Property::check_shrink(Gen::i64_any(), |x| *x > -1_000_000_007, -1_000_000_007);
+    }
+
+    #[test]
+    fn signed_shrinks_toward_zero() {
+        let gen = Gen::i32(-100..100);
+        for seed in 0..20 {
+            let tree = (*gen.run)(Random::new_from_seed(seed), 0);
+            let v = tree.value;
+            assert!(all_to_depth(&tree, 3, &|&x| (-100..100).contains(&x) && x.abs() <= v.abs()));
+        }
+        assert_eq!((*Gen::i8(-2..3).enumerate)(10).unwrap().to_vec(), vec![0, -1, 1, -2, 2]);
+        assert_eq!((*Gen::i64(-10..-5).enumerate)(10).unwrap().zero, -6);
+        assert_eq!((*Gen::i16(5..10).enumerate)(10).unwrap().zero, 5);
+
+        // Full range doesn't overflow
+        let tree = (*Gen::i128(i128::MIN..i128::MAX).run)(Random::new_from_seed(1), 0);
+        assert!(values(&tree).iter().all(|x| x.abs() < tree.value.abs()));
+    }
+
+    #[test]
+    fn no_shrink() {
+        let gen = Gen::u64(0..1000).vec(Gen::usize(1..10)).no_shrink();
+        let (value, shrinks) = gen.sample_tree(3, 0);
+        assert!(!value.is_empty());
+        assert!(shrinks.is_empty());
+    }
+
+    #[test]
+    fn custom_shrinker() {
+        // Only shrinks by dividing by ten
+        let gen = Gen::u64(0..100000).replace_shrinker(|&x| if x > 0 { vec![x / 10] } else { vec![] });
+        Property::check_shrink(gen, |&x| x < 50, 338);
+        assert_eq!(Gen::constant(0u64).with_shrinker(|_| vec![]).sample_tree(0, 0), (0, vec![]));
+        assert_eq!(Gen::bool_ratio(1, 1).with_shrinker(|_| vec![false]).sample_tree(0, 0), (true, vec![false, false]));
+    }
+
+    #[test]
+    fn flatten_shrinks_both() {
+        let gen = Gen::u64(1..10).map(|n| Gen::u64(0..n * 100)).flatten();
+        Property::check_shrink(gen.clone(), |&x| x < 50, 50);
+        assert_eq!(gen.sample(3, 0, 5), Gen::u64(1..10).and_then(|n| Gen::u64(0..n * 100)).sample(3, 0, 5));
+    }
+
+    #[test]
+    fn map_n() {
+        // The closure captures something that isn't Clone
+        let offset = std::cell::Cell::new(1000);
+        let gen = Gen::map3(Gen::u64(0..10), Gen::u64(0..10), Gen::u64(0..10), move |a, b, c| a + b + c + offset.get());
+        Property::check_shrink(gen, |&x| x < 1010, 1010);
+        let gen = Gen::map5(Gen::u8(0..2), Gen::u8(0..2), Gen::u8(0..2), Gen::u8(0..2), Gen::u8(0..2),
+            |a, b, c, d, e| vec![a, b, c, d, e]);
+        assert_eq!((*gen.enumerate)(100).unwrap().len(), 32);
+    }
+
+    #[test]
+    fn samples() {
+        let gen = Gen::u64(0..1000);
+        assert_eq!(gen.sample(7, 0, 5), gen.sample(7, 0, 5));
+        assert_eq!(gen.sample(7, 0, 5).len(), 5);
+        assert_eq!(Gen::u64(0..10).sample_tree(0, 0).0, (*Gen::u64(0..10).run)(Random::new_from_seed(0), 0).value);
+        assert_eq!(Gen::constant(10).sample_tree(1, 0), (10, vec![]));
+        assert_eq!(Gen::bool_ratio(1, 1).sample_tree(1, 0), (true, vec![false]));
+    }
+
+    #[test]
+    fn element_of_slice() {
+        let names = ["ann", "bob", "cat"];
+        Property::check_shrink(Gen::element(&names), |n| *n != "bob" && *n != "cat", "bob");
+        assert_eq!((*Gen::element(&names[1..]).enumerate)(10).unwrap().to_vec(), vec!["bob", "cat"]);
+    }
+
+    #[test]
+    fn biased_hits_edges() {
+        let values = Gen::u64_biased(0..u64::MAX).sample(0, 0, 2000);
+        assert!(values.contains(&(u64::MAX - 1)) && values.contains(&0));
+        assert!(values.iter().any(|x| x.is_power_of_two() && *x > 1));
+        let values = Gen::i32_biased(-1000..1000).sample(0, 0, 1000);
+        assert!(values.contains(&-1000) && values.contains(&999) && values.contains(&-1));
+        assert!(values.iter().all(|x| (-1000..1000).contains(x)));
+        Property::check_shrink(Gen::i8_biased(-100..100), |&x| x > -50, -50);
+    }
+
+    #[test]
+    fn explicit_origin() {
+        Property::check_shrink(Gen::i32(GenRange::from(-40..50).with_origin(20)), |&t| t < 30, 30);
+        Property::check_shrink(Gen::u8(GenRange::from(0..100).with_origin(50)), |&x| x > 10, 10);
+        assert_eq!((*Gen::u8(250..=255).enumerate)(10).unwrap().to_vec(), vec![250, 251, 252, 253, 254, 255]);
+        assert_eq!((*Gen::u64(GenRange::from(0..5).with_origin(2)).enumerate)(10).unwrap().to_vec(), vec![2, 1, 3, 0, 4]);
+    }
+
+    #[test]
+    fn children_are_cached() {
+        let forced = Rc::new(Cell::new(0));
+        let counter = forced.clone();
+        let tree = Tree::new(0, move || {
+            counter.set(counter.get() + 1);
+            vec![Tree::leaf(1), Tree::leaf(2)]
+        });
+        assert_eq!((*tree.children)().count(), 2);
+        assert_eq!((*Tree::clone(&tree).children)().count(), 2);
+        assert_eq!(forced.get(), 1);
+    }
+
+    #[test]
+    fn path_encodings() {
+        let path : TreePath = "3.0.7.1".parse().unwrap();
+        assert_eq!(path.to_string(), "3.0.7.1");
+        assert_eq!(path.to_base64(), "AwAHAQ");
+        assert_eq!(TreePath::from_base64("AwAHAQ"), Ok(path));
+        assert_eq!("".parse(), Ok(TreePath::empty()));
+        assert_eq!(TreePath::empty().to_base64(), "");
+
+        let path = TreePath { indices : vec![0, 127, 128, 300, usize::MAX] };
+        assert_eq!(path.to_string().parse(), Ok(path.clone()));
+        assert_eq!(TreePath::from_bytes(&path.to_bytes()), Ok(path.clone()));
+        assert_eq!(TreePath::from_base64(&path.to_base64()), Ok(path));
+
+        assert!("1..2".parse::<TreePath>().is_err());
+        assert!("-1".parse::<TreePath>().is_err());
+        assert!(TreePath::from_bytes(&[0x80]).is_err());
+        assert!(TreePath::from_base64("A").is_err());
+        assert!(TreePath::from_base64("AB").is_err());
+        assert!(TreePath::from_base64("a b").is_err());
+    }
+
+    #[test]
+    fn stale_paths() {
+        let tree = Tree::new(1, || vec![Tree::new(2, || vec![Tree::leaf(3)])]);
+        let (closest, consumed) = tree.get_path_prefix(&TreePath::from(vec![0, 5, 0]));
+        assert_eq!((closest.value, consumed), (2, 1));
+
+        // Once the first value shrinks, the second generator's tree has a different shape
+        let gen = Gen::combine(|c| {
+            let a = c.of(Gen::new(|_, _| Tree::new(1, || vec![Tree::leaf(0)])));
+            let b = c.of(Gen::new(move |_, _| if a == 1 {
+                Tree::new(10, || vec![Tree::leaf(5), Tree::new(6, || vec![Tree::leaf(3)])])
+            } else {
+                Tree::new(20, || vec![Tree::new(21, || vec![Tree::leaf(22)])])
+            }));
+            (a, b)
+        });
+        let tree = (*gen.run)(Random::new_from_seed(0), 10);
+        // Path [1] for the second generator doesn't fit the tree for a == 0, so shrinking goes on
+        // from the root of that tree rather than repeating the same value
+        let stale = tree.get_path_or_closest(&TreePath::from(vec![2, 0]));
+        assert_eq!(stale.value, (0, 20));
+        assert_eq!((*stale.children)().map(|t| t.value).collect::<Vec<_>>(), vec![(0, 21)]);
+    }
+
+    #[test]
+    fn paths_follow_call_sites() {
+        let gen = Gen::combine(|c| {
+            let flag = c.of(Gen::new(|_, _| Tree::new(true, || vec![Tree::leaf(false)])));
+            let x = if flag { Some(c.of(Gen::new(|_, _| Tree::new(50, || vec![Tree::leaf(7)])))) } else { None };
+            let y = c.of(Gen::new(|_, _| Tree::new(60, || vec![Tree::leaf(8)])));
+            (flag, x, y)
+        });
+        let tree = (*gen.run)(Random::new_from_seed(0), 10);
+        let at = |path : Vec<usize>| tree.get_path_or_closest(&TreePath::from(path)).value;
+        assert_eq!(at(vec![2]), (true, Some(50), 8));
+        // Dropping x keeps the shrink of y, rather than handing it x's path
+        assert_eq!(at(vec![2, 0]), (false, None, 8));
+        assert_eq!(at(vec![1, 0]), (false, None, 60));
+
+        // Values don't depend on which other generators came first
+        let ys = |flag : bool| Gen::combine(move |c| {
+            if flag {
+                c.of(Gen::u64(0..1000));
+            }
+            c.of_labelled("y", Gen::u64(0..1000))
+        }).sample(1, 10, 5);
+        assert_eq!(ys(true), ys(false));
+
+        // Nor on where the calls are in the source code, just their order
+        let pairs = Gen::combine(|c| (c.of(Gen::u64(0..1000)), c.of(Gen::u64(0..1000))));
+        let elsewhere = Gen::combine(|c| {
+            let a = c.of(Gen::u64(0..1000));
+            let b = c.of(Gen::u64(0..1000));
+            (a, b)
+        });
+        assert_eq!(pairs.sample(1, 10, 5), elsewhere.sample(1, 10, 5));
+    }
+
+    #[test]
+    fn combine_forces_children_once() {
+        let forced = Rc::new(Cell::new(0));
+        let forced_gen = forced.clone();
+        let gen = Gen::combine(move |c| {
+            let forced = forced_gen.clone();
+            let a = c.of(Gen::new(move |_, _| {
+                let forced = forced.clone();
+                Tree::new(0u64, move || {
+                    forced.set(forced.get() + 1);
+                    Vec::new()
+                })
+            }));
+            a + c.of(Gen::u64(0..100))
+        });
+        let tree = (*gen.run)(Random::new_from_seed(3), 10);
+        // Shrinking the second generator doesn't recount the first generator's shrinks
+        assert!((*tree.children)().count() > 1);
+        assert_eq!(forced.get(), 1);
+    }
+
+    #[test]
+    fn shared_paths() {
+        let path = SharedPath::default().push(3).push(1);
+        let longer = path.push(4);
+        assert_eq!(longer.to_path(), TreePath::from(vec![3, 1, 4]));
+        assert_eq!(path.to_path(), TreePath::from(vec![3, 1]));
+        assert_eq!(longer.truncate(1).to_path(), TreePath::from(vec![3]));
+
+        // Dropping a long path doesn't overflow the stack
+        let long = (0..200_000).fold(SharedPath::default(), |path, ix| path.push(ix));
+        assert_eq!(long.len(), 200_000);
+        drop(long);
+    }
+
+    #[test]
+    fn combine_reuses_generated_trees() {
+        let runs = Rc::new(Cell::new(0));
+        let runs_gen = runs.clone();
+        let gen = Gen::combine(move |c| {
+            let runs = runs_gen.clone();
+            let a = c.of_cached(Gen::new(move |r, s| {
+                runs.set(runs.get() + 1);
+                (*Gen::u64(0..100).run)(r, s)
+            }));
+            (a, c.of_cached(Gen::u64(0..100)))
+        });
+        let tree = (*gen.run)(Random::new_from_seed(3), 10);
+        // Shrinking either generator carries on from the first generator's tree instead of running it
+        // again
+        assert!(tree.value.0 > 0);
+        assert!(tree.count_to_depth(3) > 10);
+        assert_eq!(runs.get(), 1);
+    }
+
+    #[test]
+    fn combine_borrowed_values() {
+        let words = ["apple".to_string(), "banana".to_string(), "cherry".to_string()];
+        let word = Gen::usize(0..3).map(|i| words[i].as_str());
+        let gen = Gen::combine(move |c| (c.of(word.clone()), c.of_labelled("second", word.clone())));
+        Property::check_shrink(gen, |(a, b)| a == b, ("apple", "banana"));
+    }
+
+    #[test]
+    fn visit() {
+        let tree = Tree::new(0, || vec![Tree::new(1, || vec![Tree::leaf(3), Tree::leaf(4)]), Tree::leaf(2)]);
+        let mut seen = Vec::new();
+        let stats = tree.visit(1, |&value, depth, children| seen.push((value, depth, children)));
+        assert_eq!(seen, vec![(0, 0, 2), (1, 1, 2), (2, 1, 0)]);
+        assert_eq!(stats, VisitStats { nodes : 3, leaves : 1, max_depth : 1, truncated : 1 });
+        assert_eq!(tree.visit(5, |_, _, _| {}).nodes, tree.count_to_depth(5));
+    }
+
+    #[test]
+    fn debug_preview() {
+        let tree = Tree::new(10u64, || (0..10).map(Tree::leaf));
+        assert_eq!(format!("{:?}", tree), "Tree { value: 10, children: <not forced> }");
+        let untouched = Tree::new(1, || -> Vec<Tree<u64>> { panic!("children forced") });
+        assert_eq!(format!("{:?}", untouched), "Tree { value: 1, children: <not forced> }");
+        assert_eq!(format!("{:?}", Gen::u64(0..10)), "Gen<u64> { .. }");
+        assert_eq!(tree.render(1).lines().collect::<Vec<_>>()[..3], ["10", "  0", "  1"]);
+    }
+
+    #[test]
+    fn size_and_depth() {
+        let tree = Tree::new(0, || vec![Tree::new(1, || vec![Tree::leaf(3), Tree::leaf(4)]), Tree::leaf(2)]);
+        assert_eq!((tree.count_to_depth(0), tree.count_to_depth(1), tree.count_to_depth(5)), (1, 3, 5));
+        assert_eq!(tree.estimate_size(10), SizeEstimate::Exact(5));
+        assert_eq!(tree.estimate_size(4), SizeEstimate::AtLeast(4));
+        assert_eq!((tree.max_depth(1), tree.max_depth(10)), (1, 2));
+
+        // Infinitely many shrinks at every level
+        fn nats(n : u64) -> Tree<'static, u64> {
+            Tree::new(n, move || (n + 1..).map(nats))
+        }
+        assert_eq!(nats(0).estimate_size(1000), SizeEstimate::AtLeast(1000));
+        assert_eq!(nats(0).max_depth(50), 50);
+    }
+
+    #[test]
+    fn path_navigation() {
+        let mut path = TreePath::from(vec![3, 0, 7]);
+        path.push(1);
+        assert_eq!(path.indices(), &[3, 0, 7, 1]);
+        assert_eq!(path.pop(), Some(1));
+        assert_eq!(path.concat(&TreePath::from(vec![2])).to_string(), "3.0.7.2");
+        assert_eq!(path.common_prefix(&TreePath::from(vec![3, 0, 5])), TreePath::from(vec![3, 0]));
+        assert!(path.starts_with(&TreePath::from(vec![3, 0])) && !path.starts_with(&TreePath::from(vec![0])));
+        let prefixes : Vec<String> = path.prefixes().map(|p| p.to_string()).collect();
+        assert_eq!(prefixes, vec!["", "3", "3.0", "3.0.7"]);
+        path.truncate(1);
+        assert_eq!((path.len(), path.is_empty()), (1, false));
+    }
+
+    #[test]
+    fn children_stream() {
+        // Infinitely many shrinks are fine as long as nothing needs all of them
+        let tree = Tree::new(10u64, || (0..).map(Tree::leaf)).map(|x| x + 1).filter(|x| x % 2 == 0);
+        assert_eq!((*tree.children)().take(3).map(|t| t.value).collect::<Vec<_>>(), vec![2, 4, 6]);
+        assert_eq!(tree.get_path_or_closest(&TreePath { indices : vec![4] }).value, 10);
+        let gen = Gen::new(|_, _| Tree::new(10u64, || (0..).map(Tree::leaf)));
+        Property::check_shrink(gen, |x| *x < 5, 5);
+    }
+
+    #[test]
+    fn tree_ops_are_lazy() {
+        let untouched = || Tree::new(1, || -> Vec<Tree<u64>> { panic!("children forced") });
+        let tree = untouched().map(|x| x + 1).zip(untouched()).bind(|(a, b)| Tree::leaf(a * 10 + b));
+        assert_eq!(tree.value, 21);
+
+        let tree = Tree::new(2, || vec![Tree::leaf(0), Tree::leaf(1)]).zip(Tree::new(3, || vec![Tree::leaf(0)]));
+        let shrinks : Vec<(u64, u64)> = (*tree.children)().map(|t| t.value).collect();
+        assert_eq!(shrinks, vec![(0, 3), (1, 3), (2, 0)]);
+    }
+
+    #[test]
+    fn expand_adds_shrinks() {
+        let drop_first = |v : &Vec<u64>| if v.is_empty() { vec![] } else { vec![v[1..].to_vec()] };
+        let tree = Tree::new(vec![5, 6], || vec![Tree::leaf(vec![0, 6])]).expand(drop_first);
+        assert_eq!(values(&tree), vec![vec![0, 6], vec![6]]);
+        // Both the original children and the extra shrinks are expanded again
+        let children : Vec<_> = (*tree.children)().collect();
+        assert_eq!(values(&children[0]), vec![vec![6]]);
+        assert_eq!(values(&children[1]), vec![Vec::<u64>::new()]);
+    }
+
+    #[test]
+    fn full_ranges() {
+        let values = Gen::u8_any().sample(0, 0, 2000);
+        assert!(values.contains(&0) && values.contains(&u8::MAX));
+        let values = Gen::i8_any().sample(0, 0, 2000);
+        assert!(values.contains(&i8::MIN) && values.contains(&i8::MAX));
+        assert!(Gen::u64_any().sample(0, 0, 100).iter().any(|x| *x > u64::MAX / 2));
+        Property::check_shrink(Gen::i64_any(), |x| *x > -1000, -1000);
+    }
+
+    #[test]
+    fn unsigned_widths() {
+        let gen = Gen::u8_inclusive(u8::MIN..=u8::MAX);
+        assert_eq!((*gen.enumerate)(256).unwrap().len(), 256);
+        assert!((*gen.enumerate)(255).is_none());
+        assert_eq!((*Gen::u16(10..13).enumerate)(10).unwrap().to_vec(), vec![10, 11, 12]);
+
+        let tree = (*Gen::u128_inclusive(0..=u128::MAX).run)(Random::new_from_seed(2), 0);
+        let shrinks = values(&tree);
+        assert_eq!((shrinks[0], shrinks[1], shrinks[shrinks.len() - 1]), (0, tree.value - tree.value / 2, tree.value - 1));
+        let tree = (*Gen::u32(100..200).run)(Random::new_from_seed(2), 0);
+        assert!(all_to_depth(&tree, 4, &|x| (100..200).contains(x)));
+    }
+
+    #[test]
+    fn bool_ratio() {
+        let gen = Gen::bool_ratio(1, 4);
+        let trues = (0..1000).filter(|&seed| (*gen.run)(Random::new_from_seed(seed), 0).value).count();
+        assert!(trues > 150 && trues < 350);
+
+        let tree = (0..).map(|seed| (*gen.run)(Random::new_from_seed(seed), 0)).find(|t| t.value).unwrap();
+        assert_eq!(values(&tree), vec![false]);
+        assert_eq!((*Gen::bool().enumerate)(2).unwrap().to_vec(), vec![false, true]);
+    }
+
+    #[test]
+    fn filter_discards() {
+        let gen = Gen::u64(0..1000).filter(|_| false);
+        let run = std::panic::AssertUnwindSafe(|| (*gen.run)(Random::new_from_seed(0), 0).value);
+        let result = std::panic::catch_unwind(run);
+        assert!(result.unwrap_err().is::<Discard>());
+    }
+}
@@ -0,0 +1,272 @@
+// Checks that a generator's shrink tree is well behaved, for authors of custom generators and
+// shrinkers. Each law runs the generator on a range of seeds and looks at a bounded part of each
+// shrink tree, so that infinite trees can still be checked.
+// > laws::check(&Gen::u64(0..100).with_shrinker(my_shrinks), &LawConfig::default()).unwrap()
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Debug;
+
+use crate::hh3_lazy_tree::{Gen, Tree, TreePath};
+use crate::property::catch_discard;
+use crate::random::Random;
+use crate::tree::zipper::TreeZipper;
+
+/// Settings for checking the laws
+#[derive(Clone, Debug)]
+pub struct LawConfig {
+    /// How many seeds to run the generator with, starting from zero
+    pub tests : usize,
+    /// Generator size
+    pub size : usize,
+    /// Most nodes of each shrink tree to look at
+    pub max_nodes : usize,
+    /// Shrink chains longer than this count as not terminating
+    pub max_depth : usize,
+}
+
+impl Default for LawConfig {
+    fn default() -> LawConfig {
+        LawConfig { tests : 20, size : 30, max_nodes : 1000, max_depth : 100 }
+    }
+}
+
+/// A law that didn't hold, with where to find the offending node
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LawViolation {
+    pub law : &'static str,
+    pub seed : u128,
+    /// Path from the root of the shrink tree to the offending node
+    pub path : TreePath,
+    pub message : String,
+}
+
+impl fmt::Display for LawViolation {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "law {} broken with seed {} at path \"{}\": {}", self.law, self.seed, self.path, self.message)
+    }
+}
+
+impl Error for LawViolation {}
+
+/// Check all of the laws, returning the first violation
+pub fn check<A>(gen : &Gen<A>, config : &LawConfig) -> Result<(), LawViolation>
+where A : Clone + Debug + PartialEq {
+    deterministic(gen, config)?;
+    no_child_equals_parent(gen, config)?;
+    terminates(gen, config)?;
+    paths_consistent(gen, config)
+}
+
+/// Running the generator twice with the same seed gives the same tree, with the shrinks of each
+/// node in the same order
+pub fn deterministic<A>(gen : &Gen<A>, config : &LawConfig) -> Result<(), LawViolation>
+where A : Clone + Debug + PartialEq {
+    for_each_seed(gen, config, |seed, tree| {
+        let again = match run(gen, seed, config.size) {
+            Some(again) => again,
+            None => return Err(violation("deterministic", seed, TreePath::empty(),
+                format!("discarded the second time, but generated {:?} the first time", tree.value))),
+        };
+        let first = breadth_first(&tree, config.max_nodes);
+        let second = breadth_first(&again, config.max_nodes);
+        for ((path, a), (path_again, b)) in first.iter().zip(&second) {
+            if path != path_again {
+                // The trees have different shapes, so the node at path has fewer shrinks one time
+                let path = path.common_prefix(path_again);
+                return Err(violation("deterministic", seed, path,
+                    "different numbers of shrinks each time".to_string()));
+            }
+            if a.value != b.value {
+                return Err(violation("deterministic", seed, path.clone(),
+                    format!("{:?} then {:?}", a.value, b.value)));
+            }
+        }
+        if first.len() != second.len() {
+            return Err(violation("deterministic", seed, TreePath::empty(),
+                format!("{} nodes then {} nodes", first.len(), second.len())));
+        }
+        Ok(())
+    })
+}
+
+/// The shrinks of each node are ordered by the measure, from smallest to largest. A measure of
+/// how simple a value is, eg the length of a list or the distance of a number from the origin,
+/// checks that the most aggressive shrinks come first.
+/// > laws::shrinks_ordered_by(&Gen::u64(0..100).vec(Gen::usize(0..10)), &config, |v| v.len())
+pub fn shrinks_ordered_by<A, M, F>(gen : &Gen<A>, config : &LawConfig, measure : F) -> Result<(), LawViolation>
+where A : Clone + Debug + PartialEq,
+M : PartialOrd + Debug,
+F : Fn(&A) -> M {
+    for_each_seed(gen, config, |seed, tree| {
+        for (path, node) in breadth_first(&tree, config.max_nodes) {
+            let children : Vec<A> = (*node.children)().take(config.max_nodes).map(|c| c.value).collect();
+            for (ix, pair) in children.windows(2).enumerate() {
+                let (before, after) = (measure(&pair[0]), measure(&pair[1]));
+                if after < before {
+                    return Err(violation("shrinks_ordered_by", seed, path.concat(&TreePath::from(vec![ix + 1])),
+                        format!("{:?} with measure {:?} comes after {:?} with measure {:?}", pair[1], after, pair[0], before)));
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+/// No shrink is the same as the value it shrinks from, which would let shrinking go around in circles
+pub fn no_child_equals_parent<A>(gen : &Gen<A>, config : &LawConfig) -> Result<(), LawViolation>
+where A : Clone + Debug + PartialEq {
+    for_each_seed(gen, config, |seed, tree| {
+        let nodes = breadth_first(&tree, config.max_nodes);
+        for (path, node) in nodes.iter().skip(1) {
+            let mut parent = path.clone();
+            parent.pop();
+            let parent = tree.get_path_or_closest(&parent);
+            if parent.value == node.value {
+                return Err(violation("no_child_equals_parent", seed, path.clone(),
+                    format!("{:?} shrinks to itself", node.value)));
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Following shrinks from the root reaches a value without shrinks within max_depth steps.
+/// The tree is searched depth first, trying the first shrinks first, as greedy shrinking does.
+pub fn terminates<A>(gen : &Gen<A>, config : &LawConfig) -> Result<(), LawViolation>
+where A : Clone + Debug + PartialEq {
+    for_each_seed(gen, config, |seed, tree| {
+        let mut z = TreeZipper::new(tree);
+        let mut forced = 1;
+        while forced < config.max_nodes {
+            if z.down(0) {
+                forced += 1;
+                if z.depth() > config.max_depth {
+                    return Err(violation("terminates", seed, z.path(),
+                        format!("more than {} shrinks in a row, reaching {:?}", config.max_depth, z.value())));
+                }
+                continue;
+            }
+            // No shrinks here, so go on to the next sibling of the closest node that has one
+            while !z.right() {
+                if !z.up() {
+                    return Ok(());
+                }
+            }
+            forced += 1;
+        }
+        Ok(())
+    })
+}
+
+/// Looking up the path of each node from the root gives that node, and looking up a shrink of a node
+/// without any gives the node itself
+pub fn paths_consistent<A>(gen : &Gen<A>, config : &LawConfig) -> Result<(), LawViolation>
+where A : Clone + Debug + PartialEq {
+    for_each_seed(gen, config, |seed, tree| {
+        for (path, node) in breadth_first(&tree, config.max_nodes) {
+            let (found, consumed) = tree.get_path_prefix(&path);
+            if consumed != path.len() || found.value != node.value {
+                return Err(violation("paths_consistent", seed, path,
+                    format!("found {:?} after {} steps instead of {:?}", found.value, consumed, node.value)));
+            }
+            if (*node.children)().next().is_none() {
+                let (closest, consumed) = tree.get_path_prefix(&path.concat(&TreePath::from(vec![0])));
+                if consumed != path.len() || closest.value != node.value {
+                    return Err(violation("paths_consistent", seed, path,
+                        format!("looking below a node without shrinks found {:?} instead of {:?}", closest.value, node.value)));
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+fn violation(law : &'static str, seed : u128, path : TreePath, message : String) -> LawViolation {
+    LawViolation { law, seed, path, message }
+}
+
+/// Run the generator, returning None if it discarded the test case
+fn run<'a, A>(gen : &Gen<'a, A>, seed : u128, size : usize) -> Option<Tree<'a, A>> {
+    catch_discard(|| (*gen.run)(Random::new_from_seed(seed), size))
+}
+
+/// Check a law on the tree for each seed, skipping seeds that the generator discards
+fn for_each_seed<A, F>(gen : &Gen<A>, config : &LawConfig, mut law : F) -> Result<(), LawViolation>
+where F : FnMut(u128, Tree<A>) -> Result<(), LawViolation> {
+    for seed in 0..config.tests as u128 {
+        if let Some(tree) = run(gen, seed, config.size) {
+            law(seed, tree)?;
+        }
+    }
+    Ok(())
+}
+
+/// The first max_nodes nodes of the tree in breadth first order, with their paths
+fn breadth_first<'a, A : Clone>(tree : &Tree<'a, A>, max_nodes : usize) -> Vec<(TreePath, Tree<'a, A>)> {
+    let mut nodes = Vec::new();
+    let mut queue = VecDeque::new();
+    queue.push_back((TreePath::empty(), tree.clone()));
+    while let Some((path, node)) = queue.pop_front() {
+        if nodes.len() >= max_nodes {
+            break;
+        }
+        // Only queue as many children as could still be looked at, in case there are infinitely many
+        let room = max_nodes - nodes.len() - queue.len();
+        for (ix, child) in (*node.children)().take(room).enumerate() {
+            let mut child_path = path.clone();
+            child_path.push(ix);
+            queue.push_back((child_path, child));
+        }
+        nodes.push((path, node));
+    }
+    nodes
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::Gen;
+    use crate::laws::*;
+
+    #[test]
+    fn builtin_generators() {
+        let config = LawConfig { tests : 5, ..LawConfig::default() };
+        check(&Gen::u64(0..1000), &config).unwrap();
+        check(&Gen::u64(0..100).vec(Gen::usize(0..10)), &config).unwrap();
+        check(&Gen::string(Gen::char_ascii_alphanumeric(), Gen::usize(0..10)), &config).unwrap();
+    }
+
+    #[test]
+    fn shrink_order_contract() {
+        let config = LawConfig { tests : 5, max_nodes : 300, ..LawConfig::default() };
+        shrinks_ordered_by(&Gen::u64(0..1000), &config, |&x| x).unwrap();
+        shrinks_ordered_by(&Gen::i32(-500..500), &config, |x| x.abs()).unwrap();
+        shrinks_ordered_by(&Gen::u64(0..100).vec(Gen::usize(0..20)), &config, |v| v.len()).unwrap();
+        shrinks_ordered_by(&Gen::string(Gen::char_ascii_alphanumeric(), Gen::usize(0..20)), &config, |s| s.len()).unwrap();
+        deterministic(&Gen::u64(0..100).vec(Gen::usize(0..20)), &config).unwrap();
+
+        let backwards = Gen::u64(0..1000).replace_shrinker(|&x| (0..x).rev().take(3).collect());
+        let err = shrinks_ordered_by(&backwards, &config, |&x| x).unwrap_err();
+        assert_eq!(err.path.to_string(), "1");
+    }
+
+    #[test]
+    fn broken_shrinkers() {
+        let config = LawConfig::default();
+        let circular = Gen::u64(0..1000).with_shrinker(|&x| vec![x]);
+        assert_eq!(check(&circular, &config).unwrap_err().law, "no_child_equals_parent");
+
+        let growing = Gen::u64(1..1000).replace_shrinker(|&x| vec![x + 1]);
+        let err = terminates(&growing, &config).unwrap_err();
+        assert_eq!((err.seed, err.path.len()), (0, config.max_depth + 1));
+
+        let counter = std::cell::Cell::new(0);
+        let random = Gen::new(move |_, _| {
+            counter.set(counter.get() + 1);
+            Tree::leaf(counter.get())
+        });
+        assert_eq!(check(&random, &config).unwrap_err().to_string(),
+            "law deterministic broken with seed 0 at path \"\": 1 then 2");
+    }
+}
@@ -0,0 +1,23 @@
+// The doc comments use "> " lines for examples without continuing them as block quotes
+#![allow(clippy::doc_lazy_continuation)]
+
+pub mod random;
+pub mod hh1_no_tree;
+pub mod hh2_tree;
+pub mod hh3_lazy_tree;
+pub mod tree;
+pub mod tuple;
+pub mod gen;
+pub mod grammar;
+
+pub mod state;
+pub mod nondet;
+pub mod dist;
+pub mod nonempty;
+pub mod range;
+
+pub mod property;
+pub mod shrink;
+pub mod shrinker;
+pub mod laws;
+pub mod metrics;
@@ -0,0 +1,174 @@
+// Measurements of how well shrinking did, for comparing shrinkers and search strategies between
+// versions. Each counterexample in a report records how many candidates the property ran on while
+// shrinking, how many steps the shrink path took, and optionally how far the minimal value is from
+// the original.
+// > Property::new(gen, p).measure_distance().check(&config).shrink_metrics()
+use std::fmt;
+
+/// How much work shrinking one counterexample took, and how far it got
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ShrinkMetrics {
+    /// Number of times the property ran on a shrink candidate
+    pub candidates : usize,
+    /// Number of successful shrink steps from the original value to the minimal one
+    pub path_length : usize,
+    /// Distance from the original value to the minimal one, if the property has a distance measure
+    pub distance : Option<f64>,
+}
+
+impl fmt::Display for ShrinkMetrics {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} candidates, path length {}", self.candidates, self.path_length)?;
+        if let Some(distance) = self.distance {
+            write!(f, ", distance {}", distance)?;
+        }
+        Ok(())
+    }
+}
+
+/// Shrink metrics added up over every counterexample of a report
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MetricsSummary {
+    pub counterexamples : usize,
+    pub candidates : usize,
+    pub path_length : usize,
+    /// Total distance of the counterexamples that have one
+    pub distance : Option<f64>,
+}
+
+impl MetricsSummary {
+    pub fn add(&mut self, metrics : &ShrinkMetrics) {
+        self.counterexamples += 1;
+        self.candidates += metrics.candidates;
+        self.path_length += metrics.path_length;
+        if let Some(d) = metrics.distance {
+            self.distance = Some(self.distance.unwrap_or(0.0) + d);
+        }
+    }
+}
+
+impl fmt::Display for MetricsSummary {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} counterexample(s), {} candidates, path length {}",
+            self.counterexamples, self.candidates, self.path_length)?;
+        if let Some(distance) = self.distance {
+            write!(f, ", distance {}", distance)?;
+        }
+        Ok(())
+    }
+}
+
+/// How far apart two values are, for measuring how much shrinking simplified a counterexample.
+/// The measure only needs to be consistent between runs, not meaningful across types.
+pub trait Distance {
+    fn distance(&self, other : &Self) -> f64;
+}
+
+macro_rules! impl_distance_int {
+    ($($t:ident)*) => {
+        $(
+            impl Distance for $t {
+                fn distance(&self, other : &$t) -> f64 {
+                    self.abs_diff(*other) as f64
+                }
+            }
+        )*
+    }
+}
+
+impl_distance_int!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+
+impl Distance for bool {
+    fn distance(&self, other : &bool) -> f64 {
+        if self == other { 0.0 } else { 1.0 }
+    }
+}
+
+impl Distance for char {
+    fn distance(&self, other : &char) -> f64 {
+        (*self as u32).abs_diff(*other as u32) as f64
+    }
+}
+
+/// Lists are compared element by element, and each element that only one of them has counts one
+impl<T : Distance> Distance for [T] {
+    fn distance(&self, other : &[T]) -> f64 {
+        let common : f64 = self.iter().zip(other).map(|(a, b)| a.distance(b)).sum();
+        common + self.len().abs_diff(other.len()) as f64
+    }
+}
+
+impl<T : Distance> Distance for Vec<T> {
+    fn distance(&self, other : &Vec<T>) -> f64 {
+        self.as_slice().distance(other)
+    }
+}
+
+impl Distance for String {
+    fn distance(&self, other : &String) -> f64 {
+        let a : Vec<char> = self.chars().collect();
+        let b : Vec<char> = other.chars().collect();
+        a.distance(&b)
+    }
+}
+
+impl<T : Distance> Distance for Option<T> {
+    fn distance(&self, other : &Option<T>) -> f64 {
+        match (self, other) {
+            (Some(a), Some(b)) => a.distance(b),
+            (None, None) => 0.0,
+            _ => 1.0,
+        }
+    }
+}
+
+macro_rules! impl_distance_tuple {
+    ($($ix:tt : $ty:ident),*) => {
+        impl<$($ty : Distance),*> Distance for ($($ty,)*) {
+            fn distance(&self, other : &Self) -> f64 {
+                0.0 $(+ self.$ix.distance(&other.$ix))*
+            }
+        }
+    }
+}
+
+impl_distance_tuple!(0 : A, 1 : B);
+impl_distance_tuple!(0 : A, 1 : B, 2 : C);
+impl_distance_tuple!(0 : A, 1 : B, 2 : C, 3 : D);
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::Gen;
+    use crate::metrics::*;
+    use crate::property::{Config, Property};
+
+    #[test]
+    fn distances() {
+        assert_eq!(3u64.distance(&10), 7.0);
+        assert_eq!((-3i32).distance(&2), 5.0);
+        assert_eq!(vec![5u8, 1].distance(&vec![2]), 4.0);
+        assert_eq!("ab".to_string().distance(&"a".to_string()), 1.0);
+        assert_eq!((1u64, true).distance(&(0, false)), 2.0);
+    }
+
+    #[test]
+    fn records_metrics() {
+        let report = Property::new(Gen::u64(0..1000), |&x| x < 100).measure_distance().check(&Config::default());
+        let cx = &report.counterexamples[0];
+        assert_eq!(cx.metrics.path_length, cx.shrinks);
+        assert!(cx.metrics.candidates >= cx.shrinks);
+        assert_eq!(cx.metrics.distance, Some((cx.original - 100) as f64));
+
+        let summary = report.shrink_metrics();
+        assert_eq!(summary.counterexamples, 1);
+        assert_eq!(summary.candidates, cx.metrics.candidates);
+
+        // Parallel shrinking runs whole batches, so it can try more candidates along the same path
+        let config = Config { shrink_threads : 4, ..Config::default() };
+        let parallel = Property::new_sync(Gen::u64(0..1000), |&x| x < 100).check(&config);
+        assert_eq!(parallel.counterexamples[0].metrics.path_length, cx.metrics.path_length);
+        assert!(parallel.counterexamples[0].metrics.candidates >= cx.metrics.candidates);
+        assert_eq!(parallel.counterexamples[0].metrics.distance, None);
+    }
+}
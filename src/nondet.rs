@@ -6,7 +6,9 @@ use crate::nonempty::NonEmpty;
 /// The closure returns a non-empty vector that describes all the possible options the computation
 /// can evaluate to.
 pub struct NonDet<'a, A> {
-    pub run : Rc<dyn Fn() -> NonEmpty<A> + 'a>
+    pub run : Rc<dyn Fn() -> NonEmpty<A> + 'a>,
+    /// The same options as run, in the same order, but computed as they're needed
+    lazy : Rc<dyn Fn() -> Box<dyn Iterator<Item = A> + 'a> + 'a>,
 }
 
 /// The witness that lets you extract values from a non-deterministic computation is a mutable
@@ -31,39 +33,40 @@ impl<'a, A> NonDet<'a, A> {
     pub fn new<F>(f : F) -> NonDet<'a, A>
     where F : Fn() -> NonEmpty<A> + 'a,
           A : 'a {
+        let run : Rc<dyn Fn() -> NonEmpty<A> + 'a> = Rc::new(f);
+        let all = run.clone();
         NonDet {
-            run : Rc::new(f)
+            run,
+            lazy : Rc::new(move || Box::new((*all)().to_vec().into_iter())),
         }
     }
 
     /// Combine together multiple non-deterministic computations.
+    /// The closure runs once for every combination of choices, so for large choice spaces prefer
+    /// iter, which only runs it for the results that are used.
     pub fn combine<F>(f : F) -> NonDet<'a, A>
     where F : Fn(&mut Witness) -> A + 'a,
           A : 'a {
-        NonDet::new(move || {
-            // Run computation with all the choices as 0 indices, ie the first choice.
-            // This gives us the initial value, as well as telling us how many other choices there
-            // are.
-            let mut w = Witness { indices : Vec::new(), num_choices : Vec::new() };
-            let initial_value = f(&mut w);
-
-            // Mutable vector to keep track of all the results
-            let mut results = NonEmpty { zero : initial_value, vec : Vec::new() };
-
-            // Loop over all the choices and run each one
-            while let Some(next_choice) = Self::incr_choice_indices(&w.indices, &w.num_choices) {
-                // Run with next seq of indices and empty num_choices
-                // (consumer pushes onto end of num_choices to populate)
-                w.indices = next_choice;
-                w.num_choices = Vec::new();
-                let value = f(&mut w);
-
-                // Record result
-                results.vec.push(value);
-            }
+        let f = Rc::new(f);
+        let all = f.clone();
+        NonDet {
+            run : Rc::new(move || {
+                let all = all.clone();
+                enumerate(move |w| Some(all(w))).expect("enumerate: closure never gives up")
+            }),
+            lazy : Rc::new(move || {
+                let f = f.clone();
+                Box::new(Combinations::new(move |w : &mut Witness| f(w)))
+            }),
+        }
+    }
 
-            results
-        })
+    /// Iterate over the options in the same order as run, computing each one only when it's needed.
+    /// For combined computations, this doesn't try the next combination of choices until the
+    /// previous result has been used, so taking the first few is cheap however many there are.
+    /// > NonDet::combine(|c| c.of(xs.clone()) + c.of(ys.clone())).iter().take(10)
+    pub fn iter(&self) -> Box<dyn Iterator<Item = A> + 'a> {
+        (*self.lazy)()
     }
 
     /// Lexicographic ordering on indices. num_choices describes the exclusive range for each element.
@@ -74,9 +77,9 @@ impl<'a, A> NonDet<'a, A> {
     /// >   Some [2, 0, 0]
     /// >   Some [2, 1, 0]
     /// >   None
-    fn incr_choice_indices(indices : &Vec<usize>, num_choices : &Vec<usize>) -> Option<Vec<usize>> {
+    fn incr_choice_indices(indices : &[usize], num_choices : &[usize]) -> Option<Vec<usize>> {
         // Copy and ensure length is same as choices, padding with zeroes as necessary
-        let mut res = indices.clone();
+        let mut res = indices.to_vec();
         res.resize(num_choices.len(), 0);
 
         // Loop from the end of the vector, incrementing each index until the first that doesn't overflow
@@ -95,13 +98,67 @@ impl<'a, A> NonDet<'a, A> {
     }
 }
 
+/// Runs a closure once for each combination of choices, in lexicographic order of the choice
+/// indices. Each run records how many choices it saw, which tells us the next combination to try.
+pub(crate) struct Combinations<F> {
+    f : F,
+    /// Choice indices for the next run, or None once every combination has been tried
+    next : Option<Vec<usize>>,
+}
+
+impl<F> Combinations<F> {
+    pub(crate) fn new(f : F) -> Combinations<F> {
+        // The first run has all the choices as 0 indices, ie the first choice
+        Combinations { f, next : Some(Vec::new()) }
+    }
+}
+
+impl<A, F> Iterator for Combinations<F>
+where F : FnMut(&mut Witness) -> A {
+    type Item = A;
+
+    fn next(&mut self) -> Option<A> {
+        let indices = self.next.take()?;
+        // Empty num_choices, which the consumer pushes onto to populate
+        let mut w = Witness { indices, num_choices : Vec::new() };
+        let value = (self.f)(&mut w);
+        self.next = NonDet::<A>::incr_choice_indices(&w.indices, &w.num_choices);
+        Some(value)
+    }
+}
+
+/// Run a closure for every possible combination of choices, and collect the results.
+/// The closure can give up on the whole enumeration by returning None, for example if there are
+/// too many choices.
+/// This is the worker for NonDet::combine, but is also used for enumerating generators.
+pub(crate) fn enumerate<A, F>(f : F) -> Option<NonEmpty<A>>
+where F : FnMut(&mut Witness) -> Option<A> {
+    let mut results = Combinations::new(f);
+    // The first run gives us the initial value, as well as telling us how many other choices there
+    // are
+    let zero = results.next()??;
+    // Stop as soon as any run gives up
+    let vec = results.collect::<Option<Vec<A>>>()?;
+    Some(NonEmpty { zero, vec })
+}
+
 impl Witness {
+    /// Only the enumeration functions in this crate are allowed to create witnesses
+    pub(crate) fn new() -> Witness {
+        Witness { indices : Vec::new(), num_choices : Vec::new() }
+    }
+
     /// Extract a value from a wrapped up non-deterministic computation
     pub fn of<'a, A>(&mut self, m : NonDet<'a, A>) -> A
     where A : Clone {
-
         // Run the computation to get the vector of choices
         let choices = (*m.run)();
+        self.choose(&choices)
+    }
+
+    /// Extract a value from a vector of choices that has already been computed
+    pub(crate) fn choose<A>(&mut self, choices : &NonEmpty<A>) -> A
+    where A : Clone {
         // m_ix tells us how many previous nested computations we have run.
         // This is used to know which computation this is, and therefore which choice we should use
         let m_ix = self.num_choices.len();
@@ -132,6 +189,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::zero_prefixed_literal, clippy::identity_op)]
     fn ok() {
         let numbers = NonDet::combine(|c| {
             let u100 = nondet(0, vec![1, 2, 3]);
@@ -151,5 +209,22 @@ mod test {
                 200, 201, 210, 211, 220, 221,
                 300, 301, 310, 311, 320, 321
             ]);
+        assert_eq!(numbers.iter().collect::<Vec<_>>(), (*numbers.run)().to_vec());
+        assert_eq!(nondet(1, vec![2]).iter().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn iter_is_lazy() {
+        use std::cell::Cell;
+        let runs = Rc::new(Cell::new(0));
+        let counter = runs.clone();
+        // A billion combinations, which would never finish if they were all computed
+        let big = NonDet::combine(move |c| {
+            counter.set(counter.get() + 1);
+            (0..9).map(|_| c.of(nondet(0u64, (1..10).collect()))).fold(0, |acc, d| acc * 10 + d)
+        });
+        assert_eq!(big.iter().take(3).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(runs.get(), 3);
+        assert_eq!(big.iter().find(|&n| n >= 100), Some(100));
     }
 }
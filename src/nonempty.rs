@@ -7,6 +7,7 @@ pub struct NonEmpty<A> {
 }
 
 impl<A> NonEmpty<A> {
+    #[allow(clippy::should_implement_trait)]
     pub fn index(&self, ix : usize) -> &A {
         if ix == 0 {
             &self.zero
@@ -19,6 +20,19 @@ impl<A> NonEmpty<A> {
         self.vec.len() + 1
     }
 
+    /// Never empty, but clippy likes to see it next to len
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn map<B, F>(self, f : F) -> NonEmpty<B>
+    where F : Fn(A) -> B {
+        NonEmpty {
+            zero : f(self.zero),
+            vec  : self.vec.into_iter().map(f).collect()
+        }
+    }
+
     pub fn to_vec(self) -> Vec<A> {
         let mut vec = Vec::new();
         vec.push(self.zero);
@@ -0,0 +1,791 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::panic;
+#[cfg(feature = "tree_cache")]
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::{mpsc, Arc, Mutex, PoisonError};
+use std::thread;
+
+use crate::hh3_lazy_tree::{Discard, Gen, Tree, TreePath};
+use crate::metrics::{Distance, MetricsSummary, ShrinkMetrics};
+use crate::random::{Random, RngBackend};
+use crate::shrink::{search_limited, search_parallel, ShrinkLimits, ShrinkSearch};
+#[cfg(feature = "tree_cache")]
+use crate::tree::cache::{with_paths, ShrinkCheckpoint};
+use crate::tuple::Tuple;
+
+/// Settings for running a property
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// How many random test cases to generate
+    pub tests : usize,
+    /// Give up after this many test cases have been discarded by the generator, eg by a filter
+    pub discard_limit : usize,
+    /// Seed of the random number generator. Test case i draws from Random::stream(i) of it,
+    /// counting discarded test cases, so no two test cases share random numbers.
+    pub seed : u128,
+    /// Index of the first test case, so that setting seed and first_case from a counterexample
+    /// reruns it first. The size schedule is also indexed by case, so the rerun gets the same size.
+    pub first_case : u64,
+    /// Ignore seed and pick a new one from the operating system on each check, printing it to
+    /// stderr before running any test cases. Setting seed to the printed one reproduces the run.
+    pub entropy_seed : bool,
+    /// How the size passed to the generator changes over the test cases
+    pub size : SizeSchedule,
+    /// Check every value instead of random sampling if the generator's domain has at most `tests`
+    /// values
+    pub exhaustive : bool,
+    /// Keep generating after the first failure, and report every distinct minimal counterexample
+    /// instead of just the first one
+    pub continue_after_failure : bool,
+    /// How to search the shrink tree of a failing test case
+    pub shrink : ShrinkSearch,
+    /// Caps on the shrink search
+    pub shrink_limits : ShrinkLimits,
+    /// How many shrink candidates to check at once, on separate threads. Only used for properties
+    /// built with Property::new_sync, and with greedy shrinking.
+    pub shrink_threads : usize,
+    /// Random number generator to generate test cases with. The same seed gives different test
+    /// cases with different generators.
+    pub rng : RngBackend,
+    /// Save where shrinking a failing test case has got to in this file, and carry on from there if
+    /// the file exists when checking starts, eg after an interrupted run. The file is removed once
+    /// shrinking finishes.
+    #[cfg(feature = "tree_cache")]
+    pub shrink_checkpoint : Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            tests : 100,
+            discard_limit : 100,
+            seed : 0,
+            first_case : 0,
+            entropy_seed : false,
+            size : SizeSchedule::Linear { max : 100 },
+            exhaustive : true,
+            continue_after_failure : false,
+            shrink : ShrinkSearch::default(),
+            shrink_limits : ShrinkLimits::default(),
+            shrink_threads : 1,
+            rng : RngBackend::default(),
+            #[cfg(feature = "tree_cache")]
+            shrink_checkpoint : None,
+        }
+    }
+}
+
+/// How the generator size grows with each test case.
+/// Starting small means simple failures are found with simple values, while later test cases
+/// stress-test larger structures.
+#[derive(Clone)]
+pub enum SizeSchedule {
+    /// Every test case uses the same size
+    Constant(usize),
+    /// Grows linearly from 0 on the first test case to max on the last
+    Linear { max : usize },
+    /// Grows geometrically from 0 on the first test case to max on the last, so most test cases
+    /// are small
+    Exponential { max : usize },
+    /// Size computed from the index of the test case
+    Custom(Rc<dyn Fn(usize) -> usize>),
+}
+
+impl SizeSchedule {
+    /// Size to use for test case number `case` out of `tests`. Cases past the last one, eg after
+    /// discards or when starting from Config::first_case, get the last one's size.
+    pub fn size(&self, case : usize, tests : usize) -> usize {
+        // Fraction of the way through the test cases; the last test case gets the max size
+        let progress = if tests <= 1 {
+            1.0
+        } else {
+            (case as f64 / (tests - 1) as f64).min(1.0)
+        };
+
+        match self {
+            SizeSchedule::Constant(size) => *size,
+            SizeSchedule::Linear { max } => (*max as f64 * progress).round() as usize,
+            SizeSchedule::Exponential { max } => {
+                ((*max as f64 + 1.0).powf(progress) - 1.0).round() as usize
+            }
+            SizeSchedule::Custom(f) => f(case),
+        }
+    }
+}
+
+impl Debug for SizeSchedule {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SizeSchedule::Constant(size) => write!(f, "Constant({})", size),
+            SizeSchedule::Linear { max } => write!(f, "Linear {{ max: {} }}", max),
+            SizeSchedule::Exponential { max } => write!(f, "Exponential {{ max: {} }}", max),
+            SizeSchedule::Custom(_) => write!(f, "Custom(<closure>)"),
+        }
+    }
+}
+
+/// A failing test case, before and after shrinking.
+/// Counterexamples found by exhaustive checking are not shrunk, and have the configured seed, case
+/// and size.
+#[derive(Clone, Debug)]
+pub struct Counterexample<A> {
+    /// Seed that generated the original value
+    pub seed : u128,
+    /// Index of the test case, which picks the stream of the seed's generator it drew from
+    pub case : u64,
+    /// Size that generated the original value
+    pub size : usize,
+    /// The value that was originally generated
+    pub original : A,
+    /// The smallest failing value that shrinking found
+    pub minimal : A,
+    /// Number of successful shrink steps from original to minimal
+    pub shrinks : usize,
+    /// Whether shrinking stopped early because of the limits in the config, so minimal might
+    /// shrink further
+    pub truncated : bool,
+    /// How much work shrinking took, and how far it got
+    pub metrics : ShrinkMetrics,
+}
+
+/// The outcome of checking a property
+#[derive(Clone, Debug)]
+pub struct Report<A> {
+    /// Number of test cases that were run
+    pub tests : usize,
+    /// Number of test cases that were discarded by the generator
+    pub discards : usize,
+    /// Discard limit from the config
+    pub discard_limit : usize,
+    /// Whether the test cases covered every value of the generator
+    pub exhaustive : bool,
+    /// Distinct minimal counterexamples, in the order they were found.
+    /// Empty if every test passed.
+    pub counterexamples : Vec<Counterexample<A>>,
+    /// Number of test cases with each label, if the property classifies its values
+    pub labels : BTreeMap<String, usize>,
+}
+
+impl<A> Report<A> {
+    /// Passed, and didn't give up because of too many discards
+    pub fn is_success(&self) -> bool {
+        self.counterexamples.is_empty() && !self.gave_up()
+    }
+
+    pub fn gave_up(&self) -> bool {
+        self.counterexamples.is_empty() && self.discards > 0 && self.discards >= self.discard_limit
+    }
+
+    /// Shrink metrics added up over all of the counterexamples
+    pub fn shrink_metrics(&self) -> MetricsSummary {
+        let mut summary = MetricsSummary::default();
+        for cx in &self.counterexamples {
+            summary.add(&cx.metrics);
+        }
+        summary
+    }
+}
+
+impl<A : Debug> fmt::Display for Report<A> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        if self.gave_up() {
+            return write!(f, "gave up after {} tests and {} discards", self.tests, self.discards);
+        } else if self.is_success() {
+            if self.exhaustive {
+                write!(f, "passed, checked exhaustively, {} cases", self.tests)?;
+            } else if self.discards > 0 {
+                write!(f, "passed {} tests ({} discarded)", self.tests, self.discards)?;
+            } else {
+                write!(f, "passed {} tests", self.tests)?;
+            }
+            for (label, count) in &self.labels {
+                write!(f, "\n  {:3}% {}", count * 100 / self.tests.max(1), label)?;
+            }
+            return Ok(());
+        }
+
+        writeln!(f, "failed after {} tests with {} distinct counterexample(s)",
+            self.tests, self.counterexamples.len())?;
+        for cx in &self.counterexamples {
+            let truncated = if cx.truncated { ", shrinking truncated" } else { "" };
+            writeln!(f, "  {:?} (seed {}, case {}, size {}, shrunk {} times from {:?}{}; {})",
+                cx.minimal, cx.seed, cx.case, cx.size, cx.shrinks, cx.original, truncated, cx.metrics)?;
+        }
+        writeln!(f, "shrinking: {}", self.shrink_metrics())
+    }
+}
+
+/// Labels a test case, for Property::classify
+type Classifier<'a, A> = Rc<dyn Fn(&A) -> String + 'a>;
+
+/// Hash of a shrink candidate, for Property::dedup_shrinks
+type ShrinkKey<'a, A> = Rc<dyn Fn(&A) -> u64 + 'a>;
+
+/// Runs the predicate on several values at once, returning whether each one fails
+type Batch<'b, A> = dyn FnMut(Vec<A>) -> Vec<bool> + 'b;
+
+/// Starts the given number of threads to run the predicate on, and passes a shrink search a Batch
+/// that hands candidates out to them. The threads stop when the search returns.
+type WorkerPool<'a, A> = Rc<dyn Fn(usize, &mut dyn FnMut(&mut Batch<A>)) + 'a>;
+
+/// Distance from the original value to the minimal one, for Property::measure_distance
+type DistanceMeasure<'a, A> = Rc<dyn Fn(&A, &A) -> f64 + 'a>;
+
+/// A property is a generator along with a predicate that should hold for every generated value
+pub struct Property<'a, A> {
+    gen : Gen<'a, A>,
+    predicate : Rc<dyn Fn(&A) -> bool + 'a>,
+    classifier : Option<Classifier<'a, A>>,
+    shrink_key : Option<ShrinkKey<'a, A>>,
+    worker_pool : Option<WorkerPool<'a, A>>,
+    distance : Option<DistanceMeasure<'a, A>>,
+}
+
+impl<'a, A> Property<'a, A> {
+    pub fn new<F>(gen : Gen<'a, A>, predicate : F) -> Property<'a, A>
+    where F : Fn(&A) -> bool + 'a {
+        Property { gen, predicate : Rc::new(predicate), classifier : None, shrink_key : None, worker_pool : None, distance : None }
+    }
+
+    /// Property whose predicate can run on several threads at once, so that shrinking can check
+    /// Config::shrink_threads candidates in parallel. This is worth it for expensive predicates.
+    /// Shrinking still moves to the first failing candidate, so it finds the same counterexample
+    /// as it would on one thread.
+    /// The threads are started once for each shrink, and take candidates from a shared queue.
+    pub fn new_sync<F>(gen : Gen<'a, A>, predicate : F) -> Property<'a, A>
+    where F : Fn(&A) -> bool + Send + Sync + 'a,
+    A : Send {
+        let predicate = Arc::new(predicate);
+        let each = predicate.clone();
+        let pool = move |threads : usize, search : &mut dyn FnMut(&mut Batch<A>)| {
+            let predicate = &*predicate;
+            let (jobs, queue) = mpsc::channel::<(usize, A)>();
+            let queue = Mutex::new(queue);
+            let (done, results) = mpsc::channel();
+            thread::scope(|scope| {
+                for _ in 0..threads {
+                    let (queue, done) = (&queue, done.clone());
+                    scope.spawn(move || {
+                        // The queue closes when the search returns and jobs is dropped
+                        while let Ok((ix, a)) = queue.lock().unwrap_or_else(PoisonError::into_inner).recv() {
+                            let fails = panic::catch_unwind(panic::AssertUnwindSafe(|| !predicate(&a)));
+                            if done.send((ix, fails)).is_err() {
+                                break;
+                            }
+                        }
+                    });
+                }
+                let jobs = jobs;
+                search(&mut |candidates : Vec<A>| {
+                    let count = candidates.len();
+                    for job in candidates.into_iter().enumerate() {
+                        jobs.send(job).expect("the queue outlives the search");
+                    }
+                    let mut fails = vec![false; count];
+                    for _ in 0..count {
+                        let (ix, result) = results.recv().expect("every job gets a result");
+                        fails[ix] = result.unwrap_or_else(|err| panic::resume_unwind(err));
+                    }
+                    fails
+                });
+            })
+        };
+        Property { worker_pool : Some(Rc::new(pool)), ..Property::new(gen, move |a : &A| each(a)) }
+    }
+
+    /// Skip shrink candidates that have already been tried, so the predicate never runs twice on
+    /// the same value while shrinking. The same value is often reachable along several paths in
+    /// the shrink tree, especially for combined generators.
+    /// Values are compared by hash, so a collision can skip a value that wasn't really tried; this
+    /// only makes shrinking slightly less thorough.
+    pub fn dedup_shrinks(self) -> Property<'a, A>
+    where A : Hash {
+        Property { shrink_key : Some(Rc::new(|a : &A| hash_of(a))), ..self }
+    }
+
+    /// Like dedup_shrinks, but compares values by their Debug rendering, for types without Hash
+    pub fn dedup_shrinks_by_debug(self) -> Property<'a, A>
+    where A : Debug {
+        Property { shrink_key : Some(Rc::new(|a : &A| hash_of(format!("{:?}", a)))), ..self }
+    }
+
+    /// Record how far each counterexample shrank from its original value in the shrink metrics
+    pub fn measure_distance(self) -> Property<'a, A>
+    where A : Distance {
+        self.measure_distance_by(|a : &A, b : &A| a.distance(b))
+    }
+
+    /// Like measure_distance, with a custom measure for types without Distance
+    pub fn measure_distance_by<F>(self, distance : F) -> Property<'a, A>
+    where F : Fn(&A, &A) -> f64 + 'a {
+        Property { distance : Some(Rc::new(distance)), ..self }
+    }
+
+    /// Label each test case, so the report shows how the test cases were distributed, eg
+    /// > Property::new(Gen::variants(...), p).classify(|v| v.name.to_string())
+    pub fn classify<F>(self, classifier : F) -> Property<'a, A>
+    where F : Fn(&A) -> String + 'a {
+        Property { classifier : Some(Rc::new(classifier)), ..self }
+    }
+
+    fn label(&self, labels : &mut BTreeMap<String, usize>, value : &A) {
+        if let Some(classifier) = &self.classifier {
+            *labels.entry(classifier(value)).or_insert(0) += 1;
+        }
+    }
+
+    /// Property over a tuple of generators, with a predicate taking one argument per generator.
+    /// This saves writing a combine closure for simple properties:
+    /// > Property::for_all((Gen::u64(0..10), Gen::usize(0..5)), |a, b| *a as usize + *b < 15)
+    pub fn for_all<G, F>(gens : G, predicate : F) -> Property<'a, A>
+    where G : ForAll<'a, F, Value = A> {
+        gens.property(predicate)
+    }
+
+    /// Assert that the shrinker converges to a specific minimal value for a predicate that is known
+    /// to fail. This is for catching regressions in the shrinking of a generator:
+    /// > Property::check_shrink(Gen::u64(0..1000), |&x| x < 100, 100)
+    /// Panics if the property never fails, or if it shrinks to some other value. Always samples
+    /// randomly, since counterexamples found by exhaustive checking aren't shrunk.
+    pub fn check_shrink<F>(gen : Gen<'a, A>, predicate : F, expected_minimal : A)
+    where F : Fn(&A) -> bool + 'a,
+          A : Clone + Debug + PartialEq + 'a {
+        let report = Property::new(gen, predicate).check(&Config { exhaustive : false, ..Config::default() });
+        match report.counterexamples.first() {
+            None => panic!("check_shrink: expected the property to fail, but {}", report),
+            Some(cx) => assert_eq!(cx.minimal, expected_minimal,
+                "check_shrink: shrunk to the wrong value from {:?}", cx.original),
+        }
+    }
+
+    /// Run the property for the configured number of tests, shrinking any failures.
+    /// Counterexamples are deduplicated by their Debug rendering, since several failing test cases
+    /// often shrink to the same minimal value.
+    pub fn check(&self, config : &Config) -> Report<A>
+    where A : Clone + Debug + 'a {
+        if config.exhaustive {
+            if let Some(values) = (*self.gen.enumerate)(config.tests) {
+                return self.check_exhaustive(config, values.to_vec());
+            }
+        }
+
+        let seed = if config.entropy_seed {
+            let seed = Random::entropy_seed();
+            eprintln!("hedgehog: checking with seed {}", seed);
+            seed
+        } else {
+            config.seed
+        };
+
+        let mut seen = HashSet::new();
+        let mut counterexamples = Vec::new();
+        let mut labels = BTreeMap::new();
+        let mut tests = 0;
+        let mut discards = 0;
+
+        #[cfg(feature = "tree_cache")]
+        if let Some(cx) = self.resume_checkpoint(config) {
+            tests += 1;
+            seen.insert(format!("{:?}", cx.minimal));
+            counterexamples.push(cx);
+            if !config.continue_after_failure {
+                return Report { tests, discards, discard_limit : config.discard_limit, exhaustive : false, counterexamples, labels };
+            }
+        }
+
+        for i in 0usize.. {
+            if tests >= config.tests || discards >= config.discard_limit {
+                break;
+            }
+
+            let case = config.first_case + i as u64;
+            let size = config.size.size(case as usize, config.tests);
+            let tree = match catch_discard(|| (*self.gen.run)(Random::with_backend(config.rng, seed).stream(case), size)) {
+                Some(tree) => tree,
+                None => {
+                    discards += 1;
+                    continue;
+                }
+            };
+            tests += 1;
+            self.label(&mut labels, &tree.value);
+
+            if (self.predicate)(&tree.value) {
+                continue;
+            }
+
+            let cx = self.shrink(config, seed, case, size, tree.value.clone(), tree, TreePath::empty());
+            if seen.insert(format!("{:?}", cx.minimal)) {
+                counterexamples.push(cx);
+            }
+
+            if !config.continue_after_failure {
+                break;
+            }
+        }
+
+        Report { tests, discards, discard_limit : config.discard_limit, exhaustive : false, counterexamples, labels }
+    }
+
+    /// Check every value of an enumerated domain.
+    /// The values are in shrink order, so there is no point shrinking failures.
+    fn check_exhaustive(&self, config : &Config, values : Vec<A>) -> Report<A>
+    where A : Clone + Debug {
+        let mut seen = HashSet::new();
+        let mut counterexamples = Vec::new();
+        let mut labels = BTreeMap::new();
+        let mut tests = 0;
+        let size = config.size.size(0, config.tests);
+
+        for value in values {
+            tests += 1;
+            self.label(&mut labels, &value);
+
+            if (self.predicate)(&value) {
+                continue;
+            }
+
+            if seen.insert(format!("{:?}", value)) {
+                let metrics = ShrinkMetrics { distance : self.distance.as_ref().map(|_| 0.0), ..ShrinkMetrics::default() };
+                let cx = Counterexample { seed : config.seed, case : config.first_case, size, original : value.clone(), minimal : value, shrinks : 0, truncated : false, metrics };
+                counterexamples.push(cx);
+            }
+
+            if !config.continue_after_failure {
+                break;
+            }
+        }
+
+        // Only exhaustive if we didn't stop early
+        let exhaustive = counterexamples.is_empty() || config.continue_after_failure;
+        Report { tests, discards : 0, discard_limit : config.discard_limit, exhaustive, counterexamples, labels }
+    }
+
+    /// Carry on shrinking from the checkpoint in Config::shrink_checkpoint, if there is one and its
+    /// test case still fails
+    #[cfg(feature = "tree_cache")]
+    fn resume_checkpoint(&self, config : &Config) -> Option<Counterexample<A>>
+    where A : Clone + 'a {
+        let file = config.shrink_checkpoint.as_ref()?;
+        let ShrinkCheckpoint { seed, case, size, path } = ShrinkCheckpoint::load(file).ok()?;
+        let tree = catch_discard(|| (*self.gen.run)(Random::with_backend(config.rng, seed).stream(case), size))?;
+        // The generator might have changed since the checkpoint was saved, so the path might not fit
+        let (node, depth) = tree.get_path_prefix(&path);
+        if (self.predicate)(&node.value) {
+            let _ = std::fs::remove_file(file);
+            return None;
+        }
+        let start = TreePath::from(path.indices()[..depth].to_vec());
+        Some(self.shrink(config, seed, case, size, tree.value, node, start))
+    }
+
+    /// Search the shrink tree for a simpler failing value, with the configured strategy.
+    /// The tree is the subtree at start, which is where shrinking carries on from when resuming a
+    /// checkpoint, and otherwise the whole tree.
+    #[allow(clippy::too_many_arguments)]
+    fn shrink(&self, config : &Config, seed : u128, case : u64, size : usize, original : A, tree : Tree<'a, A>, start : TreePath) -> Counterexample<A>
+    where A : Clone + 'a {
+        #[cfg(feature = "tree_cache")]
+        if let Some(file) = &config.shrink_checkpoint {
+            // Saving is best effort, so a checkpoint that can't be written doesn't stop the check
+            let mut save = |(_, path) : &(A, TreePath)| {
+                let _ = ShrinkCheckpoint { seed, case, size, path : path.clone() }.save(file);
+            };
+            let ((minimal, _), shrinks, truncated, ran) = self.search(config, with_paths(tree, start.clone()), |(a, _)| a, &mut save);
+            let _ = std::fs::remove_file(file);
+            let shrinks = start.len() + shrinks;
+            let metrics = self.metrics(&original, &minimal, shrinks, ran);
+            return Counterexample { seed, case, size, original, minimal, shrinks, truncated, metrics };
+        }
+
+        let (minimal, shrinks, truncated, ran) = self.search(config, tree, |a| a, &mut |_| ());
+        let shrinks = start.len() + shrinks;
+        let metrics = self.metrics(&original, &minimal, shrinks, ran);
+        Counterexample { seed, case, size, original, minimal, shrinks, truncated, metrics }
+    }
+
+    /// Run the configured search over a shrink tree whose nodes hold the values to check, calling
+    /// on_fail with each candidate that fails.
+    /// Returns the smallest failing node, how many shrinks it took, whether the search was truncated,
+    /// and how many candidates were checked.
+    fn search<T, V>(&self, config : &Config, tree : Tree<'a, T>, value : V, on_fail : &mut dyn FnMut(&T)) -> (T, usize, bool, usize)
+    where T : Clone + 'a,
+          V : Fn(&T) -> &A,
+          A : Clone {
+        let mut tried = HashSet::new();
+        if let Some(key) = &self.shrink_key {
+            tried.insert(key(value(&tree.value)));
+        }
+        let mut ran = 0;
+
+        if let (Some(pool), ShrinkSearch::Greedy, true) = (&self.worker_pool, config.shrink, config.shrink_threads > 1) {
+            let mut tree = Some(tree);
+            let mut found = None;
+            pool(config.shrink_threads, &mut |batch| {
+                let mut first_failing = |candidates : &[T]| {
+                    // Skip candidates that were already tried, or appear earlier in the batch
+                    let keys : Vec<Option<u64>> = candidates.iter().map(|t| self.shrink_key.as_ref().map(|key| key(value(t)))).collect();
+                    let fresh : Vec<bool> = keys.iter().enumerate()
+                        .map(|(i, k)| k.is_none_or(|k| !tried.contains(&k) && !keys[..i].contains(&Some(k))))
+                        .collect();
+                    let run : Vec<A> = candidates.iter().zip(&fresh).filter(|(_, f)| **f).map(|(t, _)| value(t).clone()).collect();
+                    ran += run.len();
+                    let mut results = batch(run).into_iter();
+                    let fails : Vec<bool> = fresh.iter().map(|f| *f && results.next().unwrap_or(false)).collect();
+                    let first = fails.iter().position(|f| *f);
+                    // Only the candidates up to the failure would have been tried one at a time
+                    let tried_count = first.map_or(candidates.len(), |ix| ix + 1);
+                    tried.extend(keys[..tried_count].iter().flatten());
+                    if let Some(ix) = first {
+                        on_fail(&candidates[ix]);
+                    }
+                    first
+                };
+                let tree = tree.take().expect("the pool runs the search once");
+                found = Some(search_parallel(tree, config.shrink_limits, config.shrink_threads, &mut first_failing));
+            });
+            let (minimal, shrinks, truncated) = found.expect("the pool runs the search");
+            return (minimal, shrinks, truncated, ran);
+        }
+
+        let mut fails = |t : &T| {
+            let a = value(t);
+            if let Some(key) = &self.shrink_key {
+                if !tried.insert(key(a)) {
+                    return false;
+                }
+            }
+            ran += 1;
+            let failed = !(self.predicate)(a);
+            if failed {
+                on_fail(t);
+            }
+            failed
+        };
+        let (minimal, shrinks, truncated) = search_limited(&config.shrink, tree, config.shrink_limits, &mut fails);
+        (minimal, shrinks, truncated, ran)
+    }
+
+    fn metrics(&self, original : &A, minimal : &A, shrinks : usize, candidates : usize) -> ShrinkMetrics {
+        let distance = self.distance.as_ref().map(|d| d(original, minimal));
+        ShrinkMetrics { candidates, path_length : shrinks, distance }
+    }
+}
+
+fn hash_of<K : Hash>(key : K) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Run a generator, returning None if it discarded the test case.
+/// Any other panics are passed through.
+pub(crate) fn catch_discard<T, F : FnOnce() -> T>(f : F) -> Option<T> {
+    match panic::catch_unwind(panic::AssertUnwindSafe(f)) {
+        Ok(value) => Some(value),
+        Err(payload) if payload.is::<Discard>() => None,
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+/// Tuples of generators that can be checked with Property::for_all, up to twelve elements.
+/// F is the predicate, which takes a reference to each generated value as a separate argument.
+pub trait ForAll<'a, F> {
+    type Value;
+
+    fn property(self, predicate : F) -> Property<'a, Self::Value>;
+}
+
+macro_rules! impl_for_all {
+    ($($val:ident : $ty:ident),*) => {
+        impl<'a, P, $($ty),*> ForAll<'a, P> for ($(Gen<'a, $ty>,)*)
+        where P : Fn($(&$ty),*) -> bool + 'a,
+              $($ty : Clone + 'a),* {
+            type Value = ($($ty,)*);
+
+            fn property(self, predicate : P) -> Property<'a, Self::Value> {
+                Property::new(self.gen(), move |($($val,)*)| predicate($($val),*))
+            }
+        }
+    }
+}
+
+impl_for_all!(a : A);
+impl_for_all!(a : A, b : B);
+impl_for_all!(a : A, b : B, c : C);
+impl_for_all!(a : A, b : B, c : C, d : D);
+impl_for_all!(a : A, b : B, c : C, d : D, e : E);
+impl_for_all!(a : A, b : B, c : C, d : D, e : E, f : F);
+impl_for_all!(a : A, b : B, c : C, d : D, e : E, f : F, g : G);
+impl_for_all!(a : A, b : B, c : C, d : D, e : E, f : F, g : G, h : H);
+impl_for_all!(a : A, b : B, c : C, d : D, e : E, f : F, g : G, h : H, i : I);
+impl_for_all!(a : A, b : B, c : C, d : D, e : E, f : F, g : G, h : H, i : I, j : J);
+impl_for_all!(a : A, b : B, c : C, d : D, e : E, f : F, g : G, h : H, i : I, j : J, k : K);
+impl_for_all!(a : A, b : B, c : C, d : D, e : E, f : F, g : G, h : H, i : I, j : J, k : K, l : L);
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::Gen;
+    use crate::property::*;
+
+    #[test]
+    fn shrinks_to_boundary() {
+        let prop = Property::new(Gen::u64(0..1000), |&x| x < 100);
+        let report = prop.check(&Config::default());
+        assert!(!report.is_success());
+        assert_eq!(report.counterexamples[0].minimal, 100);
+    }
+
+    #[test]
+    fn continue_after_failure_collects_distinct() {
+        // Two unrelated bugs: one in the hundreds and one in the nine-hundreds
+        let prop = Property::new(Gen::u64(0..1000), |&x| x < 900 && !(100..200).contains(&x));
+        let config = Config { continue_after_failure : true, ..Config::default() };
+        let report = prop.check(&config);
+
+        assert_eq!(report.tests, config.tests);
+        let mut minimals : Vec<u64> = report.counterexamples.iter().map(|cx| cx.minimal).collect();
+        minimals.sort();
+        assert_eq!(minimals, vec![100, 900]);
+    }
+
+    #[test]
+    fn shrink_golden_u64() {
+        Property::check_shrink(Gen::u64(0..1000), |&x| x < 100, 100);
+        Property::check_shrink(Gen::u64(50..1000), |&x| x < 60, 60);
+    }
+
+    #[test]
+    fn shrink_golden_vec() {
+        Property::check_shrink(Gen::u64(0..100).vec(Gen::usize(0..20)), |v| v.len() < 3, vec![0, 0, 0]);
+        Property::check_shrink(Gen::u64(0..100).vec(Gen::usize(0..20)), |v| v.iter().all(|&x| x < 10), vec![10]);
+        Property::check_shrink(Gen::u64(0..100).vec(Gen::usize(2..20)), |v| v.iter().all(|&x| x < 10), vec![10, 0]);
+    }
+
+    #[test]
+    fn dedup_shrinks() {
+        // Record every value the predicate sees
+        let run = |dedup : bool| {
+            let seen = Rc::new(std::cell::RefCell::new(Vec::new()));
+            let record = seen.clone();
+            let gen = Gen::u64(0..100).zip(Gen::u64(0..100));
+            let prop = Property::new(gen, move |&(a, b)| {
+                record.borrow_mut().push((a, b));
+                a + b < 50
+            });
+            let prop = if dedup { prop.dedup_shrinks() } else { prop };
+            let minimal = prop.check(&Config::default()).counterexamples[0].minimal;
+            let seen = seen.borrow().clone();
+            (minimal, seen)
+        };
+        let (minimal, all) = run(false);
+        let (minimal_dedup, deduped) = run(true);
+        assert_eq!(minimal, minimal_dedup);
+        assert!(deduped.len() < all.len());
+
+        // Only the generation phase can see a value twice, and it stops at the first failure
+        let mut unique = deduped.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), deduped.len());
+    }
+
+    #[test]
+    #[should_panic]
+    fn shrink_golden_wrong() {
+        Property::check_shrink(Gen::u64(0..1000), |&x| x < 100, 101);
+    }
+
+    #[test]
+    fn exhaustive_small_domain() {
+        let prop = Property::for_all((Gen::u64(0..5), Gen::choose(vec![false, true])), |_, _| true);
+        let report = prop.check(&Config::default());
+        assert!(report.exhaustive);
+        assert_eq!(report.tests, 10);
+        assert_eq!(format!("{}", report), "passed, checked exhaustively, 10 cases");
+
+        let prop = Property::new(Gen::u64(0..5).vec(Gen::usize(0..3)), |v| v.iter().sum::<u64>() < 7);
+        let config = Config { continue_after_failure : true, ..Config::default() };
+        let report = prop.check(&config);
+        assert!(report.exhaustive);
+        assert_eq!(report.tests, 1 + 5 + 25);
+        assert_eq!(report.counterexamples.len(), 3);
+        assert_eq!(report.counterexamples[0].minimal, vec![3, 4]);
+        let shown = format!("{}", report);
+        assert!(shown.contains(&format!("; {})\n", report.counterexamples[0].metrics)));
+        assert!(shown.ends_with(&format!("shrinking: {}\n", report.shrink_metrics())));
+
+        let report = Property::new(Gen::u64(0..1000), |_| true).check(&Config::default());
+        assert!(!report.exhaustive);
+    }
+
+    #[test]
+    fn discards() {
+        let gen = Gen::u64(0..1000).filter(|x| x % 2 == 0);
+        let report = Property::new(gen, |x| x % 2 == 0).check(&Config::default());
+        assert!(report.is_success());
+        assert_eq!(report.discards, 0);
+
+        let gen = Gen::u64(0..1000).filter(|_| false);
+        let report = Property::new(gen, |_| true).check(&Config::default());
+        assert!(report.gave_up());
+        assert_eq!(format!("{}", report), "gave up after 0 tests and 100 discards");
+    }
+
+    #[test]
+    fn size_schedules() {
+        assert_eq!(SizeSchedule::Constant(7).size(3, 10), 7);
+        assert_eq!(SizeSchedule::Linear { max : 100 }.size(0, 11), 0);
+        assert_eq!(SizeSchedule::Linear { max : 100 }.size(5, 11), 50);
+        assert_eq!(SizeSchedule::Linear { max : 100 }.size(10, 11), 100);
+        assert_eq!(SizeSchedule::Linear { max : 100 }.size(20, 11), 100);
+        assert_eq!(SizeSchedule::Exponential { max : 100 }.size(0, 11), 0);
+        assert!(SizeSchedule::Exponential { max : 100 }.size(5, 11) < 50);
+        assert_eq!(SizeSchedule::Exponential { max : 100 }.size(10, 11), 100);
+        assert_eq!(SizeSchedule::Custom(Rc::new(|i| i * 2)).size(4, 10), 8);
+    }
+
+    #[test]
+    fn rng_backends() {
+        for rng in [RngBackend::Pcg, RngBackend::ChaCha8, RngBackend::SplitMix64] {
+            let config = Config { rng, ..Config::default() };
+            let report = Property::new(Gen::u64(0..1000).vec(Gen::usize(0..10)), |v| v.iter().all(|&x| x < 500)).check(&config);
+            assert_eq!(report.counterexamples[0].minimal, vec![500], "{:?}", rng);
+        }
+    }
+
+    #[test]
+    fn entropy_seed_reproduces() {
+        let prop = Property::new(Gen::u64(0..1000), |&x| x < 900);
+        let config = Config { entropy_seed : true, size : SizeSchedule::Constant(10), ..Config::default() };
+        let report = prop.check(&config);
+        let cx = &report.counterexamples[0];
+        // Rerunning from the counterexample's seed and case finds it first
+        let again = prop.check(&Config { seed : cx.seed, first_case : cx.case, entropy_seed : false, ..config });
+        assert_eq!(again.counterexamples[0].original, cx.original);
+        assert_eq!(again.tests, 1);
+
+        // The rerun gets the same size as the original, with the default size schedule
+        let prop = Property::new(Gen::sized(Gen::constant), |&s| s < 40);
+        let cx = prop.check(&Config::default()).counterexamples[0].clone();
+        assert_eq!((cx.case, cx.size), (40, 40));
+        let again = prop.check(&Config { first_case : cx.case, ..Config::default() });
+        assert_eq!((again.tests, again.counterexamples[0].size), (1, 40));
+    }
+
+    #[test]
+    fn for_all_tuple() {
+        let prop = Property::for_all(
+            (Gen::u64(0..100), Gen::u64(0..100), Gen::usize(0..10)),
+            |a, b, c| a + b < 150 || *c > 100);
+        let report = prop.check(&Config::default());
+        let (a, b, c) = report.counterexamples[0].minimal;
+        assert_eq!((a + b, c), (150, 0));
+    }
+}
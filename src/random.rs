@@ -1,24 +1,425 @@
+use std::collections::hash_map;
+use std::convert::TryFrom;
+use std::fmt::{self, Debug};
+use std::fs::File;
+use std::hash::{BuildHasher, Hasher};
+use std::io::Read;
 use std::ops::Range;
+use std::time::SystemTime;
 
 use oorandom::Rand64;
 
+pub mod chacha;
+pub mod pcg;
+pub mod splitmix;
+
+use chacha::ChaCha8;
+use pcg::Pcg64;
+use splitmix::SplitMix64;
+
+/// The random number generators that Random can use. Random picks one by RngBackend rather than
+/// being generic, so that it stays Copy and every generator can take any backend.
+pub(crate) trait RandomSource : Copy + Debug + PartialEq {
+    fn from_seed(seed : u128) -> Self;
+
+    /// Uniform value over all of u64
+    fn next_u64(&mut self) -> u64;
+
+    /// Fill the buffer with uniform bytes, eight at a time, in little endian order
+    fn fill_bytes(&mut self, buf : &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    /// Uniform value in a non-empty range, by rejection sampling so that it isn't biased
+    fn u64_range(&mut self, range : Range<u64>) -> u64 {
+        let width = range.end - range.start;
+        // Reject values in the last, partial, multiple of width
+        let zone = u64::MAX - (u64::MAX - width + 1) % width;
+        loop {
+            let x = self.next_u64();
+            if x <= zone {
+                return range.start + x % width;
+            }
+        }
+    }
+
+    /// Skip the next delta outputs, in much less than delta steps
+    fn advance(&mut self, delta : u128);
+
+    /// Generator for the given key, without changing self
+    fn fork(&self, key : u64) -> Self;
+
+    /// Split off a new generator, changing self so that the next split is different
+    fn split(&mut self) -> Self;
+}
+
+/// FNV-1a, for hashing values into seeds. Unlike DefaultHasher, the hash of a value is the same in
+/// every version of Rust and on every platform, so the seeds it gives reproduce anywhere.
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes : &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    // Integers are hashed in native byte order by default, so fix the order and the width of usize
+    fn write_u16(&mut self, i : u16) { self.write(&i.to_le_bytes()) }
+    fn write_u32(&mut self, i : u32) { self.write(&i.to_le_bytes()) }
+    fn write_u64(&mut self, i : u64) { self.write(&i.to_le_bytes()) }
+    fn write_u128(&mut self, i : u128) { self.write(&i.to_le_bytes()) }
+    fn write_usize(&mut self, i : usize) { self.write_u64(i as u64) }
+    fn write_i16(&mut self, i : i16) { self.write_u16(i as u16) }
+    fn write_i32(&mut self, i : i32) { self.write_u32(i as u32) }
+    fn write_i64(&mut self, i : i64) { self.write_u64(i as u64) }
+    fn write_i128(&mut self, i : i128) { self.write_u128(i as u128) }
+    fn write_isize(&mut self, i : isize) { self.write_u64(i as u64) }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Number of outputs in each of the sub-sequences given by Random::stream
+pub const STREAM_LENGTH : u128 = 1 << 40;
+
+/// Which random number generator to use, eg for Config::rng
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RngBackend {
+    /// PCG, the same algorithm as oorandom's Rand64: fast, with split generators on separate
+    /// streams. Its output for a given seed is fixed, on every platform and in every version.
+    #[default]
+    Pcg,
+    /// ChaCha with eight rounds: slower, with independent split generators
+    ChaCha8,
+    /// SplitMix64: fast, and designed for splitting
+    SplitMix64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Source {
+    Pcg(Pcg64),
+    ChaCha8(ChaCha8),
+    SplitMix64(SplitMix64),
+}
+
+/// Call a RandomSource method on whichever generator is in a Source
+macro_rules! dispatch {
+    ($source:expr, $r:ident => $e:expr) => {
+        match $source {
+            Source::Pcg($r) => $e,
+            Source::ChaCha8($r) => $e,
+            Source::SplitMix64($r) => $e,
+        }
+    }
+}
+
+/// Exact position of a Random, including which generator it uses, so that it can be saved and
+/// carry on from the same place later. With the serde feature, this can be serialized, and
+/// deserializing checks that the state is one the generator could have been in.
+/// > let state = r.state();
+/// > assert_eq!(Random::from_state(state)?.u64(), r.u64());
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "UncheckedRngState"))]
+pub enum RngState {
+    Pcg { state : u128, inc : u128 },
+    ChaCha8(ChaCha8),
+    SplitMix64(SplitMix64),
+}
+
+impl RngState {
+    /// Reject states that no generator reaches: an even PCG increment, which would shorten the
+    /// period, an even SplitMix64 gamma likewise, or a position past the end of a ChaCha8 block
+    pub fn check(&self) -> Result<(), InvalidRngState> {
+        match self {
+            RngState::Pcg { inc, .. } if inc & 1 == 0 => Err(InvalidRngState("PCG increment must be odd")),
+            RngState::ChaCha8(r) if !r.is_valid() => Err(InvalidRngState("ChaCha8 position is outside its block")),
+            RngState::SplitMix64(r) if !r.is_valid() => Err(InvalidRngState("SplitMix64 gamma must be odd")),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The same shape as RngState, for deserializing before the state has been checked
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+enum UncheckedRngState {
+    Pcg { state : u128, inc : u128 },
+    ChaCha8(ChaCha8),
+    SplitMix64(SplitMix64),
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<UncheckedRngState> for RngState {
+    type Error = InvalidRngState;
+
+    fn try_from(unchecked : UncheckedRngState) -> Result<RngState, InvalidRngState> {
+        let state = match unchecked {
+            UncheckedRngState::Pcg { state, inc } => RngState::Pcg { state, inc },
+            UncheckedRngState::ChaCha8(r) => RngState::ChaCha8(r),
+            UncheckedRngState::SplitMix64(r) => RngState::SplitMix64(r),
+        };
+        state.check()?;
+        Ok(state)
+    }
+}
+
+/// Error from restoring a Random from an RngState that no generator could have been in
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidRngState(&'static str);
+
+impl fmt::Display for InvalidRngState {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid random state: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidRngState {}
+
+/// Same as dispatch!, but wraps a generator result back up in the same kind of Source
+macro_rules! dispatch_wrap {
+    ($source:expr, $r:ident => $e:expr) => {
+        match $source {
+            Source::Pcg($r) => Source::Pcg($e),
+            Source::ChaCha8($r) => Source::ChaCha8($e),
+            Source::SplitMix64($r) => Source::SplitMix64($e),
+        }
+    }
+}
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Random {
-    rand : Rand64
+    source : Source
 }
 
+impl TryFrom<RngState> for Random {
+    type Error = InvalidRngState;
+
+    fn try_from(state : RngState) -> Result<Random, InvalidRngState> {
+        state.check()?;
+        let source = match state {
+            RngState::Pcg { state, inc } => Source::Pcg(Pcg64::from_state((state, inc))),
+            RngState::ChaCha8(r) => Source::ChaCha8(r),
+            RngState::SplitMix64(r) => Source::SplitMix64(r),
+        };
+        Ok(Random { source })
+    }
+}
 impl Random {
+    /// Default generator carrying on from where an oorandom generator is
     pub fn new(rand : Rand64) -> Random {
-        Random { rand }
+        Random { source : Source::Pcg(Pcg64::from_state(rand.state())) }
     }
 
     pub fn new_from_seed(seed : u128) -> Random {
-        Random::new(Rand64::new(seed))
+        Random::with_backend(RngBackend::Pcg, seed)
+    }
+
+    pub fn with_backend(backend : RngBackend, seed : u128) -> Random {
+        let source = match backend {
+            RngBackend::Pcg => Source::Pcg(Pcg64::from_seed(seed)),
+            RngBackend::ChaCha8 => Source::ChaCha8(ChaCha8::from_seed(seed)),
+            RngBackend::SplitMix64 => Source::SplitMix64(SplitMix64::from_seed(seed)),
+        };
+        Random { source }
+    }
+
+    /// Default generator seeded from the operating system, so each run sees different values.
+    /// Use entropy_seed instead if the seed needs to be reported for reproducing the run.
+    pub fn from_entropy() -> Random {
+        Random::new_from_seed(Random::entropy_seed())
+    }
+
+    /// Fresh seed from the operating system's random number generator.
+    /// Where there's no /dev/urandom, falls back to the randomly keyed hasher from std mixed with
+    /// the time.
+    pub fn entropy_seed() -> u128 {
+        let mut buf = [0u8; 16];
+        if File::open("/dev/urandom").and_then(|mut f| f.read_exact(&mut buf)).is_ok() {
+            return u128::from_le_bytes(buf);
+        }
+        let nanos = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+        let hash = |key : u64| {
+            let mut h = hash_map::RandomState::new().build_hasher();
+            h.write_u128(nanos);
+            h.write_u64(key);
+            h.finish() as u128
+        };
+        hash(0) << 64 | hash(1)
+    }
+
+    /// Current position of the generator
+    pub fn state(&self) -> RngState {
+        match self.source {
+            Source::Pcg(r) => {
+                let (state, inc) = r.state();
+                RngState::Pcg { state, inc }
+            }
+            Source::ChaCha8(r) => RngState::ChaCha8(r),
+            Source::SplitMix64(r) => RngState::SplitMix64(r),
+        }
+    }
+
+    /// Generator that carries on from a saved position, if it's one the generator could be in
+    pub fn from_state(state : RngState) -> Result<Random, InvalidRngState> {
+        Random::try_from(state)
+    }
+
+    /// Skip the next delta u64 outputs, without generating them
+    pub fn advance(&mut self, delta : u128) {
+        dispatch!(&mut self.source, r => r.advance(delta))
+    }
+
+    /// Generator for the nth block of STREAM_LENGTH outputs after self.
+    /// Streams with different n don't overlap as long as each draws fewer than STREAM_LENGTH
+    /// values directly, which holds for any n below 2^24 whichever backend is used. Unlike split,
+    /// which hashes its way to an unrelated generator, this only moves along the same sequence, so
+    /// the streams are known to be disjoint. Generators split off from a stream aren't covered.
+    /// Property::check draws test case i from stream i of the seed's generator.
+    /// > let case_rand = base.stream(i as u64);
+    pub fn stream(&self, n : u64) -> Random {
+        let mut r = *self;
+        r.advance(STREAM_LENGTH * n as u128);
+        r
+    }
+
+    pub fn backend(&self) -> RngBackend {
+        match self.source {
+            Source::Pcg(_) => RngBackend::Pcg,
+            Source::ChaCha8(_) => RngBackend::ChaCha8,
+            Source::SplitMix64(_) => RngBackend::SplitMix64,
+        }
+    }
+
+    /// Uniform value over all of u64
+    pub fn u64(&mut self) -> u64 {
+        dispatch!(&mut self.source, r => r.next_u64())
     }
 
     pub fn u64_range(&mut self, range : Range<u64>) -> u64 {
-        self.rand.rand_range(range)
+        dispatch!(&mut self.source, r => r.u64_range(range))
+    }
+
+    /// Fill the buffer with uniform bytes, which is faster than generating them one at a time
+    pub fn fill_bytes(&mut self, buf : &mut [u8]) {
+        dispatch!(&mut self.source, r => r.fill_bytes(buf))
+    }
+
+    /// Uniform value over all of u128
+    pub fn u128(&mut self) -> u128 {
+        ((self.u64() as u128) << 64) | self.u64() as u128
+    }
+
+    /// Uniform value in a u128 range. Ranges wider than u64 are built from two u64s, with rejection
+    /// sampling to avoid bias.
+    pub fn u128_range(&mut self, range : Range<u128>) -> u128 {
+        let width = range.end - range.start;
+        if width <= u64::MAX as u128 {
+            return range.start + self.u64_range(0..width as u64) as u128;
+        }
+        // Reject values in the last, partial, multiple of width
+        let zone = u128::MAX - (u128::MAX - width + 1) % width;
+        loop {
+            let x = self.u128();
+            if x <= zone {
+                return range.start + x % width;
+            }
+        }
+    }
+
+    /// Uniform value in a non-empty i64 range, which can have negative bounds and be wider than
+    /// i64::MAX
+    pub fn i64_range(&mut self, range : Range<i64>) -> i64 {
+        assert!(range.start < range.end, "Random::i64_range: empty range {:?}", range);
+        let width = (range.end as i128 - range.start as i128) as u64;
+        range.start.wrapping_add(self.u64_range(0..width) as i64)
+    }
+
+    /// True half of the time
+    pub fn bool(&mut self) -> bool {
+        self.ratio(1, 2)
+    }
+
+    /// True with probability num / den. Panics if den is zero.
+    pub fn ratio(&mut self, num : u64, den : u64) -> bool {
+        assert!(den > 0, "Random::ratio: zero denominator");
+        self.u64_range(0..den) < num
+    }
+
+    /// Uniform float in [0, 1), with 53 random bits
+    pub fn f64_unit(&mut self) -> f64 {
+        (self.u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform float in a non-empty range, excluding the end
+    pub fn f64_range(&mut self, range : Range<f64>) -> f64 {
+        assert!(range.start < range.end, "Random::f64_range: empty range {:?}", range);
+        loop {
+            // Interpolated rather than start + u * width, so that the width can't overflow.
+            // Rounding can land on the end of the range, so try again if it does.
+            let u = self.f64_unit();
+            let x = range.start * (1.0 - u) + range.end * u;
+            if x < range.end {
+                return x;
+            }
+        }
+    }
+
+    /// Normally distributed float, by the Box-Muller transform
+    pub fn normal(&mut self, mean : f64, stddev : f64) -> f64 {
+        // 1 - unit is in (0, 1], so the log is finite
+        let radius = (-2.0 * (1.0 - self.f64_unit()).ln()).sqrt();
+        let angle = 2.0 * std::f64::consts::PI * self.f64_unit();
+        mean + stddev * radius * angle.cos()
+    }
+
+    /// Exponentially distributed float with rate lambda, so the mean is 1 / lambda
+    pub fn exponential(&mut self, lambda : f64) -> f64 {
+        -(1.0 - self.f64_unit()).ln() / lambda
+    }
+
+    /// Index into weights, picking each index in proportion to its weight.
+    /// This scans the cumulative weights, so use an AliasTable to pick from the same weights many
+    /// times. Panics if the weights are all zero.
+    pub fn weighted_index(&mut self, weights : &[u64]) -> usize {
+        let total : u128 = weights.iter().map(|&w| w as u128).sum();
+        assert!(total > 0, "Random::weighted_index: weights are all zero");
+        let mut x = self.u128_range(0..total);
+        let mut ix = 0;
+        while x >= weights[ix] as u128 {
+            x -= weights[ix] as u128;
+            ix += 1;
+        }
+        ix
+    }
+
+    /// Index picked in proportion to the weights of an alias table, in constant time
+    pub fn alias_index(&mut self, table : &AliasTable) -> usize {
+        let ix = self.u64_range(0..table.alias.len() as u64) as usize;
+        if self.u128_range(0..table.total) < table.keep[ix] {
+            ix
+        } else {
+            table.alias[ix]
+        }
+    }
+
+    /// Generator for the given key, without changing self. The same key always gives the same
+    /// generator, however many others have been split off before it.
+    /// The generator uses the same backend as self.
+    pub fn fork(&self, key : u64) -> Random {
+        Random { source : dispatch_wrap!(&self.source, r => r.fork(key)) }
     }
 
     /// Split generator in two. The returned generator will have a different seed than the updated self.
@@ -27,18 +428,318 @@ impl Random {
     /// > let mut child1 = r1.split();
     /// > let mut child2 = r1.split();
     /// > assert!(r1.rand != child1.rand != child2.rand);
-    /// 
-    /// This implementation is probably not statistically good.
     pub fn split(&mut self) -> Random {
-        // get current state.
-        let (state, inc) = self.rand.state();
-        // generate amount to perturb child state - this also modifies current state so that
-        // consecutive splits have different seeds.
-        let growth = self.rand.rand_u64() as u128;
-        // create child with perturbed state and increment.
-        // increment needs to be an odd number, so add an even amount.
-        let rand = Rand64::from_state((state + growth, inc + 2));
-        Self::new(rand)
+        Random { source : dispatch_wrap!(&mut self.source, r => r.split()) }
     }
 }
 
+/// Weights prepared for picking an index in constant time, with Vose's alias method.
+/// Each index gets an equal share of the total weight, made up of some of its own weight and the
+/// rest from one other index. The arithmetic is in integers, so the probabilities are exact.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AliasTable {
+    total : u128,
+    /// Out of total, how much of each index's share is its own weight
+    keep : Vec<u128>,
+    /// Which index makes up the rest of each share
+    alias : Vec<usize>,
+}
+
+impl AliasTable {
+    /// Panics if the weights are all zero
+    pub fn new(weights : &[u64]) -> AliasTable {
+        let total : u128 = weights.iter().map(|&w| w as u128).sum();
+        assert!(total > 0, "AliasTable::new: weights are all zero");
+        let n = weights.len() as u128;
+        // Scaled by n, so each share is exactly total
+        let mut keep : Vec<u128> = weights.iter().map(|&w| w as u128 * n).collect();
+        let mut alias : Vec<usize> = (0..weights.len()).collect();
+        let (mut small, mut large) : (Vec<usize>, Vec<usize>) = (0..weights.len()).partition(|&ix| keep[ix] < total);
+        while let (Some(&s), Some(&l)) = (small.last(), large.last()) {
+            small.pop();
+            alias[s] = l;
+            keep[l] -= total - keep[s];
+            if keep[l] < total {
+                large.pop();
+                small.push(l);
+            }
+        }
+        // Whatever is left has exactly a full share
+        for ix in small.into_iter().chain(large) {
+            keep[ix] = total;
+        }
+        AliasTable { total, keep, alias }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::random::*;
+
+    const BACKENDS : [RngBackend; 3] = [RngBackend::Pcg, RngBackend::ChaCha8, RngBackend::SplitMix64];
+
+    #[test]
+    fn backends_are_deterministic() {
+        for backend in BACKENDS {
+            let sample = |seed| {
+                let mut r = Random::with_backend(backend, seed);
+                let mut child = r.split();
+                let forked = r.fork(7);
+                assert_eq!(forked.backend(), backend);
+                (r.u64(), child.u64_range(10..20), forked)
+            };
+            assert_eq!(sample(1), sample(1));
+            assert_ne!(sample(1), sample(2));
+            let (_, x, _) = sample(3);
+            assert!((10..20).contains(&x));
+        }
+    }
+
+    #[test]
+    fn forks_depend_on_position() {
+        for backend in BACKENDS {
+            let mut r = Random::with_backend(backend, 5);
+            let mut before = r.fork(1);
+            for _ in 0..8 {
+                r.u64();
+            }
+            let mut after = r.fork(1);
+            assert_ne!(before.u64(), after.u64());
+        }
+    }
+
+    /// Pearson's chi-squared statistic for counts that should be uniform
+    fn chi_squared(counts : &[u64]) -> f64 {
+        let expected = counts.iter().sum::<u64>() as f64 / counts.len() as f64;
+        counts.iter().map(|&c| (c as f64 - expected).powi(2) / expected).sum()
+    }
+
+    #[test]
+    fn split_children_are_independent() {
+        for backend in BACKENDS {
+            let mut parent = Random::with_backend(backend, 0);
+            let firsts : Vec<u64> = (0..10_000).map(|_| parent.split().u64_range(0..10)).collect();
+
+            // First value of each child, 9 degrees of freedom: 27.9 is the 0.1% critical value
+            let mut buckets = [0; 10];
+            for &x in &firsts {
+                buckets[x as usize] += 1;
+            }
+            assert!(chi_squared(&buckets) < 27.9, "{:?} {:?}", backend, buckets);
+
+            // Pairs of consecutive siblings, 99 degrees of freedom: 148.2 is the 0.1% critical value
+            let mut pairs = [0; 100];
+            for w in firsts.windows(2) {
+                pairs[(w[0] * 10 + w[1]) as usize] += 1;
+            }
+            assert!(chi_squared(&pairs) < 148.2, "{:?} {:?}", backend, pairs);
+
+            // A child against the value its parent gives next
+            let mut pairs = [0; 100];
+            for _ in 0..10_000 {
+                let x = parent.split().u64_range(0..10);
+                let y = parent.u64_range(0..10);
+                pairs[(x * 10 + y) as usize] += 1;
+            }
+            assert!(chi_squared(&pairs) < 148.2, "{:?} {:?}", backend, pairs);
+        }
+    }
+
+    #[test]
+    fn floats() {
+        let mut r = Random::new_from_seed(5);
+        let n = 10_000;
+        let mean = |xs : &[f64]| xs.iter().sum::<f64>() / xs.len() as f64;
+
+        let units : Vec<f64> = (0..n).map(|_| r.f64_unit()).collect();
+        assert!(units.iter().all(|x| (0.0..1.0).contains(x)));
+        assert!((mean(&units) - 0.5).abs() < 0.02);
+
+        let ranged : Vec<f64> = (0..n).map(|_| r.f64_range(-3.0..-1.0)).collect();
+        assert!(ranged.iter().all(|x| (-3.0..-1.0).contains(x)));
+        assert!(r.f64_range(f64::MIN..f64::MAX).is_finite());
+
+        let normals : Vec<f64> = (0..n).map(|_| r.normal(10.0, 2.0)).collect();
+        let m = mean(&normals);
+        let var = normals.iter().map(|x| (x - m).powi(2)).sum::<f64>() / n as f64;
+        assert!((m - 10.0).abs() < 0.1 && (var.sqrt() - 2.0).abs() < 0.1, "{} {}", m, var);
+
+        let exps : Vec<f64> = (0..n).map(|_| r.exponential(4.0)).collect();
+        assert!(exps.iter().all(|x| *x >= 0.0 && x.is_finite()));
+        assert!((mean(&exps) - 0.25).abs() < 0.02);
+    }
+
+    #[test]
+    fn weighted_indices() {
+        let weights = [1, 0, 3, 6];
+        let table = AliasTable::new(&weights);
+        let mut r = Random::new_from_seed(9);
+        let mut scanned = [0; 4];
+        let mut aliased = [0; 4];
+        for _ in 0..10_000 {
+            scanned[r.weighted_index(&weights)] += 1;
+            aliased[r.alias_index(&table)] += 1;
+        }
+        for counts in [scanned, aliased] {
+            assert_eq!(counts[1], 0);
+            for (count, weight) in counts.iter().zip(weights) {
+                assert!((*count as i64 - weight as i64 * 1000).abs() < 200, "{:?}", counts);
+            }
+        }
+        assert_eq!(Random::new_from_seed(0).weighted_index(&[0, 5]), 1);
+        assert_eq!(Random::new_from_seed(0).alias_index(&AliasTable::new(&[0, 0, 5])), 2);
+    }
+
+    #[test]
+    fn signed_and_bool() {
+        let mut r = Random::new_from_seed(2);
+        for _ in 0..1000 {
+            assert!((-5..-2).contains(&r.i64_range(-5..-2)));
+        }
+        let wide : Vec<i64> = (0..1000).map(|_| r.i64_range(i64::MIN..i64::MAX)).collect();
+        assert!(wide.iter().any(|x| *x < 0) && wide.iter().any(|x| *x > 0));
+        assert_eq!(r.i64_range(i64::MAX - 1..i64::MAX), i64::MAX - 1);
+
+        let trues = (0..1000).filter(|_| r.bool()).count();
+        assert!((400..600).contains(&trues));
+        assert!(!r.ratio(0, 3) && r.ratio(3, 3));
+    }
+
+    #[test]
+    fn resumes_from_state() {
+        for backend in BACKENDS {
+            let mut r = Random::with_backend(backend, 11);
+            // Part way through a ChaCha block, so the saved state has to include the block
+            r.u64();
+            let mut resumed = Random::from_state(r.state()).unwrap();
+            assert_eq!(resumed, r);
+            assert_eq!((resumed.u64(), resumed.split().u64()), (r.u64(), r.split().u64()));
+        }
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    #[test]
+    fn serializes_state() {
+        for backend in BACKENDS {
+            let mut r = Random::with_backend(backend, u128::MAX - 5);
+            r.u64();
+            let json = serde_json::to_string(&r.state()).unwrap();
+            let mut resumed = Random::from_state(serde_json::from_str(&json).unwrap()).unwrap();
+            assert_eq!(resumed.u64(), r.u64(), "{}", json);
+        }
+
+        let invalid = [
+            r#"{"Pcg":{"state":1,"inc":2}}"#.to_string(),
+            r#"{"SplitMix64":{"seed":1,"gamma":2}}"#.to_string(),
+            serde_json::to_string(&Random::with_backend(RngBackend::ChaCha8, 1).state()).unwrap().replace(r#""used":8"#, r#""used":9"#),
+        ];
+        for json in &invalid {
+            let err = serde_json::from_str::<RngState>(json).unwrap_err();
+            assert!(err.to_string().contains("invalid random state"), "{}", err);
+        }
+        let even = RngState::Pcg { state : 1, inc : 2 };
+        assert_eq!(Random::from_state(even), Err(InvalidRngState("PCG increment must be odd")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Random::i64_range: empty range 3..3")]
+    fn i64_range_empty() {
+        Random::new_from_seed(0).i64_range(3..3);
+    }
+
+    #[test]
+    fn fnv_is_fixed() {
+        use std::hash::Hash;
+        let hash = |x : &dyn Fn(&mut Fnv1a)| { let mut h = Fnv1a::default(); x(&mut h); h.finish() };
+        assert_eq!(hash(&|h| h.write(b"a")), 0xaf63_dc4c_8601_ec8c);
+        // Integers hash the same whatever the platform's byte order and pointer width
+        assert_eq!(hash(&|h| 1usize.hash(h)), hash(&|h| h.write(&[1, 0, 0, 0, 0, 0, 0, 0])));
+        assert_eq!(hash(&|h| (-1i16).hash(h)), hash(&|h| h.write(&[0xff, 0xff])));
+    }
+
+    /// The values for a given seed must never change, on any platform or in any version, so that
+    /// reported seeds keep reproducing their failures. Don't update these to make them pass.
+    #[test]
+    fn golden_vectors() {
+        let golden = [
+            (RngBackend::Pcg,
+                [0xac39_7c9d_9578_2bc9, 0x04fe_cb1a_88b0_4178, 0xc293_807e_3433_7c51, 0xd12e_1a03_93cb_6d93],
+                [672, 19, 760, 817], 0x4c57_99c4_8f83_527c),
+            (RngBackend::ChaCha8,
+                [0x6541_90fc_8cfa_b18e, 0x8d1c_2ee6_c8c4_2bf9, 0xf6bb_4fdf_48b9_e6c3, 0x8673_a19c_090c_bc83],
+                [206, 633, 27, 611], 0x4b3a_59ee_4534_7c0d),
+            (RngBackend::SplitMix64,
+                [0xbdd7_3226_2feb_6e95, 0x28ef_e333_b266_f103, 0x4752_6757_130f_9f52, 0x581c_e1ff_0e4a_e394],
+                [413, 291, 858, 764], 0x950d_0503_5ac1_6587),
+        ];
+        for (backend, outputs, ranged, split) in golden {
+            let mut r = Random::with_backend(backend, 42);
+            assert_eq!(outputs.map(|_| r.u64()), outputs, "{:?}", backend);
+            let mut r = Random::with_backend(backend, 42);
+            assert_eq!(ranged.map(|_| r.u64_range(0..1000)), ranged, "{:?}", backend);
+            assert_eq!(r.split().u64(), split, "{:?}", backend);
+        }
+    }
+
+    #[test]
+    fn advance_skips_outputs() {
+        for backend in BACKENDS {
+            let mut r = Random::with_backend(backend, 21);
+            // Part way into a block, and skipping across block boundaries
+            r.u64();
+            for delta in [0, 1, 6, 7, 8, 20] {
+                let mut stepped = r;
+                for _ in 0..delta {
+                    stepped.u64();
+                }
+                let mut skipped = r;
+                skipped.advance(delta);
+                assert_eq!(skipped.u64(), stepped.u64(), "{:?} {}", backend, delta);
+            }
+
+            let mut twice = r.stream(3);
+            twice.advance(STREAM_LENGTH * 2);
+            assert_eq!(twice.u64(), r.stream(5).u64(), "{:?}", backend);
+            assert_ne!(r.stream(1).u64(), r.stream(2).u64(), "{:?}", backend);
+        }
+    }
+
+    #[test]
+    fn entropy_seeds_differ() {
+        assert_ne!(Random::entropy_seed(), Random::entropy_seed());
+        assert_ne!(Random::from_entropy().u128(), Random::from_entropy().u128());
+    }
+
+    #[test]
+    fn fills_bytes() {
+        for backend in BACKENDS {
+            let mut r = Random::with_backend(backend, 4);
+            let mut buf = [0u8; 13];
+            r.fill_bytes(&mut buf);
+            let mut again = Random::with_backend(backend, 4);
+            assert_eq!(buf[..8], again.u64().to_le_bytes());
+            assert_eq!(buf[8..], again.u64().to_le_bytes()[..5]);
+        }
+
+        let mut buf = vec![0u8; 8000];
+        Random::new_from_seed(0).fill_bytes(&mut buf);
+        let mut counts = [0u64; 16];
+        for b in buf {
+            counts[(b >> 4) as usize] += 1;
+        }
+        assert!(chi_squared(&counts) < 37.7, "{:?}", counts);
+    }
+
+    #[test]
+    fn range_is_uniform() {
+        for backend in BACKENDS {
+            let mut r = Random::with_backend(backend, 0);
+            let mut counts = [0; 6];
+            for _ in 0..6000 {
+                counts[r.u64_range(0..6) as usize] += 1;
+            }
+            assert!(counts.iter().all(|c| (800..1200).contains(c)), "{:?} {:?}", backend, counts);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// ChaCha with eight rounds, as a random number generator: the output is the keystream for a key
+// taken from the seed. It's slower than PCG, but splitting derives a fresh key from the keystream,
+// so split generators are as independent as the cipher is strong.
+use crate::random::splitmix::mix64;
+use crate::random::RandomSource;
+
+const ROUNDS : usize = 8;
+
+/// "expand 32-byte k"
+const CONSTANTS : [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChaCha8 {
+    key : [u32; 8],
+    /// Which stream of the key this is; forks with different keys use different streams
+    stream : u64,
+    /// Block number of the next block
+    counter : u64,
+    /// Current block, and how much of it has been used, in u64s
+    block : [u32; 16],
+    used : usize,
+}
+
+fn quarter_round(s : &mut [u32; 16], a : usize, b : usize, c : usize, d : usize) {
+    s[a] = s[a].wrapping_add(s[b]); s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]); s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]); s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]); s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+fn block(key : &[u32; 8], stream : u64, counter : u64) -> [u32; 16] {
+    let mut input = [0u32; 16];
+    input[..4].copy_from_slice(&CONSTANTS);
+    input[4..12].copy_from_slice(key);
+    input[12] = counter as u32;
+    input[13] = (counter >> 32) as u32;
+    input[14] = stream as u32;
+    input[15] = (stream >> 32) as u32;
+
+    let mut s = input;
+    for _ in 0..ROUNDS / 2 {
+        quarter_round(&mut s, 0, 4, 8, 12);
+        quarter_round(&mut s, 1, 5, 9, 13);
+        quarter_round(&mut s, 2, 6, 10, 14);
+        quarter_round(&mut s, 3, 7, 11, 15);
+        quarter_round(&mut s, 0, 5, 10, 15);
+        quarter_round(&mut s, 1, 6, 11, 12);
+        quarter_round(&mut s, 2, 7, 8, 13);
+        quarter_round(&mut s, 3, 4, 9, 14);
+    }
+    for (out, inp) in s.iter_mut().zip(input.iter()) {
+        *out = out.wrapping_add(*inp);
+    }
+    s
+}
+
+impl ChaCha8 {
+    pub fn new(key : [u32; 8], stream : u64) -> ChaCha8 {
+        ChaCha8 { key, stream, counter : 0, block : [0; 16], used : 8 }
+    }
+
+    /// Whether the position in the block is one that next_u64 can reach, which a deserialized
+    /// generator might not be
+    pub(crate) fn is_valid(&self) -> bool {
+        (self.used < 8 && self.counter > 0) || self.used == 8
+    }
+
+    /// New key from the first half of a block
+    fn derive_key(block : &[u32; 16]) -> [u32; 8] {
+        let mut key = [0; 8];
+        key.copy_from_slice(&block[..8]);
+        key
+    }
+}
+
+impl RandomSource for ChaCha8 {
+    fn from_seed(seed : u128) -> ChaCha8 {
+        // Spread the seed over the whole key
+        let mut key = [0; 8];
+        let mut z = seed as u64 ^ mix64((seed >> 64) as u64);
+        for pair in key.chunks_mut(2) {
+            z = mix64(z.wrapping_add(0x9e37_79b9_7f4a_7c15));
+            pair[0] = z as u32;
+            pair[1] = (z >> 32) as u32;
+        }
+        ChaCha8::new(key, 0)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        if self.used == 8 {
+            self.block = block(&self.key, self.stream, self.counter);
+            self.counter = self.counter.wrapping_add(1);
+            self.used = 0;
+        }
+        let ix = self.used * 2;
+        self.used += 1;
+        (self.block[ix] as u64) | ((self.block[ix + 1] as u64) << 32)
+    }
+
+    /// Outputs are numbered by block and position in the block, so skipping ahead only needs the
+    /// block that the new position is in
+    fn advance(&mut self, delta : u128) {
+        // The counter has already moved past the current block, and used is 8 before the first block
+        let position = ((self.counter as u128) << 3) + self.used as u128 - 8;
+        let position = position.wrapping_add(delta) & ((1 << 67) - 1);
+        let counter = (position >> 3) as u64;
+        self.block = block(&self.key, self.stream, counter);
+        self.counter = counter.wrapping_add(1);
+        self.used = (position & 7) as usize;
+    }
+
+    /// The child's key is a block from a stream of the parent's key that no generator reads from
+    fn fork(&self, key : u64) -> ChaCha8 {
+        // Same numbering as advance, so the positions before and after refilling a block differ
+        let position = mix64((self.counter << 3).wrapping_add(self.used as u64).wrapping_sub(8));
+        let derived = block(&self.key, !self.stream, mix64(key ^ position));
+        ChaCha8::new(ChaCha8::derive_key(&derived), 0)
+    }
+
+    fn split(&mut self) -> ChaCha8 {
+        let mut key = [0; 8];
+        for pair in key.chunks_mut(2) {
+            let z = self.next_u64();
+            pair[0] = z as u32;
+            pair[1] = (z >> 32) as u32;
+        }
+        ChaCha8::new(key, 0)
+    }
+}
@@ -0,0 +1,120 @@
+// PCG-XSH-RR with 128 bits of state and 64 bit outputs, the algorithm of oorandom's Rand64.
+// The default generator lives here rather than in oorandom so that a given seed generates the same
+// values on every platform and with every version of this crate, whatever oorandom does next.
+// The golden vectors in the tests of random pin the outputs; if they fail, the stream has changed.
+use std::ops::Range;
+
+use crate::random::splitmix::mix64;
+use crate::random::RandomSource;
+
+/// Multiplier of the LCG, from the PCG reference implementation
+const MULTIPLIER : u128 = 47026247687942121848144207491837523525;
+
+/// Stream used by from_seed, from the PCG reference implementation
+const DEFAULT_INC : u128 = 0x2fe0_e169_ffbd_06e3_5bc3_07bd_4d2f_814f;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Pcg64 {
+    state : u128,
+    /// Always odd
+    inc : u128,
+}
+
+impl Pcg64 {
+    /// Generator on the given stream, with the same setup as oorandom's Rand64::new_inc
+    pub fn new_inc(seed : u128, inc : u128) -> Pcg64 {
+        let mut r = Pcg64 { state : 0, inc : inc.wrapping_shl(1) | 1 };
+        r.next_u64();
+        r.state = r.state.wrapping_add(seed);
+        r.next_u64();
+        r
+    }
+
+    pub fn state(&self) -> (u128, u128) {
+        (self.state, self.inc)
+    }
+
+    /// Generator at exactly the given position, as returned by state
+    pub fn from_state((state, inc) : (u128, u128)) -> Pcg64 {
+        Pcg64 { state, inc }
+    }
+}
+
+impl RandomSource for Pcg64 {
+    fn from_seed(seed : u128) -> Pcg64 {
+        Pcg64::new_inc(seed, DEFAULT_INC)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.inc);
+        let xorshifted = (((old >> 29) ^ old) >> 58) as u64;
+        xorshifted.rotate_right((old >> 122) as u32)
+    }
+
+    /// Lemire's multiply and reject, as in oorandom
+    fn u64_range(&mut self, range : Range<u64>) -> u64 {
+        let width = range.end - range.start;
+        let mut m = self.next_u64() as u128 * width as u128;
+        if (m as u64) < width {
+            let threshold = width.wrapping_neg() % width;
+            while (m as u64) < threshold {
+                m = self.next_u64() as u128 * width as u128;
+            }
+        }
+        range.start + (m >> 64) as u64
+    }
+
+    /// Jump ahead in the LCG by composing delta steps with repeated squaring, as in Brown's
+    /// "Random number generation with arbitrary strides"
+    fn advance(&mut self, mut delta : u128) {
+        let (mut acc_mul, mut acc_add) = (1u128, 0u128);
+        let (mut cur_mul, mut cur_add) = (MULTIPLIER, self.inc);
+        while delta > 0 {
+            if delta & 1 == 1 {
+                acc_mul = acc_mul.wrapping_mul(cur_mul);
+                acc_add = acc_add.wrapping_mul(cur_mul).wrapping_add(cur_add);
+            }
+            cur_add = cur_mul.wrapping_add(1).wrapping_mul(cur_add);
+            cur_mul = cur_mul.wrapping_mul(cur_mul);
+            delta >>= 1;
+        }
+        self.state = acc_mul.wrapping_mul(self.state).wrapping_add(acc_add);
+    }
+
+    fn fork(&self, key : u64) -> Pcg64 {
+        // Spread the key over all the bits, so that nearby keys give unrelated seeds
+        let mixed = (key as u128).wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c835);
+        Pcg64::from_seed(self.state ^ self.inc.rotate_left(64) ^ mixed)
+    }
+
+    /// The child's seed and stream are hashes of fresh output from self, rather than a perturbed copy
+    /// of self's state, so children don't share correlated state with the parent or each other.
+    fn split(&mut self) -> Pcg64 {
+        let mut hashed = || (mix64(self.next_u64()) as u128) << 64 | mix64(self.next_u64()) as u128;
+        let seed = hashed();
+        let inc = hashed();
+        Pcg64::new_inc(seed, inc)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use oorandom::Rand64;
+
+    use crate::random::pcg::*;
+
+    #[test]
+    fn same_as_oorandom() {
+        for seed in [0, 1, 42, u128::MAX] {
+            let mut ours = Pcg64::from_seed(seed);
+            let mut theirs = Rand64::new(seed);
+            for _ in 0..100 {
+                assert_eq!(ours.next_u64(), theirs.rand_u64());
+                assert_eq!(ours.u64_range(3..1000), theirs.rand_range(3..1000));
+            }
+            assert_eq!(ours.state(), theirs.state());
+        }
+    }
+}
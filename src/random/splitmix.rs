@@ -0,0 +1,73 @@
+// SplitMix64, from "Fast splittable pseudorandom number generators" (Steele, Lea and Flood).
+// Each generator is a counter and an odd increment, and every output is a hash of the counter, so
+// splitting and forking just pick a new counter and increment.
+use crate::random::RandomSource;
+
+/// Golden ratio increment, used for seeding
+const GOLDEN_GAMMA : u64 = 0x9e37_79b9_7f4a_7c15;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SplitMix64 {
+    seed : u64,
+    gamma : u64,
+}
+
+/// Finaliser from MurmurHash3, with the constants from the paper
+pub(crate) fn mix64(z : u64) -> u64 {
+    let z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    let z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Increment for a new generator: odd, and with enough bit transitions that the counter doesn't
+/// just count in the low bits
+fn mix_gamma(z : u64) -> u64 {
+    let z = (z ^ (z >> 33)).wrapping_mul(0xff51_afd7_ed55_8ccd);
+    let z = (z ^ (z >> 33)).wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    let z = (z ^ (z >> 33)) | 1;
+    if (z ^ (z >> 1)).count_ones() < 24 {
+        z ^ 0xaaaa_aaaa_aaaa_aaaa
+    } else {
+        z
+    }
+}
+
+impl SplitMix64 {
+    pub fn new(seed : u64, gamma : u64) -> SplitMix64 {
+        SplitMix64 { seed, gamma : gamma | 1 }
+    }
+
+    /// Whether gamma is odd, as new makes it, so that the counter goes through every u64
+    pub(crate) fn is_valid(&self) -> bool {
+        self.gamma & 1 == 1
+    }
+}
+
+impl RandomSource for SplitMix64 {
+    fn from_seed(seed : u128) -> SplitMix64 {
+        SplitMix64::new(seed as u64 ^ mix64((seed >> 64) as u64), GOLDEN_GAMMA)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.seed = self.seed.wrapping_add(self.gamma);
+        mix64(self.seed)
+    }
+
+    /// The counter goes up by gamma for each output, so skipping ahead is a multiply
+    fn advance(&mut self, delta : u128) {
+        self.seed = self.seed.wrapping_add(self.gamma.wrapping_mul(delta as u64));
+    }
+
+    fn fork(&self, key : u64) -> SplitMix64 {
+        let base = mix64(self.seed ^ mix64(key.wrapping_add(self.gamma)));
+        SplitMix64::new(mix64(base), mix_gamma(base.wrapping_add(GOLDEN_GAMMA)))
+    }
+
+    fn split(&mut self) -> SplitMix64 {
+        let seed = self.next_u64();
+        let gamma = mix_gamma(self.seed.wrapping_add(self.gamma));
+        self.seed = self.seed.wrapping_add(self.gamma);
+        SplitMix64::new(seed, gamma)
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt::Debug;
+use std::ops::{Range, RangeFull, RangeInclusive};
+use std::rc::Rc;
+
+use crate::shrinker::Shrinker;
+
+/// Range of values for an integer generator, with the origin that shrinking moves toward.
+/// Both ends are inclusive, so a GenRange is never empty.
+/// Ordinary ranges convert to a GenRange with the origin at the value closest to zero, so these
+/// are the same:
+/// > Gen::i32(-40..50)
+/// > Gen::i32(GenRange::from(-40..50).with_origin(0))
+/// but temperatures might be better off shrinking toward room temperature:
+/// > Gen::i32(GenRange::from(-40..50).with_origin(20))
+/// The integer generators take anything that implements IntoGenRange, which also includes
+/// inclusive ranges and the full range `..`, and ranges with their own shrinker:
+/// > Gen::i32(GenRange::from(-40..50).shrink_with(TowardsLargest))
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GenRange<T> {
+    start : T,
+    last : T,
+    origin : T,
+}
+
+impl<T : Copy + Debug + PartialOrd + Default> GenRange<T> {
+    /// Inclusive range, with the origin at the value closest to zero.
+    /// Panics if the range is empty.
+    pub fn inclusive(range : RangeInclusive<T>) -> GenRange<T> {
+        let (start, last) = (*range.start(), *range.end());
+        assert!(start <= last, "GenRange: empty range {:?}", range);
+        let zero = T::default();
+        let origin = if start > zero {
+            start
+        } else if last < zero {
+            last
+        } else {
+            zero
+        };
+        GenRange { start, last, origin }
+    }
+
+    /// Shrink toward the given origin instead. Panics if the origin is outside the range.
+    pub fn with_origin(self, origin : T) -> GenRange<T> {
+        assert!(self.contains(&origin), "GenRange: origin {:?} outside of {:?}", origin, self);
+        GenRange { origin, ..self }
+    }
+
+    pub fn start(&self) -> T {
+        self.start
+    }
+
+    /// The last value in the range, inclusive
+    pub fn last(&self) -> T {
+        self.last
+    }
+
+    pub fn origin(&self) -> T {
+        self.origin
+    }
+
+    pub fn contains(&self, value : &T) -> bool {
+        self.start <= *value && *value <= self.last
+    }
+
+    /// Convert all the bounds, which should preserve the ordering, eg widening to a larger type
+    pub fn map<U, F>(self, f : F) -> GenRange<U>
+    where F : Fn(T) -> U {
+        GenRange { start : f(self.start), last : f(self.last), origin : f(self.origin) }
+    }
+}
+
+/// Ranges that the integer generators accept: GenRange itself, `a..b`, `a..=b` and `..`
+pub trait IntoGenRange<T> {
+    fn into_gen_range(self) -> GenRange<T>;
+
+    /// How to shrink values in the range, if not toward the origin; see GenRange::shrink_with
+    fn shrinker(&self) -> Option<Rc<dyn Shrinker<T>>> {
+        None
+    }
+}
+
+impl<T> IntoGenRange<T> for GenRange<T> {
+    fn into_gen_range(self) -> GenRange<T> {
+        self
+    }
+}
+
+macro_rules! impl_from_range {
+    ($($t:ty)*) => {
+        $(
+            impl From<Range<$t>> for GenRange<$t> {
+                /// Panics if the range is empty
+                fn from(range : Range<$t>) -> GenRange<$t> {
+                    assert!(!range.is_empty(), "GenRange: empty range {:?}", range);
+                    GenRange::inclusive(range.start ..= range.end - 1)
+                }
+            }
+
+            impl IntoGenRange<$t> for Range<$t> {
+                /// Panics if the range is empty
+                fn into_gen_range(self) -> GenRange<$t> {
+                    GenRange::from(self)
+                }
+            }
+
+            impl IntoGenRange<$t> for RangeInclusive<$t> {
+                /// Panics if the range is empty
+                fn into_gen_range(self) -> GenRange<$t> {
+                    GenRange::inclusive(self)
+                }
+            }
+
+            impl IntoGenRange<$t> for RangeFull {
+                /// Every value of the type, from MIN to MAX
+                fn into_gen_range(self) -> GenRange<$t> {
+                    GenRange::inclusive(<$t>::MIN ..= <$t>::MAX)
+                }
+            }
+        )*
+    }
+}
+
+impl_from_range!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+
+
+#[cfg(test)]
+mod test {
+    use crate::range::*;
+
+    #[test]
+    fn default_origin_nearest_zero() {
+        assert_eq!(GenRange::from(3u8..10).origin(), 3);
+        assert_eq!(GenRange::from(-10i32..-3).origin(), -4);
+        assert_eq!(GenRange::from(-10i32..10).origin(), 0);
+        assert_eq!(GenRange::from(-10i32..10).with_origin(5).origin(), 5);
+    }
+
+    #[test]
+    fn into_gen_range() {
+        assert_eq!((3u8..10).into_gen_range(), (3u8..=9).into_gen_range());
+        assert_eq!((..).into_gen_range(), GenRange::inclusive(i8::MIN..=i8::MAX));
+        assert_eq!(IntoGenRange::<u64>::into_gen_range(..).origin(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn origin_outside() {
+        GenRange::from(0u64..10).with_origin(10);
+    }
+}
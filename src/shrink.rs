@@ -0,0 +1,376 @@
+// Strategies for searching the shrink tree of a failing test case for a simpler counterexample.
+// Greedy search is fast, but commits to the first failing shrink it finds, which can be a dead end
+// when a later sibling would have led to a much simpler value. The other strategies look further
+// before committing, at the cost of running the property more often.
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::hh3_lazy_tree::{Children, Tree};
+use crate::property::catch_discard;
+use crate::tree::zipper::TreeZipper;
+
+/// How to walk the shrink tree of a failing test case. Config picks one with ShrinkSearch, since
+/// search is generic over the value type and so can't be boxed.
+pub(crate) trait ShrinkStrategy {
+    /// Search the tree, whose root is known to fail. `fails` runs the property on a shrink
+    /// candidate. Returns the simplest failing value found, and the number of shrink steps from the
+    /// root to it.
+    fn search<'a, A : Clone>(&self, tree : Tree<'a, A>, fails : &mut dyn FnMut(&A) -> bool) -> (A, usize);
+}
+
+/// Take the first failing child of each node, until a node has no failing children.
+/// This is the default, and what Hedgehog does.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Greedy;
+
+/// Search the failing nodes level by level, keeping up to width of them at each level, and return
+/// the first failing node of the deepest level. With a width of one, this is the same as Greedy.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BreadthFirst {
+    pub(crate) width : usize,
+}
+
+/// From the current node, search all failing descendants up to depth levels down, backtracking out
+/// of dead ends, and move to the deepest one found. Repeats until nothing below fails.
+/// With a depth of one, this is the same as Greedy.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DepthLimited {
+    pub(crate) depth : usize,
+}
+
+/// Choice of shrink strategy for Config
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShrinkSearch {
+    /// Take the first failing child of each node
+    #[default]
+    Greedy,
+    /// Keep up to width failing nodes at each level, and return the first of the deepest level
+    BreadthFirst { width : usize },
+    /// Look up to depth levels down for the deepest failing node, backtracking out of dead ends
+    DepthLimited { depth : usize },
+}
+
+/// Caps on the shrink search, so that huge shrink trees stop with a partly shrunk value instead
+/// of appearing to hang. The report says when shrinking was truncated by one of these.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShrinkLimits {
+    /// Most shrink steps to take from the original value
+    pub max_depth : usize,
+    /// Most times to run the property while shrinking
+    pub max_steps : usize,
+    /// Most children of each node to try
+    pub max_candidates : usize,
+}
+
+impl Default for ShrinkLimits {
+    fn default() -> ShrinkLimits {
+        ShrinkLimits { max_depth : 1000, max_steps : 10_000, max_candidates : 1000 }
+    }
+}
+
+/// Shared state of a limited search
+struct Limiter {
+    limits : ShrinkLimits,
+    /// Whether the search wanted to go past one of the limits
+    truncated : Cell<bool>,
+    /// Whether the property has run max_steps times, so there's no point looking at more children
+    exhausted : Cell<bool>,
+}
+
+/// Run a strategy with limits on how deep and wide it goes, and how often it runs the property.
+/// Returns the strategy's result, and whether the search was cut short.
+pub(crate) fn search_limited<'a, A, S>(strategy : &S, tree : Tree<'a, A>, limits : ShrinkLimits, fails : &mut dyn FnMut(&A) -> bool) -> (A, usize, bool)
+where S : ShrinkStrategy,
+A : Clone + 'a {
+    let limiter = Rc::new(Limiter { limits, truncated : Cell::new(false), exhausted : Cell::new(false) });
+    let mut steps = 0;
+    let mut limited_fails = |a : &A| {
+        if steps >= limits.max_steps {
+            limiter.truncated.set(true);
+            limiter.exhausted.set(true);
+            return false;
+        }
+        steps += 1;
+        fails(a)
+    };
+    let (minimal, shrinks) = strategy.search(limit(tree, 0, limiter.clone()), &mut limited_fails);
+    (minimal, shrinks, limiter.truncated.get())
+}
+
+/// Greedy search that checks up to threads children at once. first_failing runs the property on a
+/// batch of candidates and returns the index of the first one that fails. Moving to the first
+/// failure rather than whichever finishes first keeps the result the same as Greedy.
+pub(crate) fn search_parallel<'a, A>(tree : Tree<'a, A>, limits : ShrinkLimits, threads : usize, first_failing : &mut dyn FnMut(&[A]) -> Option<usize>) -> (A, usize, bool)
+where A : Clone + 'a {
+    let limiter = Rc::new(Limiter { limits, truncated : Cell::new(false), exhausted : Cell::new(false) });
+    let mut here = limit(tree, 0, limiter.clone());
+    let mut shrinks = 0;
+    let mut steps = 0;
+    'search: loop {
+        let mut candidates = children(&here);
+        loop {
+            let room = limits.max_steps - steps;
+            let batch : Vec<Tree<'a, A>> = candidates.by_ref().take(threads.max(1).min(room)).collect();
+            if batch.is_empty() {
+                if room == 0 && candidates.next().is_some() {
+                    limiter.truncated.set(true);
+                }
+                return (here.value, shrinks, limiter.truncated.get());
+            }
+            steps += batch.len();
+            let values : Vec<A> = batch.iter().map(|t| t.value.clone()).collect();
+            if let Some(ix) = first_failing(&values) {
+                here = batch.into_iter().nth(ix).expect("first_failing returns an index into the batch");
+                shrinks += 1;
+                continue 'search;
+            }
+        }
+    }
+}
+
+/// Cut off the children of a tree that are past the limits
+fn limit<'a, A : Clone + 'a>(tree : Tree<'a, A>, depth : usize, limiter : Rc<Limiter>) -> Tree<'a, A> {
+    let children = tree.children;
+    let children_clo = move || -> Children<'a, A> {
+        let mut inner = (*children)();
+        if limiter.exhausted.get() || depth >= limiter.limits.max_depth {
+            if inner.next().is_some() {
+                limiter.truncated.set(true);
+            }
+            return Box::new(std::iter::empty());
+        }
+        let limiter = limiter.clone();
+        let mut taken = 0;
+        Box::new(std::iter::from_fn(move || {
+            if limiter.exhausted.get() {
+                return None;
+            }
+            if taken >= limiter.limits.max_candidates {
+                if inner.next().is_some() {
+                    limiter.truncated.set(true);
+                }
+                return None;
+            }
+            taken += 1;
+            inner.next().map(|child| limit(child, depth + 1, limiter.clone()))
+        }))
+    };
+    Tree::from_children(tree.value, Rc::new(children_clo))
+}
+
+/// Children of a node. If a child can't be generated, it and the rest of its siblings are treated
+/// as missing.
+fn children<'a, A>(tree : &Tree<'a, A>) -> impl Iterator<Item = Tree<'a, A>> {
+    let mut children = catch_discard(|| (*tree.children)());
+    std::iter::from_fn(move || {
+        let children = children.as_mut()?;
+        catch_discard(|| children.next()).flatten()
+    })
+}
+
+impl ShrinkStrategy for Greedy {
+    fn search<'a, A : Clone>(&self, tree : Tree<'a, A>, fails : &mut dyn FnMut(&A) -> bool) -> (A, usize) {
+        let mut here = tree;
+        let mut shrinks = 0;
+        'search: loop {
+            // The children are lazy, so siblings after the first failing one are never computed
+            for child in children(&here) {
+                if fails(&child.value) {
+                    here = child;
+                    shrinks += 1;
+                    continue 'search;
+                }
+            }
+            return (here.value, shrinks);
+        }
+    }
+}
+
+impl ShrinkStrategy for BreadthFirst {
+    fn search<'a, A : Clone>(&self, tree : Tree<'a, A>, fails : &mut dyn FnMut(&A) -> bool) -> (A, usize) {
+        let width = self.width.max(1);
+        let mut level = vec![tree];
+        let mut depth = 0;
+        loop {
+            let mut next = Vec::new();
+            'level: for node in &level {
+                for child in children(node) {
+                    if fails(&child.value) {
+                        next.push(child);
+                        if next.len() >= width {
+                            break 'level;
+                        }
+                    }
+                }
+            }
+            if next.is_empty() {
+                return (level.swap_remove(0).value, depth);
+            }
+            level = next;
+            depth += 1;
+        }
+    }
+}
+
+impl DepthLimited {
+    /// Deepest failing descendant at most depth levels down, preferring earlier children.
+    /// The zipper walks the failing nodes depth first and backs out of dead ends without
+    /// recursion, so deep searches don't overflow.
+    fn deepest<'a, A : Clone>(tree : &Tree<'a, A>, depth : usize, fails : &mut dyn FnMut(&A) -> bool) -> Option<(Tree<'a, A>, usize)> {
+        let mut best : Option<(Tree<'a, A>, usize)> = None;
+        let mut z = TreeZipper::new(Tree::clone(tree));
+        // A child that can't be generated is treated as missing, along with the rest of its siblings
+        let step = |z : &mut TreeZipper<'a, A>, go : fn(&mut TreeZipper<'a, A>) -> bool| catch_discard(|| go(z)).unwrap_or(false);
+        if depth == 0 || !step(&mut z, |z| z.down(0)) {
+            return None;
+        }
+        loop {
+            // The focus hasn't been checked yet
+            let steps = z.depth();
+            if fails(z.value()) {
+                if best.as_ref().is_none_or(|(_, best_steps)| steps > *best_steps) {
+                    best = Some((Tree::clone(z.focus()), steps));
+                }
+                // Nothing can be deeper than the limit, so stop looking
+                if steps == depth {
+                    return best;
+                }
+                if step(&mut z, |z| z.down(0)) {
+                    continue;
+                }
+            }
+            // On to the next sibling of the focus or of the nearest ancestor that has one
+            while !step(&mut z, TreeZipper::right) {
+                z.up();
+                if z.depth() == 0 {
+                    return best;
+                }
+            }
+        }
+    }
+}
+
+impl ShrinkStrategy for DepthLimited {
+    fn search<'a, A : Clone>(&self, tree : Tree<'a, A>, fails : &mut dyn FnMut(&A) -> bool) -> (A, usize) {
+        let mut here = tree;
+        let mut shrinks = 0;
+        while let Some((node, steps)) = Self::deepest(&here, self.depth.max(1), fails) {
+            here = node;
+            shrinks += steps;
+        }
+        (here.value, shrinks)
+    }
+}
+
+impl ShrinkStrategy for ShrinkSearch {
+    fn search<'a, A : Clone>(&self, tree : Tree<'a, A>, fails : &mut dyn FnMut(&A) -> bool) -> (A, usize) {
+        match *self {
+            ShrinkSearch::Greedy => Greedy.search(tree, fails),
+            ShrinkSearch::BreadthFirst { width } => BreadthFirst { width }.search(tree, fails),
+            ShrinkSearch::DepthLimited { depth } => DepthLimited { depth }.search(tree, fails),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::{Gen, Tree};
+    use crate::property::{Config, Property};
+    use crate::shrink::*;
+
+    /// Greedy search takes 50, which is a dead end, while the second child leads to 0
+    fn dead_end<'a>() -> Tree<'a, u64> {
+        Tree::new(100, || vec![Tree::leaf(50), Tree::new(60, || vec![Tree::leaf(0)])])
+    }
+
+    #[test]
+    fn strategies_escape_dead_ends() {
+        let minimal = |shrink : ShrinkSearch| {
+            let config = Config { shrink, ..Config::default() };
+            Property::new(Gen::new(|_, _| dead_end()), |_| false).check(&config).counterexamples[0].minimal
+        };
+        assert_eq!(minimal(ShrinkSearch::Greedy), 50);
+        assert_eq!(minimal(ShrinkSearch::BreadthFirst { width : 1 }), 50);
+        assert_eq!(minimal(ShrinkSearch::BreadthFirst { width : 2 }), 0);
+        assert_eq!(minimal(ShrinkSearch::DepthLimited { depth : 1 }), 50);
+        assert_eq!(minimal(ShrinkSearch::DepthLimited { depth : 2 }), 0);
+
+        assert_eq!(DepthLimited { depth : 2 }.search(dead_end(), &mut |_| true), (0, 2));
+    }
+
+    #[test]
+    fn parallel_matches_greedy() {
+        let gen = Gen::u64(0..1000).vec(Gen::usize(0..20));
+        let predicate = |v : &Vec<u64>| v.iter().sum::<u64>() < 500;
+        let sequential = Property::new(gen.clone(), predicate).check(&Config::default());
+        let config = Config { shrink_threads : 4, ..Config::default() };
+        let parallel = Property::new_sync(gen.clone(), predicate).check(&config);
+        assert_eq!(parallel.counterexamples[0].minimal, sequential.counterexamples[0].minimal);
+        assert_eq!(parallel.counterexamples[0].shrinks, sequential.counterexamples[0].shrinks);
+
+        let deduped = Property::new_sync(gen.clone(), predicate).dedup_shrinks().check(&config);
+        assert_eq!(deduped.counterexamples[0].minimal, sequential.counterexamples[0].minimal);
+
+        // The candidates are checked on a pool of shrink_threads threads, not one per candidate
+        let threads = std::sync::Mutex::new(std::collections::HashSet::new());
+        let parallel = Property::new_sync(gen, |v : &Vec<u64>| {
+            threads.lock().unwrap().insert(std::thread::current().id());
+            predicate(v)
+        }).check(&config);
+        assert!(parallel.counterexamples[0].metrics.candidates > 4);
+        assert!(threads.lock().unwrap().len() <= 1 + 4);
+    }
+
+    /// Shrinks by one at a time, so shrinking n to zero takes n steps
+    fn decrement<'a>(n : u64) -> Tree<'a, u64> {
+        Tree::new(n, move || if n > 0 { vec![decrement(n - 1)] } else { vec![] })
+    }
+
+    #[test]
+    fn deep_shrinks_dont_overflow() {
+        let shrink_limits = ShrinkLimits { max_depth : usize::MAX, max_steps : usize::MAX, max_candidates : usize::MAX };
+        for shrink in [ShrinkSearch::Greedy, ShrinkSearch::BreadthFirst { width : 2 }, ShrinkSearch::DepthLimited { depth : 3 }] {
+            let config = Config { shrink, shrink_limits, ..Config::default() };
+            let cx = Property::new(Gen::new(|_, _| decrement(150_000)), |x| *x < 1).check(&config).counterexamples.remove(0);
+            assert_eq!((cx.minimal, cx.shrinks), (1, 149_999), "{:?}", shrink);
+        }
+
+        // Through combine, each shrink carries on from the node the generator got to, rather than
+        // following its path from the root again
+        let config = Config { shrink_limits, ..Config::default() };
+        let gen = Gen::combine(|c| c.of_cached(Gen::new(|_, _| decrement(150_000))));
+        let cx = Property::new(gen, |x| *x < 1).check(&config).counterexamples.remove(0);
+        assert_eq!((cx.minimal, cx.shrinks), (1, 149_999));
+
+        // Keeping the root while forcing the whole chain, and then dropping it
+        let tree = decrement(150_000);
+        assert_eq!(tree.first_shrink_leaf(), 0);
+        assert_eq!(tree.max_depth(usize::MAX), 150_000);
+        assert_eq!(tree.count_to_depth(usize::MAX), 150_001);
+        drop(tree);
+    }
+
+    #[test]
+    fn limits_truncate() {
+        let check = |shrink_limits : ShrinkLimits| {
+            let config = Config { shrink_limits, ..Config::default() };
+            let cx = Property::new(Gen::u64(1000..2000), |x| *x < 1500).check(&config).counterexamples.remove(0);
+            (cx.minimal, cx.truncated)
+        };
+        assert_eq!(check(ShrinkLimits::default()), (1500, false));
+        let (minimal, truncated) = check(ShrinkLimits { max_depth : 1, ..ShrinkLimits::default() });
+        assert!(minimal > 1500 && truncated);
+        let (minimal, truncated) = check(ShrinkLimits { max_steps : 3, ..ShrinkLimits::default() });
+        assert!(minimal > 1500 && truncated);
+        // Only the first shrink of each node, which halves the distance to 1000 and always passes
+        let (minimal, truncated) = check(ShrinkLimits { max_candidates : 1, ..ShrinkLimits::default() });
+        assert!(minimal >= 1500 && truncated);
+
+        // Infinitely many shrinks stop at the limit rather than hanging
+        let gen = Gen::new(|_, _| Tree::new(10u64, || (11..).map(Tree::leaf)));
+        let report = Property::new(gen, |x| *x != 10).check(&Config::default());
+        assert!(report.counterexamples[0].truncated, "{}", report);
+        assert!(format!("{}", report).contains("shrinking truncated"), "{}", report);
+    }
+}
@@ -0,0 +1,236 @@
+// Pluggable shrinking for the integer and list generators.
+// A Shrinker gives the candidates to try in place of a value, most aggressive first, given the
+// range the value was generated from. The integer generators take one along with their range, and
+// Gen::vec along with its lengths, and build the shrink tree by applying the shrinker to each
+// candidate in turn.
+// > Gen::u64(GenRange::from(0..100).shrink_with(TowardsLargest))
+// > Gen::u64(0..10).vec(Lengths::new(Gen::usize(0..20)).shrink_with(RemoveChunks))
+use std::fmt::Debug;
+use std::rc::Rc;
+
+use crate::hh3_lazy_tree::{towards, Gen, Tree};
+use crate::range::{GenRange, IntoGenRange};
+
+/// Shrink candidates for a value, given metadata about where it came from: the range for
+/// integers, or the range of lengths for lists
+pub trait Shrinker<A, R = GenRange<A>> {
+    /// Values to try instead, most aggressive first. They should all be simpler than value by some
+    /// measure, so that shrinking terminates.
+    fn shrink(&self, value : &A, range : &R) -> Vec<A>;
+}
+
+/// Any function of the value and range is a shrinker
+impl<A, R, F> Shrinker<A, R> for F
+where F : Fn(&A, &R) -> Vec<A> {
+    fn shrink(&self, value : &A, range : &R) -> Vec<A> {
+        self(value, range)
+    }
+}
+
+/// Shrink toward the origin of the range, trying the origin itself first and then halving the
+/// distance back to the value. This is how Gen::u64 and the other integer generators shrink.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TowardsOrigin;
+
+/// Shrink toward the largest value in the range, in the same way as TowardsOrigin
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TowardsLargest;
+
+/// Remove chunks of elements from a list, largest chunks first, without going below the start of the
+/// range of lengths. This is how Gen::vec shrinks the length.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RemoveChunks;
+
+/// Integer types that the shrinkers work on
+pub trait Integer : Copy + Debug + PartialOrd + Default {
+    /// Distance between two values
+    fn distance(a : Self, b : Self) -> u128;
+    /// Value that is d away from `from`, in the direction of `to`
+    fn step_toward(from : Self, to : Self, d : u128) -> Self;
+}
+
+macro_rules! impl_integer {
+    ($wide:ty : $($t:ident)*) => {
+        $(
+            impl Integer for $t {
+                fn distance(a : $t, b : $t) -> u128 {
+                    (a as $wide).abs_diff(b as $wide) as u128
+                }
+
+                fn step_toward(from : $t, to : $t, d : u128) -> $t {
+                    if to > from {
+                        (from as $wide).wrapping_add(d as $wide) as $t
+                    } else {
+                        (from as $wide).wrapping_sub(d as $wide) as $t
+                    }
+                }
+            }
+        )*
+    }
+}
+
+impl_integer!(u128 : u8 u16 u32 u64 u128 usize);
+impl_integer!(i128 : i8 i16 i32 i64 i128 isize);
+
+/// Values from target toward value, halving the distance left each time; see towards
+fn toward<T : Integer>(target : T, value : T) -> Vec<T> {
+    towards(T::distance(value, target)).into_iter().map(|d| T::step_toward(target, value, d)).collect()
+}
+
+impl<T : Integer> Shrinker<T> for TowardsOrigin {
+    fn shrink(&self, value : &T, range : &GenRange<T>) -> Vec<T> {
+        toward(range.origin(), *value)
+    }
+}
+
+impl<T : Integer> Shrinker<T> for TowardsLargest {
+    fn shrink(&self, value : &T, range : &GenRange<T>) -> Vec<T> {
+        toward(range.last(), *value)
+    }
+}
+
+impl<A : Clone> Shrinker<Vec<A>, GenRange<usize>> for RemoveChunks {
+    fn shrink(&self, value : &Vec<A>, lengths : &GenRange<usize>) -> Vec<Vec<A>> {
+        let (len, min_len) = (value.len(), lengths.start());
+        let mut shrinks = Vec::new();
+        let mut chunk = len.saturating_sub(min_len);
+        while chunk > 0 {
+            for start in (0 ..= len - chunk).step_by(chunk) {
+                let mut smaller = value.clone();
+                smaller.drain(start .. start + chunk);
+                shrinks.push(smaller);
+            }
+            chunk /= 2;
+        }
+        shrinks
+    }
+}
+
+/// Tree of a value and its shrinks, applying the shrinker again to each shrink
+fn shrink_tree<'a, A, R, S>(value : A, range : Rc<R>, shrinker : Rc<S>) -> Tree<'a, A>
+where S : Shrinker<A, R> + ?Sized + 'a,
+A : Clone + 'a, R : 'a {
+    let root = value.clone();
+    let children = move || {
+        let (range, shrinker) = (range.clone(), shrinker.clone());
+        shrinker.shrink(&root, &range).into_iter()
+            .map(move |v| shrink_tree(v, range.clone(), shrinker.clone()))
+    };
+    Tree::new(value, children)
+}
+
+/// Range for an integer generator, with the shrinker to use instead of shrinking toward the origin
+#[derive(Clone)]
+pub struct ShrinkRange<T> {
+    range : GenRange<T>,
+    shrinker : Rc<dyn Shrinker<T>>,
+}
+
+impl<T> GenRange<T> {
+    /// Shrink with the given shrinker, which gets this range along with each value
+    pub fn shrink_with<S>(self, shrinker : S) -> ShrinkRange<T>
+    where S : Shrinker<T> + 'static {
+        ShrinkRange { range : self, shrinker : Rc::new(shrinker) }
+    }
+}
+
+impl<T> IntoGenRange<T> for ShrinkRange<T> {
+    fn into_gen_range(self) -> GenRange<T> {
+        self.range
+    }
+
+    fn shrinker(&self) -> Option<Rc<dyn Shrinker<T>>> {
+        Some(self.shrinker.clone())
+    }
+}
+
+impl<'a, T : Integer + 'a> Gen<'a, T> {
+    /// Integer generator for the range, from gen, with the range's own shrinker if it has one.
+    /// There's no enumeration with a shrinker, since exhaustive checking reports the first failing
+    /// value in enumeration order, which wouldn't follow the shrinker.
+    pub(crate) fn integer<R, F>(range : R, gen : F) -> Gen<'a, T>
+    where R : IntoGenRange<T>,
+    F : FnOnce(GenRange<T>) -> Gen<'a, T> {
+        let shrinker = range.shrinker();
+        let range = range.into_gen_range();
+        let gen = gen(range);
+        match shrinker {
+            None => gen,
+            Some(shrinker) => {
+                let range = Rc::new(range);
+                Gen::new(move |r, s| shrink_tree((*gen.run)(r, s).value, range.clone(), shrinker.clone()))
+            }
+        }
+    }
+}
+
+/// Lengths for Gen::vec, and optionally a shrinker to use instead of removing chunks of elements
+/// and then shrinking each element. A plain length generator converts to Lengths without one.
+pub struct Lengths<'a, A> {
+    pub(crate) gen : Gen<'a, usize>,
+    pub(crate) shrinker : Option<Rc<dyn Shrinker<Vec<A>, GenRange<usize>> + 'a>>,
+}
+
+impl<'a, A> Lengths<'a, A> {
+    pub fn new(gen : Gen<'a, usize>) -> Lengths<'a, A> {
+        Lengths { gen, shrinker : None }
+    }
+
+    /// Shrink the lists with the given shrinker, which gets the lengths from the one that the
+    /// length generator shrinks to, up to the length of the generated list. Only the shrinker's
+    /// candidates are tried, so the elements themselves don't shrink unless the shrinker does that too.
+    pub fn shrink_with<S>(self, shrinker : S) -> Lengths<'a, A>
+    where S : Shrinker<Vec<A>, GenRange<usize>> + 'a {
+        Lengths { shrinker : Some(Rc::new(shrinker)), ..self }
+    }
+}
+
+impl<'a, A> From<Gen<'a, usize>> for Lengths<'a, A> {
+    fn from(gen : Gen<'a, usize>) -> Lengths<'a, A> {
+        Lengths::new(gen)
+    }
+}
+
+impl<'a, A : Clone + 'a> Gen<'a, A> {
+    /// Lists with length from gen_len, shrunk by the given shrinker; see Lengths::shrink_with
+    pub(crate) fn vec_shrunk_by(self, gen_len : Gen<'a, usize>, shrinker : Rc<dyn Shrinker<Vec<A>, GenRange<usize>> + 'a>) -> Gen<'a, Vec<A>> {
+        Gen::new(move |mut r, s| {
+            let len_tree = (*gen_len.run)(r.split(), s);
+            let min_len = len_tree.first_shrink_leaf().min(len_tree.value);
+            let value : Vec<A> = (0..len_tree.value).map(|_| (*self.run)(r.split(), s).value).collect();
+            let lengths = GenRange::inclusive(min_len ..= len_tree.value).with_origin(min_len);
+            shrink_tree(value, Rc::new(lengths), shrinker.clone())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::Gen;
+    use crate::property::{Config, Property};
+    use crate::shrinker::*;
+
+    #[test]
+    fn integer_shrinkers() {
+        // The default shrinks, but through the trait
+        let shrink_with = |range : GenRange<u64>| Gen::u64(range.shrink_with(TowardsOrigin)).sample_tree(5, 10);
+        assert_eq!(shrink_with(GenRange::from(0..1000)), Gen::u64(0..1000).sample_tree(5, 10));
+        assert_eq!(Gen::i32(GenRange::from(-50..50).shrink_with(TowardsOrigin)).sample_tree(5, 10), Gen::i32(-50..50).sample_tree(5, 10));
+        assert!((*Gen::u64(GenRange::from(0..10).shrink_with(TowardsOrigin)).enumerate)(100).is_none());
+
+        Property::check_shrink(Gen::u64(GenRange::from(0..100).shrink_with(TowardsLargest)), |x| !(60..=90).contains(x), 90);
+        Property::check_shrink(Gen::u8_biased(GenRange::from(0..100).shrink_with(TowardsLargest)), |x| !(60..=90).contains(x), 90);
+        let down_by_one = |&x : &i64, range : &GenRange<i64>| if x > range.start() { vec![x - 1] } else { vec![] };
+        Property::check_shrink(Gen::i64(GenRange::from(-100..100).shrink_with(down_by_one)), |&x| x < 20, 20);
+    }
+
+    #[test]
+    fn vec_shrinker() {
+        let gen = Gen::u64(0..10).vec(Lengths::new(Gen::usize(2..20)).shrink_with(RemoveChunks));
+        let report = Property::new(gen.clone(), |v| v.len() < 5).check(&Config::default());
+        assert_eq!(report.counterexamples[0].minimal.len(), 5);
+        // Never shorter than the length generator's origin
+        let report = Property::new(gen, |v| v.len() > 10).check(&Config::default());
+        assert_eq!(report.counterexamples[0].minimal.len(), 2);
+    }
+}
@@ -0,0 +1,6 @@
+// Tools for inspecting shrink trees, eg to see why a generator has so many shrinks.
+// The trees themselves are in hh3_lazy_tree.
+pub mod render;
+pub mod zipper;
+#[cfg(feature = "tree_cache")]
+pub mod cache;
@@ -0,0 +1,296 @@
+// Save the forced part of a shrink tree to disk and resume from it later, behind the tree_cache
+// feature. A long shrink session that gets interrupted can load the saved nodes instead of
+// recomputing them, and only falls back to the lazy tree past the nodes that were saved.
+// > ForcedTree::force(&tree, 5).save("shrinks.json")?;
+// > let tree = ForcedTree::load("shrinks.json")?.resume((*gen.run)(rand, size));
+// Property::check saves a ShrinkCheckpoint as it shrinks if Config::shrink_checkpoint is set, and
+// carries on from it the next time.
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::hh3_lazy_tree::{lazily, Children, Tree, TreePath};
+
+/// A node that was forced, with how many of its children follow it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct ForcedNode<A> {
+    value : A,
+    children : usize,
+    /// Whether only the first children were saved, eg because the node was at the depth limit, so the
+    /// rest have to come from the lazy tree
+    truncated : bool,
+}
+
+/// The nodes of a tree down to a bounded depth, or along a path, in depth-first order.
+/// The nodes are a flat list rather than nested, so that deep trees don't overflow the stack when
+/// they are saved or loaded.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ForcedTree<A> {
+    nodes : Vec<ForcedNode<A>>,
+    /// Where the shrink search had got to, for trees saved by force_path
+    path : TreePath,
+}
+
+impl<A : Clone> ForcedTree<A> {
+    /// Force every node down to max_depth, where the root is at depth zero.
+    /// This forces every child of every node above that depth, so keep it small for wide trees.
+    pub fn force(tree : &Tree<A>, max_depth : usize) -> ForcedTree<A> {
+        let mut nodes = Vec::new();
+        // Nodes still to save, last first
+        let mut todo = vec![(Tree::clone(tree), 0)];
+        while let Some((node, depth)) = todo.pop() {
+            if depth == max_depth {
+                let truncated = (*node.children)().next().is_some();
+                nodes.push(ForcedNode { value : node.value, children : 0, truncated });
+                continue;
+            }
+            let children : Vec<Tree<A>> = (*node.children)().collect();
+            nodes.push(ForcedNode { value : node.value, children : children.len(), truncated : false });
+            todo.extend(children.into_iter().rev().map(|c| (c, depth + 1)));
+        }
+        ForcedTree { nodes, path : TreePath::empty() }
+    }
+
+    /// Force the spine of the tree along a path, eg to where the shrink search has got to. Each node
+    /// on the path saves its children up to the next one on the path, and the rest of the tree is
+    /// left to the lazy tree when resuming. Stops early if the path doesn't fit the tree.
+    pub fn force_path(tree : &Tree<A>, path : &TreePath) -> ForcedTree<A> {
+        let mut nodes = Vec::new();
+        let mut here = Tree::clone(tree);
+        let mut followed = TreePath::empty();
+        for &ix in path.indices() {
+            let children : Vec<Tree<A>> = (*here.children)().take(ix + 1).collect();
+            if children.len() <= ix {
+                break;
+            }
+            nodes.push(ForcedNode { value : here.value, children : ix + 1, truncated : true });
+            let mut children = children.into_iter();
+            for sibling in children.by_ref().take(ix) {
+                nodes.push(ForcedNode { value : sibling.value, children : 0, truncated : true });
+            }
+            here = children.next().expect("path child was forced");
+            followed.push(ix);
+        }
+        nodes.push(ForcedNode { value : here.value, children : 0, truncated : true });
+        ForcedTree { nodes, path : followed }
+    }
+
+    /// Path that force_path saved the spine along, which is empty for force
+    pub fn path(&self) -> &TreePath {
+        &self.path
+    }
+
+    /// Number of nodes that were saved
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Lazy tree with the saved values, which uses fallback for the children that weren't saved. The
+    /// fallback should be the tree the saved one was forced from, eg by running the generator again
+    /// with the same seed and size; it's only forced along the paths that go past the saved nodes.
+    pub fn resume<'a>(self, fallback : Tree<'a, A>) -> Tree<'a, A>
+    where A : 'a {
+        // Index just past the subtree of each node, so that siblings can be found without walking
+        // their subtrees
+        let mut ends = vec![0; self.nodes.len()];
+        for ix in (0..self.nodes.len()).rev() {
+            let mut end = ix + 1;
+            for _ in 0..self.nodes[ix].children {
+                end = ends[end];
+            }
+            ends[ix] = end;
+        }
+        let saved = Rc::new(Saved { nodes : self.nodes, ends, fallback });
+        resume_node(saved, 0, TreePath::empty())
+    }
+}
+
+impl<A : Clone + Serialize> ForcedTree<A> {
+    /// Write the tree to a file as JSON
+    pub fn save<P : AsRef<Path>>(&self, path : P) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec(self)?)
+    }
+}
+
+impl<A : Clone + DeserializeOwned> ForcedTree<A> {
+    /// Read a tree written by save
+    pub fn load<P : AsRef<Path>>(path : P) -> io::Result<ForcedTree<A>> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+}
+
+/// Where the shrink search of a failing test case had got to, so that Property::check can carry on
+/// from there after being interrupted. The shrink tree is generated again from the seed, case and
+/// size.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShrinkCheckpoint {
+    pub seed : u128,
+    pub case : u64,
+    pub size : usize,
+    /// Path from the root of the shrink tree to the smallest failing value found so far
+    pub path : TreePath,
+}
+
+impl ShrinkCheckpoint {
+    /// Write the checkpoint to a file as JSON
+    pub fn save<P : AsRef<Path>>(&self, path : P) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec(self)?)
+    }
+
+    /// Read a checkpoint written by save
+    pub fn load<P : AsRef<Path>>(path : P) -> io::Result<ShrinkCheckpoint> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+}
+
+/// Tree with the path to each node alongside its value, where the root is at path
+pub(crate) fn with_paths<'a, A : 'a>(tree : Tree<'a, A>, path : TreePath) -> Tree<'a, (A, TreePath)> {
+    let children = tree.children;
+    let value = (tree.value, path.clone());
+    let children_clo = move || -> Children<'a, (A, TreePath)> {
+        let path = path.clone();
+        Box::new((*children)().enumerate().map(move |(ix, child)| {
+            let mut child_path = path.clone();
+            child_path.push(ix);
+            with_paths(child, child_path)
+        }))
+    };
+    Tree::from_children(value, Rc::new(children_clo))
+}
+
+/// Saved nodes shared by the whole resumed tree
+struct Saved<'a, A> {
+    nodes : Vec<ForcedNode<A>>,
+    ends : Vec<usize>,
+    fallback : Tree<'a, A>,
+}
+
+fn resume_node<'a, A : Clone + 'a>(saved : Rc<Saved<'a, A>>, ix : usize, path : TreePath) -> Tree<'a, A> {
+    let value = saved.nodes[ix].value.clone();
+    let children = move || -> Children<'a, A> {
+        let node = &saved.nodes[ix];
+        let (count, truncated) = (node.children, node.truncated);
+        let (fallback, fallback_path) = (saved.clone(), path.clone());
+        let (saved, path) = (saved.clone(), path.clone());
+        let mut child = ix + 1;
+        let forced = (0..count).map(move |child_ix| {
+            let here = child;
+            child = saved.ends[here];
+            let mut child_path = path.clone();
+            child_path.push(child_ix);
+            resume_node(saved.clone(), here, child_path)
+        });
+        // The rest of the children come from the fallback, which is only forced once the saved ones
+        // have been tried
+        let rest = lazily(move || -> Children<'a, A> {
+            if !truncated {
+                return Box::new(std::iter::empty());
+            }
+            // Only the children of a node we got all the way to belong to it
+            let (node, depth) = fallback.fallback.get_path_prefix(&fallback_path);
+            if depth < fallback_path.len() {
+                return Box::new(std::iter::empty());
+            }
+            Box::new((*node.children)().skip(count))
+        });
+        Box::new(forced.chain(rest))
+    };
+    Tree::new(value, children)
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::{Gen, Tree, TreePath};
+    use crate::random::Random;
+    use crate::tree::cache::*;
+
+    fn values<A : Clone>(tree : &Tree<A>) -> Vec<A> {
+        (*tree.children)().map(|c| c.value).collect()
+    }
+
+    #[test]
+    fn save_and_resume() {
+        let gen = Gen::u64(0..1000).vec(Gen::usize(0..5));
+        let tree = (*gen.run)(Random::new_from_seed(3), 30);
+        let forced = ForcedTree::force(&tree, 2);
+
+        let file = std::env::temp_dir().join(format!("hedgehog-tree-cache-{}.json", std::process::id()));
+        forced.save(&file).unwrap();
+        let loaded : ForcedTree<Vec<u64>> = ForcedTree::load(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(loaded, forced);
+
+        // Past the saved depth, the children come from the fallback tree
+        let resumed = loaded.resume((*gen.run)(Random::new_from_seed(3), 30));
+        assert_eq!(resumed.value, tree.value);
+        assert_eq!(resumed.render(3), tree.render(3));
+
+        // Saved values are used even if the fallback has changed
+        let resumed = ForcedTree::force(&tree, 1).resume(Tree::leaf(vec![]));
+        assert_eq!(values(&resumed), values(&tree));
+        assert!(values(&(*resumed.children)().next().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn save_path() {
+        let gen = Gen::u64(0..1000).vec(Gen::usize(0..5));
+        let tree = (*gen.run)(Random::new_from_seed(3), 30);
+        let path = TreePath::from(vec![2, 1]);
+        let forced = ForcedTree::force_path(&tree, &path);
+        assert_eq!(forced.path(), &path);
+        // The node on the path, its ancestors, and the siblings before each of them
+        assert_eq!(forced.len(), 1 + 3 + 2);
+
+        let resumed = forced.clone().resume((*gen.run)(Random::new_from_seed(3), 30));
+        assert_eq!(resumed.render(3), tree.render(3));
+        let resumed = forced.resume(Tree::leaf(vec![]));
+        assert_eq!(resumed.get_path_or_closest(&path).value, tree.get_path_or_closest(&path).value);
+
+        // A path that doesn't fit only saves as far as it goes
+        let forced = ForcedTree::force_path(&Tree::new(0, || vec![Tree::leaf(1)]), &TreePath::from(vec![0, 5]));
+        assert_eq!(forced.path(), &TreePath::from(vec![0]));
+    }
+
+    #[test]
+    fn checkpoint_resumes() {
+        use std::cell::Cell;
+        use std::panic;
+        use crate::property::{Config, Property};
+
+        let file = std::env::temp_dir().join(format!("hedgehog-checkpoint-{}.json", std::process::id()));
+        let config = Config { shrink_checkpoint : Some(file.clone()), ..Config::default() };
+        let gen = Gen::u64(0..1000);
+        // Stop partway through shrinking, as if interrupted
+        let failures = Cell::new(0);
+        let interrupted = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            Property::new(gen.clone(), |&x| {
+                if x >= 100 {
+                    failures.set(failures.get() + 1);
+                    if failures.get() > 3 {
+                        panic::resume_unwind(Box::new(()));
+                    }
+                }
+                x < 100
+            }).check(&config)
+        }));
+        assert!(interrupted.is_err());
+        let checkpoint = ShrinkCheckpoint::load(&file).unwrap();
+        assert_eq!(checkpoint.path.len(), 2);
+
+        let report = Property::new(gen, |&x| x < 100).check(&config);
+        let cx = &report.counterexamples[0];
+        assert_eq!((cx.minimal, cx.seed, cx.case, cx.size), (100, checkpoint.seed, checkpoint.case, checkpoint.size));
+        assert!(cx.shrinks > checkpoint.path.len());
+        // Nothing to resume once shrinking has finished
+        assert!(!file.exists());
+    }
+}
@@ -0,0 +1,159 @@
+// Export shrink trees as Graphviz DOT, JSON or indented text, down to a bounded depth.
+// Values are rendered with Debug. Nodes that have children past the depth limit, or more children
+// than MAX_CHILDREN, are marked as truncated rather than forced any further.
+use std::fmt::Debug;
+use std::fmt::Write;
+
+use crate::hh3_lazy_tree::Tree;
+
+/// Most children of each node to render
+pub const MAX_CHILDREN : usize = 100;
+
+/// A node of the tree that has been forced for rendering
+struct Node {
+    value : String,
+    children : Vec<Node>,
+    /// Whether the node has more children than were rendered
+    truncated : bool,
+}
+
+fn force<A : Debug>(tree : &Tree<A>, max_depth : usize) -> Node {
+    let value = format!("{:?}", tree.value);
+    let mut children = (*tree.children)();
+    if max_depth == 0 {
+        return Node { value, children : Vec::new(), truncated : children.next().is_some() };
+    }
+    let rendered = children.by_ref().take(MAX_CHILDREN).map(|c| force(&c, max_depth - 1)).collect();
+    Node { value, children : rendered, truncated : children.next().is_some() }
+}
+
+/// Graphviz DOT graph of the tree, to max_depth levels below the root.
+/// Edges are labelled with the child index, as used in shrink paths.
+/// > dot -Tsvg shrinks.dot > shrinks.svg
+pub fn to_dot<A : Debug>(tree : &Tree<A>, max_depth : usize) -> String {
+    let mut out = String::from("digraph shrinks {\n  node [shape=box];\n");
+    let mut next_id = 0;
+    dot_node(&force(tree, max_depth), &mut next_id, &mut out);
+    out.push_str("}\n");
+    out
+}
+
+fn dot_node(node : &Node, next_id : &mut usize, out : &mut String) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+    writeln!(out, "  n{} [label={}];", id, dot_quote(&node.value)).expect("write to string");
+    for (ix, child) in node.children.iter().enumerate() {
+        let child_id = dot_node(child, next_id, out);
+        writeln!(out, "  n{} -> n{} [label=\"{}\"];", id, child_id, ix).expect("write to string");
+    }
+    if node.truncated {
+        let more = *next_id;
+        *next_id += 1;
+        writeln!(out, "  n{} [label=\"...\", shape=plaintext];", more).expect("write to string");
+        writeln!(out, "  n{} -> n{} [style=dashed];", id, more).expect("write to string");
+    }
+    id
+}
+
+/// JSON object for the tree, to max_depth levels below the root, of the form
+/// > {"value": "Debug of value", "children": [...], "truncated": false}
+pub fn to_json<A : Debug>(tree : &Tree<A>, max_depth : usize) -> String {
+    let mut out = String::new();
+    json_node(&force(tree, max_depth), &mut out);
+    out
+}
+
+fn json_node(node : &Node, out : &mut String) {
+    write!(out, "{{\"value\": {}, \"children\": [", json_quote(&node.value)).expect("write to string");
+    for (ix, child) in node.children.iter().enumerate() {
+        if ix > 0 {
+            out.push_str(", ");
+        }
+        json_node(child, out);
+    }
+    write!(out, "], \"truncated\": {}}}", node.truncated).expect("write to string");
+}
+
+/// Indented outline of the tree, to max_depth levels below the root, with two spaces per level:
+/// > 10
+/// >   0
+/// >   5
+/// >     0
+/// >   ...
+pub fn to_text<A : Debug>(tree : &Tree<A>, max_depth : usize) -> String {
+    let mut out = String::new();
+    text_node(&force(tree, max_depth), 0, &mut out);
+    out
+}
+
+fn text_node(node : &Node, depth : usize, out : &mut String) {
+    writeln!(out, "{:indent$}{}", "", node.value, indent = depth * 2).expect("write to string");
+    for child in &node.children {
+        text_node(child, depth + 1, out);
+    }
+    if node.truncated {
+        writeln!(out, "{:indent$}...", "", indent = (depth + 1) * 2).expect("write to string");
+    }
+}
+
+/// Double-quoted string for JSON
+fn json_quote(s : &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).expect("write to string"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Double-quoted string for a DOT label. DOT only escapes quotes, and a backslash followed by
+/// anything else is a label escape, so backslashes are doubled and other control characters are
+/// written out as Rust escapes.
+fn dot_quote(s : &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(out, "\\\\u{{{:x}}}", c as u32).expect("write to string"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::Tree;
+    use crate::tree::render::*;
+
+    fn tree<'a>() -> Tree<'a, &'static str> {
+        Tree::new("a\"b", || vec![Tree::new("c", || vec![Tree::leaf("d")]), Tree::leaf("e")])
+    }
+
+    #[test]
+    fn renders() {
+        assert_eq!(to_json(&tree(), 1),
+            r#"{"value": "\"a\\\"b\"", "children": [{"value": "\"c\"", "children": [], "truncated": true}, {"value": "\"e\"", "children": [], "truncated": false}], "truncated": false}"#);
+
+        let dot = to_dot(&tree(), 2);
+        assert!(dot.starts_with("digraph shrinks {") && dot.ends_with("}\n"));
+        assert!(dot.contains("n1 -> n2 [label=\"0\"]") && dot.contains("n0 -> n3 [label=\"1\"]"), "{}", dot);
+        assert!(!dot.contains("..."));
+        assert!(to_dot(&tree(), 0).contains("n0 -> n1 [style=dashed]"));
+
+        assert_eq!(dot_quote("\"\\\n\u{1}"), r#""\"\\\n\\u{1}""#);
+        assert_eq!(json_quote("\"\\\n\u{1}"), r#""\"\\\n\u0001""#);
+
+        assert_eq!(to_text(&tree(), 1), "\"a\\\"b\"\n  \"c\"\n    ...\n  \"e\"\n");
+    }
+}
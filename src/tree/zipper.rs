@@ -0,0 +1,172 @@
+// Cursor for walking around a shrink tree without looking up each node from the root.
+// The zipper keeps the nodes above the focus, and the children of each of them that have been
+// forced so far, so moving up, down or sideways doesn't recompute anything already seen.
+use crate::hh3_lazy_tree::{Children, Tree, TreePath};
+
+/// Children of a node that have been forced so far, and the iterator for the rest
+struct Forced<'a, A> {
+    forced : Vec<Tree<'a, A>>,
+    rest : Children<'a, A>,
+}
+
+impl<'a, A : Clone> Forced<'a, A> {
+    fn new(tree : &Tree<'a, A>) -> Forced<'a, A> {
+        Forced { forced : Vec::new(), rest : (*tree.children)() }
+    }
+
+    /// The child at ix, forcing the children up to it if they haven't been already
+    fn get(&mut self, ix : usize) -> Option<Tree<'a, A>> {
+        while self.forced.len() <= ix {
+            self.forced.push(self.rest.next()?);
+        }
+        Some(self.forced[ix].clone())
+    }
+}
+
+/// A node above the focus: the node itself, its children, and which child the path went through
+struct Crumb<'a, A> {
+    node : Tree<'a, A>,
+    children : Forced<'a, A>,
+    ix : usize,
+}
+
+/// Position in a tree, with the path back up to the root
+/// > let mut z = TreeZipper::new(tree);
+/// > z.down(0); z.right(); z.up();
+pub struct TreeZipper<'a, A> {
+    focus : Tree<'a, A>,
+    /// Children of the focus, if they've been forced before
+    focus_children : Option<Forced<'a, A>>,
+    /// Nodes from the root down to the parent of the focus
+    crumbs : Vec<Crumb<'a, A>>,
+}
+
+impl<'a, A : Clone> TreeZipper<'a, A> {
+    /// Zipper focused on the root of the tree
+    pub fn new(tree : Tree<'a, A>) -> TreeZipper<'a, A> {
+        TreeZipper { focus : tree, focus_children : None, crumbs : Vec::new() }
+    }
+
+    /// Node at the focus
+    pub fn focus(&self) -> &Tree<'a, A> {
+        &self.focus
+    }
+
+    pub fn value(&self) -> &A {
+        &self.focus.value
+    }
+
+    /// Number of steps from the root to the focus
+    pub fn depth(&self) -> usize {
+        self.crumbs.len()
+    }
+
+    /// Path from the root to the focus
+    pub fn path(&self) -> TreePath {
+        TreePath::from(self.crumbs.iter().map(|c| c.ix).collect::<Vec<usize>>())
+    }
+
+    /// Move to child ix of the focus. Returns false and stays put if there's no such child.
+    pub fn down(&mut self, ix : usize) -> bool {
+        let focus = &self.focus;
+        let children = self.focus_children.get_or_insert_with(|| Forced::new(focus));
+        match children.get(ix) {
+            None => false,
+            Some(child) => {
+                let children = self.focus_children.take().expect("focus children were just forced");
+                let node = std::mem::replace(&mut self.focus, child);
+                self.crumbs.push(Crumb { node, children, ix });
+                true
+            }
+        }
+    }
+
+    /// Move to the parent of the focus. Returns false if the focus is the root.
+    pub fn up(&mut self) -> bool {
+        match self.crumbs.pop() {
+            None => false,
+            Some(crumb) => {
+                self.focus = crumb.node;
+                self.focus_children = Some(crumb.children);
+                true
+            }
+        }
+    }
+
+    /// Move to the next sibling of the focus. Returns false if there isn't one.
+    pub fn right(&mut self) -> bool {
+        self.sibling(|ix| ix.checked_add(1))
+    }
+
+    /// Move to the previous sibling of the focus. Returns false if there isn't one.
+    pub fn left(&mut self) -> bool {
+        self.sibling(|ix| ix.checked_sub(1))
+    }
+
+    fn sibling(&mut self, step : impl Fn(usize) -> Option<usize>) -> bool {
+        let crumb = match self.crumbs.last_mut() {
+            None => return false,
+            Some(crumb) => crumb,
+        };
+        match step(crumb.ix).and_then(|ix| Some((ix, crumb.children.get(ix)?))) {
+            None => false,
+            Some((ix, sibling)) => {
+                crumb.ix = ix;
+                self.focus = sibling;
+                self.focus_children = None;
+                true
+            }
+        }
+    }
+
+    /// Move back up to the root
+    pub fn top(&mut self) {
+        while self.up() {}
+    }
+
+    /// Follow a path down from the focus, stopping where it no longer fits.
+    /// Returns how many steps were taken, as with Tree::get_path_prefix.
+    pub fn follow(&mut self, path : &TreePath) -> usize {
+        path.indices().iter().take_while(|&&ix| self.down(ix)).count()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::{Tree, TreePath};
+    use crate::tree::zipper::*;
+
+    #[test]
+    fn moves() {
+        let tree = Tree::new(0, || vec![Tree::new(1, || vec![Tree::leaf(3)]), Tree::leaf(2)]);
+        let mut z = TreeZipper::new(tree);
+        assert!(!z.up() && !z.right() && !z.down(2));
+        assert!(z.down(0) && z.down(0));
+        assert_eq!((*z.value(), z.depth(), z.path().to_string()), (3, 2, "0.0".to_string()));
+        assert!(!z.right() && z.up() && z.right() && !z.right());
+        assert_eq!((*z.value(), z.path().to_string()), (2, "1".to_string()));
+        assert!(z.left() && !z.left());
+        assert_eq!(*z.value(), 1);
+
+        z.top();
+        assert_eq!(z.follow(&TreePath::from(vec![0, 0, 5])), 2);
+        assert_eq!(*z.value(), 3);
+    }
+
+    #[test]
+    fn forces_children_once() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+        let forced = Rc::new(Cell::new(0));
+        let counter = forced.clone();
+        // Not built with Tree::new, so nothing caches the children except the zipper
+        let tree = Tree::from_children(0u64, Rc::new(move || {
+            counter.set(counter.get() + 1);
+            Box::new((1..4u64).map(Tree::leaf)) as Children<u64>
+        }));
+        let mut z = TreeZipper::new(tree);
+        assert!(z.down(0) && z.right() && z.right() && z.up() && z.down(1) && z.left());
+        assert_eq!(forced.get(), 1);
+    }
+}
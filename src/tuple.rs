@@ -0,0 +1,89 @@
+use crate::hh3_lazy_tree::Gen;
+
+/// Tuples of generators, up to twelve elements, which can be turned into a generator of tuples.
+/// The generated tuple shrinks each element in turn, as with Gen::zip:
+/// > tuple::gen((Gen::u64(0..10), Gen::usize(0..5), Gen::choose(vec!['a', 'b'])))
+pub trait Tuple<'a> {
+    type Value;
+
+    fn gen(self) -> Gen<'a, Self::Value>;
+}
+
+/// Generate a tuple with one value from each generator
+pub fn gen<'a, T : Tuple<'a>>(gens : T) -> Gen<'a, T::Value> {
+    gens.gen()
+}
+
+/// Right-nested zip of generators: nested_zip!(a, b, c) is a.zip(b.zip(c))
+macro_rules! nested_zip {
+    ($gen:expr) => { $gen };
+    ($gen:expr, $($rest:expr),+) => { $gen.zip(nested_zip!($($rest),+)) };
+}
+
+/// Pattern for the right-nested pairs produced by nested_zip!
+macro_rules! nested_pat {
+    ($val:ident) => { $val };
+    ($val:ident, $($rest:ident),+) => { ($val, nested_pat!($($rest),+)) };
+}
+
+macro_rules! impl_tuple {
+    ($($gen:ident $val:ident : $ty:ident),*) => {
+        impl<'a, $($ty),*> Tuple<'a> for ($(Gen<'a, $ty>,)*)
+        where $($ty : Clone + 'a),* {
+            type Value = ($($ty,)*);
+
+            fn gen(self) -> Gen<'a, Self::Value> {
+                let ($($gen,)*) = self;
+                nested_zip!($($gen),*).map(|nested_pat!($($val),*)| ($($val,)*))
+            }
+        }
+    }
+}
+
+impl_tuple!(ga a : A);
+impl_tuple!(ga a : A, gb b : B);
+impl_tuple!(ga a : A, gb b : B, gc c : C);
+impl_tuple!(ga a : A, gb b : B, gc c : C, gd d : D);
+impl_tuple!(ga a : A, gb b : B, gc c : C, gd d : D, ge e : E);
+impl_tuple!(ga a : A, gb b : B, gc c : C, gd d : D, ge e : E, gf f : F);
+impl_tuple!(ga a : A, gb b : B, gc c : C, gd d : D, ge e : E, gf f : F, gg g : G);
+impl_tuple!(ga a : A, gb b : B, gc c : C, gd d : D, ge e : E, gf f : F, gg g : G, gh h : H);
+impl_tuple!(ga a : A, gb b : B, gc c : C, gd d : D, ge e : E, gf f : F, gg g : G, gh h : H, gi i : I);
+impl_tuple!(ga a : A, gb b : B, gc c : C, gd d : D, ge e : E, gf f : F, gg g : G, gh h : H, gi i : I, gj j : J);
+impl_tuple!(ga a : A, gb b : B, gc c : C, gd d : D, ge e : E, gf f : F, gg g : G, gh h : H, gi i : I, gj j : J, gk k : K);
+impl_tuple!(ga a : A, gb b : B, gc c : C, gd d : D, ge e : E, gf f : F, gg g : G, gh h : H, gi i : I, gj j : J, gk k : K, gl l : L);
+
+
+#[cfg(test)]
+mod test {
+    use crate::hh3_lazy_tree::Gen;
+    use crate::random::Random;
+    use crate::tuple;
+
+    #[test]
+    fn shrinks_each_element() {
+        let gen = tuple::gen((Gen::u64(10..20), Gen::u64(30..40), Gen::u64(50..60)));
+        let tree = (*gen.run)(Random::new_from_seed(1), 0);
+        let (a, b, c) = tree.value;
+
+        // Every child changes exactly one element, and they're in order
+        let mut changed = Vec::new();
+        for child in (*tree.children)() {
+            let (a1, b1, c1) = child.value;
+            let diff = [a1 != a, b1 != b, c1 != c];
+            assert_eq!(diff.iter().filter(|&&d| d).count(), 1);
+            changed.push(diff.iter().position(|&d| d).unwrap());
+        }
+        let mut sorted = changed.clone();
+        sorted.sort();
+        assert_eq!(changed, sorted);
+    }
+
+    #[test]
+    fn zip_enumerates_product() {
+        let gen = Gen::u64(0..3).zip(Gen::choose(vec![false, true]));
+        let values = (*gen.enumerate)(10).unwrap().to_vec();
+        assert_eq!(values, vec![(0, false), (0, true), (1, false), (1, true), (2, false), (2, true)]);
+        assert!((*gen.enumerate)(5).is_none());
+    }
+}